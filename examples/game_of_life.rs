@@ -31,8 +31,11 @@ impl GameOfLife {
         let p1 = Vector3::new(-size_ratio / 2.0, -size_ratio / 2.0, 0.0);
         let p2 = Vector3::new(size_ratio / 2.0, size_ratio / 2.0, 1.0);
 
-        let shape_alive = Shape::rect(p1, p2, ALIVE, vec![]);
-        let shape_dead = Shape::rect(p1, p2, DEAD, vec![]);
+        // Far away, only the front face of the cells is drawn
+        let shape_alive = Shape::rect(p1, p2, ALIVE, vec![])
+            .with_lod(Shape::quad(p1, p2, ALIVE, vec![]), LOD_SIZE);
+        let shape_dead = Shape::rect(p1, p2, DEAD, vec![])
+            .with_lod(Shape::quad(p1, p2, DEAD, vec![]), LOD_SIZE);
         let delta = 0.5;
         let shape_frame = Shape::rect(
             Vector3::new(-delta, -delta, 0.01),
@@ -157,6 +160,8 @@ impl Scene for GameOfLife {
 const GRID_SIZE: usize = 300;
 const TIME_STEP: f32 = 0.0 / 20.0;
 const BOX_SIZE: f32 = 20.0;
+/// Projected size under which the cells are drawn as quads
+const LOD_SIZE: f32 = 0.05;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = Config::init();
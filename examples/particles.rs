@@ -0,0 +1,96 @@
+use std::{cell::RefCell, rc::Rc};
+
+use test_wgpu::{
+    App, Config, Logger, Point, PointCloud, PointShape, PointSizing, Scene,
+};
+
+const GALAXY_POINTS: usize = 50_000;
+const PARTICLES: usize = 2_000;
+
+/// A static spiral galaxy sized in world units, and particles sized in
+/// pixels falling around it
+struct Particles {
+    galaxy: Rc<RefCell<PointCloud>>,
+    particles: Rc<RefCell<PointCloud>>,
+    velocities: Vec<[f32; 3]>,
+}
+
+impl Particles {
+    fn new() -> Self {
+        let galaxy = (0..GALAXY_POINTS)
+            .map(|i| {
+                let arm = (i % 3) as f32 * std::f32::consts::TAU / 3.0;
+                let distance = rand::random::<f32>().sqrt() * 15.0;
+                let angle = arm + distance * 0.4 + rand::random::<f32>() * 0.5;
+                let height = (rand::random::<f32>() - 0.5) * 0.5;
+                let heat = 1.0 - distance / 15.0;
+                Point::new(
+                    [distance * angle.cos(), height, distance * angle.sin()],
+                    [0.5 + heat * 0.5, 0.5 + heat * 0.3, 1.0 - heat * 0.5],
+                    0.08,
+                )
+            })
+            .collect();
+        let galaxy = PointCloud::new(galaxy)
+            .with_sizing(PointSizing::World)
+            .with_shape(PointShape::Circle);
+
+        let particles =
+            PointCloud::new((0..PARTICLES).map(|_| Self::spawn()).collect())
+                .with_sizing(PointSizing::Screen)
+                .with_shape(PointShape::Square);
+
+        Self {
+            galaxy: Rc::new(RefCell::new(galaxy)),
+            particles: Rc::new(RefCell::new(particles)),
+            velocities: (0..PARTICLES)
+                .map(|_| [0.0, -rand::random::<f32>() * 3.0, 0.0])
+                .collect(),
+        }
+    }
+
+    fn spawn() -> Point {
+        Point::new(
+            [
+                (rand::random::<f32>() - 0.5) * 30.0,
+                10.0 + rand::random::<f32>() * 10.0,
+                (rand::random::<f32>() - 0.5) * 30.0,
+            ],
+            [1.0, 1.0, 1.0],
+            3.0,
+        )
+    }
+}
+
+impl Scene for Particles {
+    fn update(&mut self, dt: std::time::Duration) {
+        let dt = dt.as_secs_f32();
+        let mut particles = self.particles.borrow_mut();
+        for (point, velocity) in
+            particles.points_mut().iter_mut().zip(&self.velocities)
+        {
+            for (position, speed) in point.position.iter_mut().zip(velocity) {
+                *position += speed * dt;
+            }
+            if point.position[1] < -10.0 {
+                *point = Self::spawn();
+            }
+        }
+    }
+
+    fn point_clouds(&self) -> Vec<Rc<RefCell<PointCloud>>> {
+        vec![self.galaxy.clone(), self.particles.clone()]
+    }
+}
+
+fn main() {
+    let config = Config::init();
+    Logger::setup(config.disable_egui).expect("Failed to setup logger");
+    let mut app = App::new(config);
+    app.add_scene(Box::new(Particles::new()));
+
+    pollster::block_on(
+        app.run(include_str!("../src/graphics/shaders/shader.wgsl")),
+    )
+    .expect("Failed to run the application");
+}
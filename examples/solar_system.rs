@@ -0,0 +1,139 @@
+use std::{cell::RefCell, rc::Rc};
+
+use cgmath::{Rotation, Rotation3, Vector3};
+use test_wgpu::{
+    debug_aabb, debug_label, debug_line, debug_polyline, debug_text, hud_text,
+    Aabb, App, Config, LineStyle, Logger, Node, Polyline, Scene, SceneGraph,
+    Shape, Text,
+};
+
+struct SolarSystem {
+    graph: SceneGraph,
+    sun: Rc<RefCell<Node>>,
+    earth_orbit: Rc<RefCell<Node>>,
+    moon_orbit: Rc<RefCell<Node>>,
+    angle: f32,
+}
+
+impl SolarSystem {
+    fn new() -> Self {
+        let cube = |color| {
+            Rc::new(RefCell::new(Shape::rect(
+                Vector3::new(-0.5, -0.5, -0.5),
+                Vector3::new(0.5, 0.5, 0.5),
+                color,
+                vec![],
+            )))
+        };
+
+        let sun = Node::with_shape("sun", cube([1.0, 0.8, 0.0]));
+        sun.borrow_mut().set_scale([3.0, 3.0, 3.0]);
+
+        // Orbits are empty nodes, rotating them moves every child
+        let earth_orbit = Node::new("earth orbit");
+        let earth = Node::with_shape("earth", cube([0.0, 0.3, 1.0]));
+        earth.borrow_mut().set_translation([8.0, 0.0, 0.0]);
+
+        let moon_orbit = Node::new("moon orbit");
+        let moon = Node::with_shape("moon", cube([0.7, 0.7, 0.7]));
+        moon.borrow_mut().set_translation([2.0, 0.0, 0.0]);
+        moon.borrow_mut().set_scale([0.4, 0.4, 0.4]);
+
+        moon_orbit.borrow_mut().add_child(moon);
+        earth.borrow_mut().add_child(moon_orbit.clone());
+        earth_orbit.borrow_mut().add_child(earth);
+
+        let mut graph = SceneGraph::new();
+        graph.add(sun.clone()).add(earth_orbit.clone());
+
+        Self {
+            graph,
+            sun,
+            earth_orbit,
+            moon_orbit,
+            angle: 0.0,
+        }
+    }
+}
+
+impl Scene for SolarSystem {
+    fn update(&mut self, dt: std::time::Duration) {
+        self.angle += dt.as_secs_f32();
+
+        self.sun
+            .borrow_mut()
+            .set_rotation(cgmath::Quaternion::from_angle_y(cgmath::Rad(
+                self.angle * 0.2,
+            )));
+        self.earth_orbit.borrow_mut().set_rotation(
+            cgmath::Quaternion::from_angle_y(cgmath::Rad(self.angle * 0.5)),
+        );
+        self.moon_orbit.borrow_mut().set_rotation(
+            cgmath::Quaternion::from_angle_y(cgmath::Rad(self.angle * 2.0)),
+        );
+
+        self.graph.update();
+
+        // Debug lines are cleared every frame, so they are queued again here
+        let orbit = (0..64).fold(
+            Polyline::new(1.5)
+                .with_style(LineStyle::Dashed {
+                    dash: 8.0,
+                    gap: 6.0,
+                })
+                .with_closed(true),
+            |orbit, i| {
+                let angle = i as f32 / 64.0 * std::f32::consts::TAU;
+                orbit.with_point(
+                    [8.0 * angle.cos(), 0.0, 8.0 * angle.sin()],
+                    [0.4, 0.4, 0.6],
+                )
+            },
+        );
+        debug_polyline(&orbit);
+
+        let earth =
+            cgmath::Quaternion::from_angle_y(cgmath::Rad(self.angle * 0.5))
+                .rotate_vector(Vector3::new(8.0, 0.0, 0.0));
+        debug_line(Vector3::new(0.0, 0.0, 0.0), earth, [1.0, 0.8, 0.0]);
+        debug_aabb(
+            &Aabb::new(
+                Vector3::new(-1.6, -1.6, -1.6),
+                Vector3::new(1.6, 1.6, 1.6),
+            ),
+            [1.0, 1.0, 1.0],
+        );
+
+        debug_text(
+            Text::world([0.0, 0.0, 0.0], "Sun")
+                .with_offset([0.0, -30.0])
+                .with_color([1.0, 0.8, 0.0]),
+        );
+        debug_label(
+            earth + Vector3::new(0.0, 1.0, 0.0),
+            &format!("Earth ({:.1}, {:.1})", earth.x, earth.z),
+            [0.4, 0.6, 1.0],
+        );
+        hud_text(
+            [10.0, 10.0],
+            &format!("t = {:.1}s", self.angle),
+            [1.0, 1.0, 1.0],
+        );
+    }
+
+    fn shapes(&self) -> Vec<Rc<RefCell<Shape>>> {
+        self.graph.shapes()
+    }
+}
+
+fn main() {
+    let config = Config::init();
+    Logger::setup(config.disable_egui).expect("Failed to setup logger");
+    let mut app = App::new(config);
+    app.add_scene(Box::new(SolarSystem::new()));
+
+    pollster::block_on(
+        app.run(include_str!("../src/graphics/shaders/shader.wgsl")),
+    )
+    .expect("Failed to run the application");
+}
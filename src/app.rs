@@ -1,15 +1,31 @@
-use std::{ops::RangeInclusive, time::Duration};
+use std::{cell::RefCell, ops::RangeInclusive, sync::Arc, time::Duration};
+
+use clap::ValueEnum as _;
+use winit::event_loop::ControlFlow;
 
 use crate::{
+    benchmark::Benchmark,
+    config::{
+        BackgroundMode, FullscreenMode, RedrawMode, VideoModeSelection,
+        WindowSizeConfig, WindowSizeHint,
+    },
     debug::{self, ColorRef as _},
     elapsed_handler,
+    graphics::{self, profiler::GpuPass},
+    input::{Action, Input, InputEvent},
+    recording::{Recorder, Replay},
+    render,
     scene::Scene,
-    Config, Renderer,
+    utils::{spin_until, FramePacer, Pacing},
+    Config, Error, Renderer, WindowConfig, WindowContent,
 };
 
 pub struct App {
     config: Option<Config>,
     scenes: Vec<Box<dyn Scene>>,
+    /// Windows opened next to the main one, with their scenes
+    windows: Vec<(WindowConfig, Vec<Box<dyn Scene>>)>,
+    post_effects: Vec<(String, &'static str, [f32; 4])>,
 }
 
 impl App {
@@ -17,6 +33,8 @@ impl App {
         Self {
             config: Some(config),
             scenes: Vec::new(),
+            windows: Vec::new(),
+            post_effects: Vec::new(),
         }
     }
 
@@ -24,20 +42,88 @@ impl App {
         self.scenes.push(scene);
     }
 
-    pub async fn run(
+    /// Open another window when the application starts, drawn with the
+    /// device of the main window
+    ///
+    /// Returns the index of the window for `add_scene_to`, the main window
+    /// being 0.
+    pub fn add_window(&mut self, config: WindowConfig) -> usize {
+        self.windows.push((config, Vec::new()));
+        self.windows.len()
+    }
+
+    /// Add a scene to a window returned by `add_window`, or to the main
+    /// window with 0
+    pub fn add_scene_to(
         &mut self,
-        shader: &'static str,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        let config = self.config.take().ok_or("Config not set")?;
+        window: usize,
+        scene: Box<dyn Scene>,
+    ) -> Result<(), Error> {
+        match window.checked_sub(1) {
+            None => self.scenes.push(scene),
+            Some(index) => self
+                .windows
+                .get_mut(index)
+                .ok_or(Error::WindowNotFound(window))?
+                .1
+                .push(scene),
+        }
+        Ok(())
+    }
+
+    /// Append a custom WGSL effect to the post-processing chain
+    ///
+    /// The source only defines `fs_main`, the bindings and the vertex shader
+    /// are declared in `graphics/shaders/post/common.wgsl`. The effect is
+    /// created when the application starts, `run` returns `Error::Shader`
+    /// if it does not compile.
+    pub fn add_post_effect<S: ToString>(
+        &mut self,
+        name: S,
+        source: &'static str,
+        params: [f32; 4],
+    ) {
+        self.post_effects.push((name.to_string(), source, params));
+    }
+
+    pub async fn run(&mut self, shader: &'static str) -> Result<(), Error> {
+        let mut config = self.config.take().ok_or(Error::NoConfig)?;
         let event_loop = winit::event_loop::EventLoop::new()?;
-        let window = winit::window::WindowBuilder::new()
-            .with_title(&config.window_title)
-            .build(&event_loop)?;
+        let window = Arc::new(
+            winit::window::WindowBuilder::new()
+                .with_title(&config.window_title)
+                .build(&event_loop)?,
+        );
+        // The other windows start from the configuration of the main one
+        let window_config = config.clone();
+        let settings_file = config.settings_file();
+        let debug_enabled = !config.disable_egui;
+        let detached = config.debug.detached && debug_enabled;
+        config.disable_egui |= detached;
 
         let mut wgpu_update = Duration::from_nanos(0);
         let mut egui_update = Duration::from_nanos(0);
         let mut wgpu_redraw = Duration::from_nanos(0);
         let mut egui_redraw = Duration::from_nanos(0);
+        let mut benchmark = config.benchmark.clone().map(Benchmark::new);
+        let mut recorder =
+            config.record.as_deref().map(Recorder::create).transpose()?;
+        let mut replay =
+            config.replay.as_deref().map(Replay::open).transpose()?;
+        // The benchmark moves the camera and the replay gives its inputs on
+        // every frame
+        let redraw = if benchmark.is_some() || replay.is_some() {
+            RedrawMode::Continuous
+        } else {
+            config.redraw
+        };
+        let mut pacer = FramePacer::new(redraw, config.max_fps);
+        let reactive = debug::widget::Value::new(
+            redraw == RedrawMode::Reactive,
+            "Reactive Redraw",
+        );
+        let max_fps =
+            debug::widget::Slider::new(config.max_fps, 0..=240, "Max FPS");
 
         let frame_time_label = debug::widget::Label::new(
             (String::new(), Duration::from_nanos(0), 0.0),
@@ -66,6 +152,39 @@ impl App {
             ],
         );
 
+        // GPU time of each pass, measured with timestamp queries
+        let gpu_time_label = debug::widget::Label::new(
+            None,
+            |timings: &Option<[f32; GpuPass::ALL.len()]>| match timings {
+                Some(timings) => format!(
+                    "GPU Time: {:.03}ms | {}",
+                    timings.iter().sum::<f32>(),
+                    GpuPass::ALL
+                        .iter()
+                        .zip(timings)
+                        .map(|(pass, time)| format!(
+                            "{}: {:.03}ms",
+                            pass.name(),
+                            time
+                        ))
+                        .collect::<Vec<_>>()
+                        .join(" | ")
+                ),
+                None => "GPU Time: timestamp queries not supported".to_string(),
+            },
+        );
+        let gpu_time = debug::widget::BarChart::new(
+            [[0.0; GpuPass::ALL.len()]; 100],
+            GpuPass::ALL.map(|pass| format!("GPU {}", pass.name())),
+            [
+                egui::Color32::DARK_GRAY,
+                egui::Color32::BLUE,
+                egui::Color32::GOLD,
+                egui::Color32::LIGHT_BLUE,
+                egui::Color32::GREEN,
+            ],
+        );
+
         let color = debug::widget::ColorPicker::new(
             debug::RGBA {
                 red: 0.1,
@@ -76,17 +195,17 @@ impl App {
             "Background Color",
         );
         let camera_speed = debug::widget::Slider::new(
-            10.0,
+            config.camera.speed,
             RangeInclusive::new(0.1, 20.0),
             "Camera Speed",
         );
         let camera_sensitivity = debug::widget::Slider::new(
-            2.0,
+            config.camera.sensitivity,
             RangeInclusive::new(0.1, 5.0),
             "Camera Sensitivity",
         );
         let camera_zoom_sensitivity = debug::widget::Slider::new(
-            2.5,
+            config.camera.zoom_sensitivity,
             RangeInclusive::new(0.1, 5.0),
             "Camera Zoom Sensitivity",
         );
@@ -103,6 +222,12 @@ impl App {
             },
         );
 
+        let culling = debug::widget::Value::new(true, "Frustum Culling");
+        let culling_label =
+            debug::widget::Label::new((0u32, 0u32), |(visible, total)| {
+                format!("[Culling] visible instances: {} / {}", visible, total)
+            });
+
         // let outter_rect = Shape::rect(
         //     Vector3::new(1.0, 1.0, 1.0),
         //     Vector3::new(0.0, 0.0, 0.0),
@@ -120,12 +245,214 @@ impl App {
         //     ],
         // );
 
-        let mut renderer = { Renderer::new(&window, config, shader).await? };
+        let mut renderer = { Renderer::new(window, config, shader).await? };
+        // The interface of the main window is recorded with its frames
+        if let (Some(debug_renderer), true) =
+            (renderer.debug_renderer.as_mut(), recorder.is_some())
+        {
+            debug_renderer.set_recording(true);
+        }
+        if detached && (recorder.is_some() || replay.is_some()) {
+            log::warn!(
+                "The detached debug window is not recorded nor replayed"
+            );
+        }
+        // Window showing the debug interface of the main window
+        let mut debug_host = match detached {
+            true => Some(SecondaryWindow::open(
+                &event_loop,
+                &renderer,
+                &window_config,
+                WindowConfig::new(format!(
+                    "{} - Debug",
+                    window_config.window_title
+                ))
+                .with_content(WindowContent::Interface)
+                .with_debug(true),
+                Vec::new(),
+            )?),
+            false => None,
+        };
 
         // *app.shapes_mut() = vec![outter_rect, innert_rect];
         // app.load_shapes();
 
-        renderer.update_size();
+        for (name, source, params) in self.post_effects.iter() {
+            renderer.add_post_effect(name, source, *params)?;
+        }
+        let post_chain = debug::widget::OrderedList::new(
+            renderer
+                .pipeline
+                .post
+                .effects()
+                .iter()
+                .map(|effect| (effect.name().to_string(), effect.enabled()))
+                .collect(),
+            "Post Processing",
+        );
+        let tonemap_aces = debug::widget::Value::new(
+            true,
+            "ACES Tonemapping (Reinhard otherwise)",
+        );
+        let exposure = debug::widget::Slider::new(
+            1.0,
+            RangeInclusive::new(0.1, 5.0),
+            "Exposure",
+        );
+
+        let shadows = debug::widget::Value::new(
+            renderer.pipeline.shadow.enabled(),
+            "Shadows",
+        );
+        let shadow_bias = debug::widget::Slider::new(
+            renderer.pipeline.shadow.bias(),
+            RangeInclusive::new(0.0, 0.05),
+            "Shadow Bias",
+        );
+        let shadow_resolution = debug::widget::Slider::new(
+            renderer.pipeline.shadow.resolution(),
+            RangeInclusive::new(
+                256,
+                8192.min(renderer.pipeline.capabilities.max_texture_size()),
+            ),
+            "Shadow Resolution",
+        );
+        let present_mode = debug::widget::Choice::new(
+            renderer
+                .present_modes()
+                .iter()
+                .map(|mode| format!("{:?}", mode))
+                .collect(),
+            renderer
+                .present_modes()
+                .iter()
+                .position(|mode| *mode == renderer.present_mode())
+                .unwrap_or_default(),
+            "Present Mode",
+        );
+        // Index 0 of the monitors and the video modes is the default one
+        let monitors = renderer.available_monitors();
+        let monitor = debug::widget::Choice::new(
+            std::iter::once("Monitor of the window".to_string())
+                .chain(monitors.iter().map(render::describe_monitor))
+                .collect(),
+            renderer
+                .display()
+                .monitor
+                .as_deref()
+                .and_then(|select| render::find_monitor(&monitors, select))
+                .map_or(0, |index| index + 1),
+            "Monitor",
+        );
+        let video_mode_options = |renderer: &Renderer| {
+            let video_modes = renderer
+                .fullscreen_monitor()
+                .ok()
+                .flatten()
+                .map(|monitor| monitor.video_modes().collect::<Vec<_>>())
+                .unwrap_or_default();
+            let selected = renderer
+                .display()
+                .video_mode
+                .and_then(|selection| {
+                    render::find_video_mode(&video_modes, Some(selection))
+                })
+                .map_or(0, |index| index + 1);
+            let options = std::iter::once("Largest".to_string())
+                .chain(video_modes.iter().map(render::describe_video_mode))
+                .collect::<Vec<_>>();
+            (options, selected)
+        };
+        let (options, selected) = video_mode_options(&renderer);
+        let video_mode =
+            debug::widget::Choice::new(options, selected, "Video Mode");
+        let fullscreen_modes = FullscreenMode::value_variants();
+        let fullscreen = debug::widget::Choice::new(
+            fullscreen_modes
+                .iter()
+                .map(|mode| format!("{:?}", mode))
+                .collect(),
+            fullscreen_modes
+                .iter()
+                .position(|mode| *mode == renderer.display().fullscreen)
+                .unwrap_or_default(),
+            "Fullscreen",
+        );
+        let frame_latency = debug::widget::Slider::new(
+            renderer.frame_latency(),
+            crate::config::FRAME_LATENCIES,
+            "Frame Latency",
+        );
+        let shadow_preview = debug::widget::Image::new(
+            match debug_host.as_mut() {
+                Some(host) => {
+                    host.renderer.register_shadow_preview(Some(&renderer))
+                }
+                None => renderer.register_shadow_preview(None),
+            },
+            [256.0, 256.0],
+            "Shadow Map",
+        );
+
+        let wireframe = debug::widget::Value::new(false, "Wireframe");
+        let indirect = debug::widget::Value::new(
+            renderer.pipeline.batch.enabled(),
+            "Indirect Draw",
+        );
+        let draw_label = debug::widget::Label::new(
+            (renderer.pipeline.batch.mode(), 0usize),
+            |(mode, draws)| {
+                format!("[Draw] mode: {:?}, draws: {}", mode, draws)
+            },
+        );
+
+        let grid = debug::widget::Value::new(
+            renderer.pipeline.helpers.grid(),
+            "Ground Grid",
+        );
+        let axes = debug::widget::Value::new(
+            renderer.pipeline.helpers.axes(),
+            "World Axes",
+        );
+        let gizmo = debug::widget::Value::new(
+            renderer.pipeline.helpers.gizmo(),
+            "Orientation Gizmo",
+        );
+
+        let background_modes = BackgroundMode::value_variants();
+        let background_mode = debug::widget::Choice::new(
+            background_modes
+                .iter()
+                .filter_map(|mode| mode.to_possible_value())
+                .map(|value| value.get_name().to_string())
+                .collect(),
+            background_modes
+                .iter()
+                .position(|mode| *mode == renderer.pipeline.background.mode())
+                .unwrap_or(0),
+            "Background Mode",
+        );
+        let (top, bottom) = renderer.pipeline.background.gradient();
+        let gradient_top = debug::widget::ColorPicker::new(
+            debug::RGB {
+                red: top[0],
+                green: top[1],
+                blue: top[2],
+            },
+            "Gradient Top",
+        );
+        let gradient_bottom = debug::widget::ColorPicker::new(
+            debug::RGB {
+                red: bottom[0],
+                green: bottom[1],
+                blue: bottom[2],
+            },
+            "Gradient Bottom",
+        );
+
+        if let Err(error) = renderer.update_size() {
+            log::warn!("Failed to apply the window size: {}", error);
+        }
         renderer.pipeline.set_background({
             let color = color.borrow().get().into_rgba();
             wgpu::Color {
@@ -138,7 +465,21 @@ impl App {
 
         renderer.debug().add_debug_item(frame_time_label.clone());
         renderer.debug().add_debug_item(frame_time.clone());
+        renderer.debug().add_debug_item(gpu_time_label.clone());
+        if renderer.pipeline.profiler.supported() {
+            renderer.debug().add_debug_item(gpu_time.clone());
+        }
+        renderer.debug().add_debug_item(reactive.clone());
+        renderer.debug().add_debug_item(max_fps.clone());
+        renderer.debug().add_debug_item(monitor.clone());
+        renderer.debug().add_debug_item(video_mode.clone());
+        renderer.debug().add_debug_item(fullscreen.clone());
+        renderer.debug().add_debug_item(present_mode.clone());
+        renderer.debug().add_debug_item(frame_latency.clone());
         renderer.debug().add_debug_item(color.clone());
+        renderer.debug().add_debug_item(background_mode.clone());
+        renderer.debug().add_debug_item(gradient_top.clone());
+        renderer.debug().add_debug_item(gradient_bottom.clone());
         renderer.debug().add_debug_item(camera_speed.clone());
         renderer.debug().add_debug_item(camera_sensitivity.clone());
         renderer
@@ -146,8 +487,51 @@ impl App {
             .add_debug_item(camera_zoom_sensitivity.clone());
         renderer.debug().add_debug_item(reset_camera_button.clone());
         renderer.debug().add_debug_item(camera_info_label.clone());
+        renderer.debug().add_debug_item(grid.clone());
+        renderer.debug().add_debug_item(axes.clone());
+        renderer.debug().add_debug_item(gizmo.clone());
+        renderer.debug().add_debug_item(culling.clone());
+        renderer.debug().add_debug_item(culling_label.clone());
+        if renderer.pipeline.capabilities.wireframe() {
+            renderer.debug().add_debug_item(wireframe.clone());
+        }
+        renderer.debug().add_debug_item(indirect.clone());
+        renderer.debug().add_debug_item(draw_label.clone());
+        renderer.debug().add_debug_item(shadows.clone());
+        renderer.debug().add_debug_item(shadow_bias.clone());
+        renderer.debug().add_debug_item(shadow_resolution.clone());
+        renderer.debug().add_debug_item(shadow_preview.clone());
+        let viewports = match debug_host.as_mut() {
+            Some(host) => host.renderer.register_viewports(Some(&renderer)),
+            None => renderer.register_viewports(None),
+        };
+        for (name, texture) in viewports {
+            let image =
+                debug::widget::Image::new(texture, [256.0, 256.0], name);
+            renderer.debug().add_debug_item(image);
+        }
+        renderer.debug().add_debug_item(post_chain.clone());
+        renderer.debug().add_debug_item(tonemap_aces.clone());
+        renderer.debug().add_debug_item(exposure.clone());
 
         log::debug!("Scenes count: {}", self.scenes.len());
+        for scene in self.scenes.iter_mut() {
+            scene.set_capabilities(&renderer.pipeline.capabilities);
+        }
+        // The inputs of the configuration win over the ones of the scenes
+        for scene in &self.scenes {
+            for (name, inputs) in scene.actions() {
+                renderer
+                    .pipeline
+                    .bindings
+                    .register(Action::Scene(name), inputs);
+            }
+        }
+        let bindings = debug::widget::BindingsEditor::new(
+            &renderer.pipeline.bindings,
+            "Key Bindings",
+        );
+        renderer.debug().add_debug_item(bindings.clone());
         // Add the debug items from the scenes
         for scene in &self.scenes {
             renderer.debug().add_separator();
@@ -159,8 +543,10 @@ impl App {
         // Setup the shapes from the scenes
         for scene in &self.scenes {
             renderer.set_shapes(scene.shapes());
+            renderer.set_point_clouds(scene.point_clouds());
         }
         renderer.load_shapes();
+        renderer.load_point_clouds();
         log::debug!(
             "Renderer buffer count: {}",
             renderer
@@ -170,45 +556,64 @@ impl App {
                 .count()
         );
 
+        // The debug interface of the main window is drawn by its host
+        let mut windows = Vec::new();
+        if let Some(mut host) = debug_host {
+            std::mem::swap(host.renderer.debug(), renderer.debug());
+            windows.push(host);
+        }
+        for (config, scenes) in self.windows.drain(..) {
+            windows.push(SecondaryWindow::open(
+                &event_loop,
+                &renderer,
+                &window_config,
+                config,
+                scenes,
+            )?);
+        }
+
         let mut last_instant = std::time::Instant::now();
 
         let mut duration_mean = 0.0;
         let mut duration_count = 0;
 
         let _ = event_loop.run(move |event, ewlt| {
+            // Every event of the window may change the next frame
+            if let winit::event::Event::WindowEvent { ref event, .. } = event {
+                if !matches!(event, winit::event::WindowEvent::RedrawRequested)
+                {
+                    pacer.request_redraw();
+                }
+            }
             match event {
                 winit::event::Event::WindowEvent {
                     ref event,
                     window_id,
                 } if window_id == renderer.window().id()
                     // If the debug renderer is active, we want to handle the input of egui first (if it's not handled by egui, we can handle it)
-                    && renderer.debug_renderer.as_mut().map_or(true, |debug_renderer| !debug_renderer.handle_input(renderer.window, event))
+                    && renderer.debug_renderer.as_mut().map_or(true, |debug_renderer| !debug_renderer.handle_input(&renderer.window, event)) => {
+
+                    let input = InputEvent::from_event(event);
+                    if let (Some(recorder), Some(input)) = (recorder.as_mut(), input) {
+                        recorder.record(input);
+                    }
+                    // The replay replaces the inputs of the user, the window can still be resized
+                    let replaced = replay.is_some() && !matches!(input, None | Some(InputEvent::Resize { .. }));
                     // Handle the input of the app itself
-                    && !renderer.input(event) => {
+                    if replaced || input.is_some_and(|input| renderer.input_event(&input)) {
+                        return;
+                    }
 
                     // Handle the window events
                     match event {
-                        winit::event::WindowEvent::KeyboardInput {
-                            event:
-                                winit::event::KeyEvent {
-                                    logical_key: key,
-                                    state: winit::event::ElementState::Released, // Trigger only once
-                                    ..
-                                },
-                            ..
-                        } => match key {
-                            winit::keyboard::Key::Named(
-                                winit::keyboard::NamedKey::Escape,
-                            ) => ewlt.exit(),
-                            winit::keyboard::Key::Named(
-                                winit::keyboard::NamedKey::F11,
-                            ) => {
-                                log::info!("Toggling fullscreen");
-                                renderer.set_fullscreen(!renderer.fullscreen());
+                        winit::event::WindowEvent::KeyboardInput { .. }
+                        | winit::event::WindowEvent::MouseInput { .. } => {
+                            if let Some(InputEvent::Input { input, state }) = input {
+                                if run_actions(&mut renderer, &mut self.scenes, &present_mode, input, state) {
+                                    ewlt.exit();
+                                }
                             }
-
-                            _ => {}
-                        },
+                        }
                         winit::event::WindowEvent::CloseRequested => {
                             ewlt.exit()
                         }
@@ -216,29 +621,76 @@ impl App {
                             renderer.resize(*physical_size);
                         }
                         winit::event::WindowEvent::RedrawRequested => {
-                            elapsed_handler!(*(&mut wgpu_update) => renderer.update(&mut self.scenes));
+                            pacer.frame_started(std::time::Instant::now());
+                            if let Some(benchmark) = &benchmark {
+                                renderer.pipeline.camera.orbit(cgmath::Rad(
+                                    std::f32::consts::TAU * benchmark.progress(),
+                                ));
+                            }
+                            // The inputs and the time step of the frame come from the replay
+                            let replayed = match replay.as_mut().map(Replay::next_frame) {
+                                Some(Some(frame)) => {
+                                    for input in &frame.events {
+                                        if replay_input(&mut renderer, &mut self.scenes, &present_mode, input) {
+                                            ewlt.exit();
+                                        }
+                                    }
+                                    // The debug interface gets the recorded input instead of the one of the user
+                                    if let Some(debug_renderer) = renderer.debug_renderer.as_mut() {
+                                        debug_renderer.replay_input(frame.interface.unwrap_or_default());
+                                    }
+                                    Some(frame.dt)
+                                }
+                                Some(None) => {
+                                    log::info!("End of the replay");
+                                    ewlt.exit();
+                                    return;
+                                }
+                                None => None,
+                            };
+                            let dt = elapsed_handler!(*(&mut wgpu_update) => match replayed {
+                                Some(dt) => {
+                                    renderer.step(&mut self.scenes, dt);
+                                    dt
+                                }
+                                None => renderer.update(&mut self.scenes),
+                            });
                             // Reload the buffers if needed
                             renderer.load_shapes();
+                            renderer.load_point_clouds();
                             match renderer.render(&mut wgpu_redraw, &mut egui_redraw)
                             {
                                 Ok(_) => {}
-                                Err(
+                                Err(Error::Surface(
                                     wgpu::SurfaceError::Lost
                                     | wgpu::SurfaceError::Outdated,
-                                ) => {
+                                )) => {
                                     renderer.resize(renderer.size());
                                 }
-                                Err(wgpu::SurfaceError::OutOfMemory) => {
-                                    println!("Out of memory! (peepoSad)");
-                                    ewlt.exit();
-                                }
-                                Err(wgpu::SurfaceError::Timeout) => {
+                                Err(Error::Surface(wgpu::SurfaceError::Timeout)) => {
                                     log::error!("Surface Timeout!");
                                 }
+                                Err(error) if error.needs_recovery() => {
+                                    log::error!("{}", error);
+                                    if let Err(error) = recover(&mut renderer, &mut self.scenes, &mut windows) {
+                                        log::error!("Failed to recover: {}", error);
+                                        ewlt.exit();
+                                    }
+                                }
+                                Err(error) => {
+                                    log::error!("{}", error);
+                                    ewlt.exit();
+                                }
+                            }
+                            // Written once the debug interface took its input
+                            let interface = renderer.debug_renderer.as_mut().and_then(|debug_renderer| debug_renderer.take_recorded_input());
+                            if let Some(Err(error)) = recorder.as_mut().map(|recorder| recorder.end_frame(dt, interface)) {
+                                log::error!("{}", error);
+                                recorder = None;
                             }
 
                             // Update the debug fields if the debug renderer is active
-                            if renderer.debug_renderer.is_some() {
+                            if debug_enabled {
                                 let t1 = std::time::Instant::now();
                                 {
 
@@ -252,17 +704,88 @@ impl App {
                                         });
                                     });
 
+                                    background_mode.borrow_mut().callback_update(|index| renderer.pipeline.background.set_mode(background_modes[index]));
+                                    gradient_top.borrow_mut().callback_update(|value| {
+                                        let bottom = gradient_bottom.borrow().get().into_rgb();
+                                        renderer.pipeline.background.set_gradient(value.into_rgb(), bottom);
+                                    });
+                                    gradient_bottom.borrow_mut().callback_update(|value| {
+                                        let top = gradient_top.borrow().get().into_rgb();
+                                        renderer.pipeline.background.set_gradient(top, value.into_rgb());
+                                    });
+
                                     camera_speed.borrow_mut().callback_update(|value| renderer.pipeline.camera_controller.set_speed(*value));
                                     camera_sensitivity.borrow_mut().callback_update(|value| renderer.pipeline.camera_controller.set_sensitivity(*value));
                                     camera_zoom_sensitivity.borrow_mut().callback_update(|value| renderer.pipeline.camera_controller.set_zoom_sensitivity(*value));
 
+                                    present_mode.borrow_mut().callback_update(|index| {
+                                        if let Some(mode) = renderer.present_modes().get(index).copied() {
+                                            renderer.set_present_mode(mode);
+                                        }
+                                    });
+                                    let mut display = renderer.display().clone();
+                                    monitor.borrow_mut().callback_update(|index| {
+                                        display.monitor = index.checked_sub(1).map(|index| index.to_string());
+                                        // The video modes are the ones of the new monitor
+                                        display.video_mode = None;
+                                    });
+                                    video_mode.borrow_mut().callback_update(|index| {
+                                        display.video_mode = index.checked_sub(1).map(VideoModeSelection::Index);
+                                    });
+                                    fullscreen.borrow_mut().callback_update(|index| display.fullscreen = fullscreen_modes[index]);
+                                    if display != *renderer.display() {
+                                        let monitor_changed = display.monitor != renderer.display().monitor;
+                                        match renderer.set_display(display) {
+                                            // Remembered for the next runs
+                                            Ok(()) => {
+                                                if let Err(error) = renderer.display().save(&settings_file) {
+                                                    log::warn!("{}", error);
+                                                }
+                                            }
+                                            Err(error) => log::warn!("Failed to change the display: {}", error),
+                                        }
+                                        if monitor_changed {
+                                            let (options, selected) = video_mode_options(&renderer);
+                                            video_mode.borrow_mut().set_options(options, selected);
+                                        }
+                                    }
+                                    frame_latency.borrow_mut().callback_update(|value| renderer.set_frame_latency(*value));
+                                    max_fps.borrow_mut().callback_update(|value| pacer.set_max_fps(*value));
+
+                                    shadow_bias.borrow_mut().callback_update(|value| renderer.pipeline.shadow.set_bias(*value));
+                                    shadow_resolution.borrow_mut().callback_update(|value| renderer.set_shadow_resolution(*value));
+
+                                    post_chain.borrow_mut().callback_update(|items| renderer.pipeline.post.configure(items));
+
                                     reset_camera_button.borrow_mut().callback_update(|| {
                                         renderer.pipeline.camera.reset_camera();
                                     });
 
+                                    bindings.borrow_mut().callback_update(|bindings| {
+                                        renderer.pipeline.bindings = bindings.clone();
+                                    });
+
                                 }
 
                                 camera_info_label.borrow_mut().set(renderer.pipeline.camera.get_camera_info());
+                                pacer.set_mode(if *reactive.borrow().get() { RedrawMode::Reactive } else { RedrawMode::Continuous });
+                                renderer.pipeline.set_culling(*culling.borrow().get());
+                                renderer.pipeline.helpers.set_grid(*grid.borrow().get());
+                                renderer.pipeline.helpers.set_axes(*axes.borrow().get());
+                                renderer.pipeline.helpers.set_gizmo(*gizmo.borrow().get());
+                                let aces = if *tonemap_aces.borrow().get() { 1.0 } else { 0.0 };
+                                let tonemap_params = [aces, *exposure.borrow().get(), 0.0, 0.0];
+                                if renderer.pipeline.post.params(graphics::post::TONEMAP) != Some(tonemap_params) {
+                                    renderer.pipeline.post.set_params(graphics::post::TONEMAP, tonemap_params);
+                                }
+                                renderer.pipeline.shadow.set_preview_visible(shadow_preview.borrow_mut().take_visible());
+                                if *shadows.borrow().get() != renderer.pipeline.shadow.enabled() {
+                                    renderer.pipeline.shadow.set_enabled(*shadows.borrow().get());
+                                }
+                                culling_label.borrow_mut().set(renderer.pipeline.culling_stats());
+                                renderer.pipeline.set_wireframe(*wireframe.borrow().get());
+                                renderer.pipeline.batch.set_enabled(*indirect.borrow().get());
+                                draw_label.borrow_mut().set((renderer.pipeline.batch.mode(), renderer.pipeline.batch.draw_count()));
 
                                 let time = std::time::Instant::now();
                                 let duration = time.duration_since(last_instant);
@@ -278,10 +801,8 @@ impl App {
                                     wgpu_update.as_secs_f32() * 1000.0; // ms
                                 let wgpu_redraw_f32 =
                                     wgpu_redraw.as_secs_f32() * 1000.0; // ms
-                                wgpu_redraw = Duration::from_nanos(0);
                                 let egui_redraw_f32 =
                                     egui_redraw.as_secs_f32() * 1000.0; // ms
-                                egui_redraw = Duration::from_nanos(0);
                                 let egui_update_f32 =
                                     egui_update.as_secs_f32() * 1000.0; // ms
 
@@ -312,9 +833,36 @@ impl App {
                                     egui_redraw_f32,
                                     // other_time_f32,
                                 ]);
+                                if renderer.pipeline.profiler.supported() {
+                                    let timings = GpuPass::ALL.map(|pass| {
+                                        renderer.pipeline.profiler.timing(pass).unwrap_or(0.0)
+                                    });
+                                    gpu_time_label.borrow_mut().set(Some(timings));
+                                    gpu_time.borrow_mut().push(timings);
+                                }
                                 last_instant = time;
                             }
 
+                            if let Some(benchmark) = &mut benchmark {
+                                benchmark.record(wgpu_update, wgpu_redraw, egui_update, egui_redraw);
+                                if benchmark.is_done() {
+                                    for (bucket, statistics) in benchmark.summary() {
+                                        log::info!(
+                                            "{:<12} mean {:.3}ms | p50 {:.3}ms | p95 {:.3}ms | p99 {:.3}ms | max {:.3}ms",
+                                            bucket, statistics.mean, statistics.p50, statistics.p95, statistics.p99, statistics.max
+                                        );
+                                    }
+                                    match benchmark.write() {
+                                        Ok(paths) => {
+                                            for path in paths {
+                                                log::info!("Benchmark written to {}", path.display());
+                                            }
+                                        }
+                                        Err(error) => log::error!("{}", error),
+                                    }
+                                    ewlt.exit();
+                                }
+                            }
 
                         }
                         _ => {
@@ -323,13 +871,72 @@ impl App {
                     };
 
                 }
+                winit::event::Event::WindowEvent {
+                    ref event,
+                    window_id,
+                } if window_id != renderer.window().id() => {
+                    let Some(index) = windows.iter().position(|window| window.renderer.window().id() == window_id) else {
+                        return;
+                    };
+                    match windows[index].handle_event(event) {
+                        Ok(true) => {
+                            windows.remove(index);
+                        }
+                        Ok(false) => {}
+                        Err(error) if error.needs_recovery() => {
+                            log::error!("{}", error);
+                            if let Err(error) = recover(&mut renderer, &mut self.scenes, &mut windows) {
+                                log::error!("Failed to recover: {}", error);
+                                ewlt.exit();
+                            }
+                        }
+                        // Only this window is lost
+                        Err(error) => {
+                            log::error!("{}", error);
+                            windows.remove(index);
+                        }
+                    }
+                }
                 winit::event::Event::DeviceEvent {
                     event: winit::event::DeviceEvent::MouseMotion{ delta, },
                     .. // We're not using device_id currently
-                } => renderer.process_mouse_motion(delta),
+                } => {
+                    if let Some(recorder) = recorder.as_mut() {
+                        recorder.record(InputEvent::MouseMotion { delta });
+                    }
+                    if replay.is_none() {
+                        renderer.process_mouse_motion(delta);
+                    }
+                    for window in windows.iter_mut() {
+                        window.renderer.process_mouse_motion(delta);
+                    }
+                }
                 winit::event::Event::AboutToWait => {
-                    // Send a redraw request
-                    renderer.window().request_redraw();
+                    let now = std::time::Instant::now();
+                    let delays = std::iter::once(&renderer)
+                        .chain(windows.iter().map(|window| &window.renderer))
+                        .filter_map(Renderer::debug_repaint_delay);
+                    for delay in delays {
+                        pacer.request_redraw_at(now + delay);
+                    }
+                    let animating = renderer.pipeline.is_animating()
+                        || self.scenes.iter().any(|scene| scene.needs_redraw())
+                        || windows.iter().any(SecondaryWindow::is_animating);
+                    match pacer.poll(now, animating) {
+                        Pacing::Redraw => {
+                            ewlt.set_control_flow(ControlFlow::Wait);
+                            request_redraws(&renderer, &windows);
+                        }
+                        Pacing::Spin(instant) => {
+                            spin_until(instant);
+                            ewlt.set_control_flow(ControlFlow::Wait);
+                            request_redraws(&renderer, &windows);
+                        }
+                        Pacing::WaitUntil(instant) => {
+                            ewlt.set_control_flow(ControlFlow::WaitUntil(instant));
+                        }
+                        Pacing::Wait => ewlt.set_control_flow(ControlFlow::Wait),
+                    }
                 }
                 _ => {
                     // Nothing to do yet
@@ -342,3 +949,285 @@ impl App {
         Ok(())
     }
 }
+
+/// Window opened with `App::add_window`, or hosting the detached debug
+/// interface
+struct SecondaryWindow {
+    renderer: Renderer,
+    scenes: Vec<Box<dyn Scene>>,
+}
+
+impl SecondaryWindow {
+    fn open(
+        event_loop: &winit::event_loop::EventLoop<()>,
+        main: &Renderer,
+        app_config: &Config,
+        config: WindowConfig,
+        mut scenes: Vec<Box<dyn Scene>>,
+    ) -> Result<Self, Error> {
+        let (width, height) = config.size;
+        let window = Arc::new(
+            winit::window::WindowBuilder::new()
+                .with_title(&config.title)
+                .with_inner_size(winit::dpi::PhysicalSize::new(width, height))
+                .build(event_loop)?,
+        );
+        let mut window_config = app_config.clone();
+        window_config.window_title = config.title;
+        window_config.window_size = WindowSizeConfig {
+            hint: WindowSizeHint::Windowed,
+            size: config.size,
+        };
+        window_config.disable_egui |= !config.debug;
+        let mut renderer = main.open(
+            window,
+            window_config,
+            config.content != WindowContent::Interface,
+        )?;
+
+        for scene in scenes.iter_mut() {
+            scene.set_capabilities(&renderer.pipeline.capabilities);
+            for (name, inputs) in scene.actions() {
+                renderer
+                    .pipeline
+                    .bindings
+                    .register(Action::Scene(name), inputs);
+            }
+        }
+        for scene in &scenes {
+            renderer.debug().add_separator();
+            for item in scene.debug_item() {
+                renderer.debug().add_debug_item(item.clone());
+            }
+        }
+        match config.content {
+            WindowContent::Scenes => {
+                for scene in &scenes {
+                    renderer.set_shapes(scene.shapes());
+                    renderer.set_point_clouds(scene.point_clouds());
+                }
+            }
+            WindowContent::MainScenes => {
+                renderer.set_shapes(main.shapes.clone());
+                renderer.set_point_clouds(main.point_clouds.clone());
+                renderer.share_debug_draws(main);
+            }
+            WindowContent::Interface => {}
+        }
+        renderer.load_shapes();
+        renderer.load_point_clouds();
+        Ok(Self { renderer, scenes })
+    }
+
+    /// Handle an event of the window, returns whether the window is closed
+    fn handle_event(
+        &mut self,
+        event: &winit::event::WindowEvent,
+    ) -> Result<bool, Error> {
+        let renderer = &mut self.renderer;
+        if renderer
+            .debug_renderer
+            .as_mut()
+            .is_some_and(|debug_renderer| {
+                debug_renderer.handle_input(&renderer.window, event)
+            })
+            || renderer.input(event)
+        {
+            return Ok(false);
+        }
+        match event {
+            winit::event::WindowEvent::KeyboardInput { .. }
+            | winit::event::WindowEvent::MouseInput { .. } => {
+                let Some((input, state)) = Input::from_event(event) else {
+                    return Ok(false);
+                };
+                let pressed = state == winit::event::ElementState::Pressed;
+                let actions = renderer
+                    .pipeline
+                    .bindings
+                    .actions(input)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for action in actions {
+                    match action {
+                        Action::Scene(name) => {
+                            for scene in self.scenes.iter_mut() {
+                                scene.on_action(&name, pressed);
+                            }
+                            renderer.collect_debug_draws();
+                        }
+                        _ if pressed => {}
+                        // Only closes this window
+                        Action::Quit => return Ok(true),
+                        Action::ToggleFullscreen => {
+                            if let Err(error) =
+                                renderer.set_fullscreen(!renderer.fullscreen())
+                            {
+                                log::warn!(
+                                    "Failed to toggle fullscreen: {}",
+                                    error
+                                );
+                            }
+                        }
+                        Action::NextPresentMode => {
+                            renderer.next_present_mode();
+                        }
+                        Action::ResetCamera => {
+                            renderer.pipeline.camera.reset_camera()
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            winit::event::WindowEvent::CloseRequested => return Ok(true),
+            winit::event::WindowEvent::Resized(physical_size) => {
+                renderer.resize(*physical_size);
+            }
+            winit::event::WindowEvent::RedrawRequested => self.redraw()?,
+            _ => {}
+        }
+        Ok(false)
+    }
+
+    /// Update the scenes of the window and draw it, the errors needing a new
+    /// device are returned
+    fn redraw(&mut self) -> Result<(), Error> {
+        self.renderer.update(&mut self.scenes);
+        self.renderer.load_shapes();
+        self.renderer.load_point_clouds();
+        let mut wgpu_redraw = Duration::from_nanos(0);
+        let mut egui_redraw = Duration::from_nanos(0);
+        match self.renderer.render(&mut wgpu_redraw, &mut egui_redraw) {
+            Err(Error::Surface(
+                wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated,
+            )) => {
+                self.renderer.resize(self.renderer.size());
+                Ok(())
+            }
+            Err(Error::Surface(wgpu::SurfaceError::Timeout)) => {
+                log::error!("Surface Timeout!");
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// Whether the window must keep being redrawn, see `Scene::needs_redraw`
+    fn is_animating(&self) -> bool {
+        self.renderer.pipeline.is_animating()
+            || self.scenes.iter().any(|scene| scene.needs_redraw())
+    }
+}
+
+/// Run the actions bound to an input of the main window which are left to
+/// the application, returns whether the application must quit
+fn run_actions(
+    renderer: &mut Renderer,
+    scenes: &mut [Box<dyn Scene>],
+    present_mode: &RefCell<debug::widget::Choice>,
+    input: Input,
+    state: winit::event::ElementState,
+) -> bool {
+    let pressed = state == winit::event::ElementState::Pressed;
+    let actions = renderer
+        .pipeline
+        .bindings
+        .actions(input)
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut quit = false;
+    for action in actions {
+        match action {
+            Action::Scene(name) => {
+                for scene in scenes.iter_mut() {
+                    scene.on_action(&name, pressed);
+                }
+                renderer.collect_debug_draws();
+            }
+            // Trigger only once
+            _ if pressed => {}
+            Action::Quit => quit = true,
+            Action::ToggleFullscreen => {
+                log::info!("Toggling fullscreen");
+                if let Err(error) =
+                    renderer.set_fullscreen(!renderer.fullscreen())
+                {
+                    log::warn!("Failed to toggle fullscreen: {}", error);
+                }
+            }
+            Action::NextPresentMode => {
+                let mode = renderer.next_present_mode();
+                if let Some(index) =
+                    renderer.present_modes().iter().position(|m| *m == mode)
+                {
+                    present_mode.borrow_mut().set(index);
+                }
+            }
+            Action::ResetCamera => renderer.pipeline.camera.reset_camera(),
+            _ => {}
+        }
+    }
+    quit
+}
+
+/// Apply a recorded input as if the main window had received it, returns
+/// whether the application must quit
+fn replay_input(
+    renderer: &mut Renderer,
+    scenes: &mut [Box<dyn Scene>],
+    present_mode: &RefCell<debug::widget::Choice>,
+    event: &InputEvent,
+) -> bool {
+    match *event {
+        InputEvent::Input { input, state } => {
+            if !renderer.input_event(event) {
+                return run_actions(
+                    renderer,
+                    scenes,
+                    present_mode,
+                    input,
+                    state,
+                );
+            }
+        }
+        InputEvent::MouseMotion { delta } => {
+            renderer.process_mouse_motion(delta)
+        }
+        InputEvent::Scroll { .. } => {
+            renderer.input_event(event);
+        }
+        InputEvent::Resize { width, height } => {
+            let size = winit::dpi::PhysicalSize::new(width, height);
+            let _ = renderer.window().request_inner_size(size);
+            renderer.resize(size);
+        }
+    }
+    false
+}
+
+/// Recreate the device of the main window, then the resources of the other
+/// windows with it
+fn recover(
+    renderer: &mut Renderer,
+    scenes: &mut [Box<dyn Scene>],
+    windows: &mut [SecondaryWindow],
+) -> Result<(), Error> {
+    renderer.recover()?;
+    for scene in scenes.iter_mut() {
+        scene.set_capabilities(&renderer.pipeline.capabilities);
+    }
+    for window in windows.iter_mut() {
+        window.renderer.recover_shared(renderer)?;
+        for scene in window.scenes.iter_mut() {
+            scene.set_capabilities(&window.renderer.pipeline.capabilities);
+        }
+    }
+    Ok(())
+}
+
+fn request_redraws(renderer: &Renderer, windows: &[SecondaryWindow]) {
+    renderer.window().request_redraw();
+    for window in windows {
+        window.renderer.window().request_redraw();
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+/// Names of the timings of a frame, in the order of `FrameTiming::values`
+///
+/// The buckets are the ones of the frame-time chart of the debug window,
+/// `frame_time` is the time since the start of the previous frame.
+pub const BUCKETS: [&str; 5] = [
+    "wgpu_update",
+    "wgpu_draw",
+    "egui_update",
+    "egui_draw",
+    "frame_time",
+];
+
+/// Names of the fields of `Statistics`, in the order of
+/// `Statistics::values`
+pub const STATISTICS: [&str; 5] = ["mean", "p50", "p95", "p99", "max"];
+
+/// How long the benchmark runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BenchmarkLength {
+    Seconds(f32),
+    Frames(u32),
+}
+
+/// `10`, `10s` or `2.5s` for seconds, `600f` or `600frames` for frames
+impl FromStr for BenchmarkLength {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid benchmark length {:?}, expected seconds (10s) or \
+                 frames (600f)",
+                value
+            )
+        };
+        if let Some(frames) = value
+            .strip_suffix("frames")
+            .or_else(|| value.strip_suffix('f'))
+        {
+            match frames.parse::<u32>() {
+                Ok(frames) if frames > 0 => Ok(Self::Frames(frames)),
+                _ => Err(invalid()),
+            }
+        } else {
+            let seconds = value.strip_suffix('s').unwrap_or(value);
+            match seconds.parse::<f32>() {
+                Ok(seconds) if seconds > 0.0 => Ok(Self::Seconds(seconds)),
+                _ => Err(invalid()),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BenchmarkConfig {
+    pub length: BenchmarkLength,
+    /// Report written at the end, in JSON when the extension is `.json`,
+    /// in CSV otherwise
+    pub output: PathBuf,
+}
+
+/// CPU time spent in each part of a frame, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct FrameTiming {
+    pub wgpu_update: f32,
+    pub wgpu_draw: f32,
+    pub egui_update: f32,
+    pub egui_draw: f32,
+    pub frame_time: f32,
+}
+
+impl FrameTiming {
+    pub fn values(&self) -> [f32; BUCKETS.len()] {
+        [
+            self.wgpu_update,
+            self.wgpu_draw,
+            self.egui_update,
+            self.egui_draw,
+            self.frame_time,
+        ]
+    }
+}
+
+/// Summary of the timings of a bucket, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Statistics {
+    pub mean: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl Statistics {
+    pub fn compute(values: &[f32]) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        // Nearest rank
+        let percentile = |p: f32| {
+            let rank = (p / 100.0 * sorted.len() as f32).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+        Self {
+            mean: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            p50: percentile(50.0),
+            p95: percentile(95.0),
+            p99: percentile(99.0),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+
+    pub fn values(&self) -> [f32; STATISTICS.len()] {
+        [self.mean, self.p50, self.p95, self.p99, self.max]
+    }
+}
+
+#[derive(Serialize)]
+struct BucketSummary {
+    bucket: &'static str,
+    #[serde(flatten)]
+    statistics: Statistics,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    frames: &'a [FrameTiming],
+    summary: Vec<BucketSummary>,
+}
+
+/// Timings recorded while the camera goes around the scene once
+pub struct Benchmark {
+    config: BenchmarkConfig,
+    started: Option<Instant>,
+    last_frame: Option<Instant>,
+    frames: Vec<FrameTiming>,
+}
+
+impl Benchmark {
+    pub fn new(config: BenchmarkConfig) -> Self {
+        Self {
+            config,
+            started: None,
+            last_frame: None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Part of the benchmark already run, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        let progress = match self.config.length {
+            BenchmarkLength::Frames(frames) => {
+                self.frames.len() as f32 / frames as f32
+            }
+            BenchmarkLength::Seconds(seconds) => {
+                self.started.map_or(0.0, |started| {
+                    started.elapsed().as_secs_f32() / seconds
+                })
+            }
+        };
+        progress.min(1.0)
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.progress() >= 1.0
+    }
+
+    /// Record a frame, the CPU times are measured by the caller
+    pub fn record(
+        &mut self,
+        wgpu_update: Duration,
+        wgpu_draw: Duration,
+        egui_update: Duration,
+        egui_draw: Duration,
+    ) {
+        let now = Instant::now();
+        self.started.get_or_insert(now);
+        let frame = self
+            .last_frame
+            .map_or(Duration::ZERO, |last_frame| now - last_frame);
+        self.last_frame = Some(now);
+        let ms = |duration: Duration| duration.as_secs_f32() * 1000.0;
+        self.frames.push(FrameTiming {
+            wgpu_update: ms(wgpu_update),
+            wgpu_draw: ms(wgpu_draw),
+            egui_update: ms(egui_update),
+            egui_draw: ms(egui_draw),
+            frame_time: ms(frame),
+        });
+    }
+
+    /// Statistics of each bucket of `BUCKETS`
+    pub fn summary(&self) -> Vec<(&'static str, Statistics)> {
+        BUCKETS
+            .iter()
+            .enumerate()
+            .map(|(index, bucket)| {
+                let values = self
+                    .frames
+                    .iter()
+                    // The first frame has no previous frame
+                    .skip(usize::from(index == BUCKETS.len() - 1))
+                    .map(|frame| frame.values()[index])
+                    .collect::<Vec<_>>();
+                (*bucket, Statistics::compute(&values))
+            })
+            .collect()
+    }
+
+    /// One row per frame, the first column is the frame index
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("frame,{}\n", BUCKETS.join(","));
+        for (index, frame) in self.frames.iter().enumerate() {
+            let values = frame.values().map(|value| format!("{:.4}", value));
+            csv += &format!("{},{}\n", index, values.join(","));
+        }
+        csv
+    }
+
+    /// One row per statistic, the first column is the name of the statistic
+    pub fn summary_csv(&self) -> String {
+        let mut csv = format!("statistic,{}\n", BUCKETS.join(","));
+        let summary = self.summary();
+        for (row, name) in STATISTICS.iter().enumerate() {
+            let values = summary
+                .iter()
+                .map(|(_, statistics)| {
+                    format!("{:.4}", statistics.values()[row])
+                })
+                .collect::<Vec<_>>();
+            csv += &format!("{},{}\n", name, values.join(","));
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        let report = Report {
+            frames: &self.frames,
+            summary: self
+                .summary()
+                .into_iter()
+                .map(|(bucket, statistics)| BucketSummary {
+                    bucket,
+                    statistics,
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&report).unwrap_or_default()
+    }
+
+    /// Write the report to the output of the configuration, returns the
+    /// written files
+    ///
+    /// In CSV, the statistics go to a second file next to the frames,
+    /// `benchmark.summary.csv` for `benchmark.csv`.
+    pub fn write(&self) -> Result<Vec<PathBuf>, crate::Error> {
+        let path = self.config.output.as_path();
+        let json = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+        let reports = if json {
+            vec![(path.to_path_buf(), self.to_json())]
+        } else {
+            vec![
+                (path.to_path_buf(), self.to_csv()),
+                (path.with_extension("summary.csv"), self.summary_csv()),
+            ]
+        };
+        let mut written = Vec::new();
+        for (path, report) in reports {
+            std::fs::write(&path, report).map_err(|source| {
+                crate::Error::Report {
+                    path: path.clone(),
+                    source,
+                }
+            })?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length() {
+        assert_eq!("10".parse(), Ok(BenchmarkLength::Seconds(10.0)));
+        assert_eq!("2.5s".parse(), Ok(BenchmarkLength::Seconds(2.5)));
+        assert_eq!("600f".parse(), Ok(BenchmarkLength::Frames(600)));
+        assert_eq!("600frames".parse(), Ok(BenchmarkLength::Frames(600)));
+        assert!("0f".parse::<BenchmarkLength>().is_err());
+        assert!("ten".parse::<BenchmarkLength>().is_err());
+    }
+
+    #[test]
+    fn test_statistics() {
+        let values = (1..=100)
+            .rev()
+            .map(|value| value as f32)
+            .collect::<Vec<_>>();
+        let statistics = Statistics::compute(&values);
+        assert_eq!(statistics.mean, 50.5);
+        assert_eq!(statistics.p50, 50.0);
+        assert_eq!(statistics.p95, 95.0);
+        assert_eq!(statistics.p99, 99.0);
+        assert_eq!(statistics.max, 100.0);
+        assert_eq!(Statistics::compute(&[]), Statistics::default());
+    }
+
+    #[test]
+    fn test_report() {
+        let mut benchmark = Benchmark::new(BenchmarkConfig {
+            length: BenchmarkLength::Frames(2),
+            output: PathBuf::from("benchmark.csv"),
+        });
+        let ms = Duration::from_millis;
+        benchmark.record(ms(1), ms(2), ms(3), ms(4));
+        assert!(!benchmark.is_done());
+        benchmark.record(ms(1), ms(2), ms(3), ms(4));
+        assert!(benchmark.is_done());
+
+        let csv = benchmark.to_csv();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "frame,wgpu_update,wgpu_draw,egui_update,egui_draw,frame_time"
+        );
+        assert!(lines[1].starts_with("0,1.0000,2.0000,3.0000,4.0000,"));
+        assert_eq!(lines.len(), 1 + 2);
+
+        let summary = benchmark.summary_csv();
+        let lines = summary.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            "statistic,wgpu_update,wgpu_draw,egui_update,egui_draw,frame_time"
+        );
+        assert_eq!(lines.len(), 1 + 5);
+        assert!(lines[1].starts_with("mean,1.0000,2.0000,3.0000,4.0000,"));
+
+        let json = benchmark.to_json();
+        assert!(json.contains("\"frames\""));
+        assert!(json.contains("\"bucket\": \"frame_time\""));
+        assert!(json.contains("\"p95\""));
+    }
+}
@@ -1,10 +1,44 @@
-use clap::{Parser, ValueEnum};
+use std::path::{Path, PathBuf};
+
+use clap::{
+    builder::{PossibleValuesParser, TypedValueParser as _},
+    parser::ValueSource,
+    ArgMatches, CommandFactory, FromArgMatches as _, Parser, ValueEnum,
+};
+use serde::{Deserialize, Serialize};
 use wgpu::{PresentMode, SurfaceCapabilities};
 
+use crate::benchmark::{BenchmarkConfig, BenchmarkLength};
+use crate::graphics::{
+    camera::{
+        DEFAULT_CAMERA_FOVY, DEFAULT_CAMERA_PITCH, DEFAULT_CAMERA_POSITION,
+        DEFAULT_CAMERA_SENSITIVITY, DEFAULT_CAMERA_SPEED, DEFAULT_CAMERA_YAW,
+        DEFAULT_CAMERA_ZFAR, DEFAULT_CAMERA_ZNEAR,
+        DEFAULT_CAMERA_ZOOM_SENSITIVITY,
+    },
+    shadow::{DEFAULT_SHADOW_BIAS, DEFAULT_SHADOW_RESOLUTION},
+};
+use crate::input::Bindings;
+
+/// Name of the configuration file looked up in the working directory
+const CONFIG_FILE_NAME: &str = "test-wgpu.toml";
+/// Sample counts of the multisampling supported by every adapter
+const MSAA_SAMPLE_COUNTS: [u32; 2] = [1, 4];
+/// Frames the presentation engine may queue, see
+/// `wgpu::SurfaceConfiguration::desired_maximum_frame_latency`
+pub const FRAME_LATENCIES: std::ops::RangeInclusive<u32> = 1..=3;
+const DEFAULT_FRAME_LATENCY: u32 = 2;
+const DEFAULT_WINDOW_WIDTH: u32 = 800;
+const DEFAULT_WINDOW_HEIGHT: u32 = 600;
+
 /// Config struct for the application
 ///
 /// This struct will hold all the configuration for the application
 /// and will be used to initialize the application
+///
+/// Every option can also be set with an environment variable or in the
+/// configuration file, the command line wins over the environment which wins
+/// over the file.
 #[derive(Parser)]
 #[command(
     version = "0.0.1",
@@ -14,46 +48,650 @@ use wgpu::{PresentMode, SurfaceCapabilities};
 )]
 pub struct ClapConfig {
     /// Title of the window
-    #[arg(short = 't', long = "title", default_value = "Test WGPU")]
+    #[arg(
+        short = 't',
+        long = "title",
+        env = "TEST_WGPU_TITLE",
+        default_value = "Test WGPU"
+    )]
     window_title: String,
 
     /// Window size configuration
-    #[arg(short = 'm', long = "mode", default_value = "windowed")]
+    #[arg(
+        short = 'm',
+        long = "mode",
+        env = "TEST_WGPU_MODE",
+        default_value = "windowed"
+    )]
     screen_mode: WindowSizeHint,
 
     /// Window size value
-    #[arg(short = 'e', long = "height", default_value = "600")]
+    #[arg(
+        short = 'e',
+        long = "height",
+        env = "TEST_WGPU_HEIGHT",
+        default_value_t = DEFAULT_WINDOW_HEIGHT
+    )]
     window_height: u32,
 
     /// Window size value
-    #[arg(short = 'w', long = "width", default_value = "800")]
+    #[arg(
+        short = 'w',
+        long = "width",
+        env = "TEST_WGPU_WIDTH",
+        default_value_t = DEFAULT_WINDOW_WIDTH
+    )]
     window_width: u32,
 
     /// Present mode configuration
-    #[arg(short = 'p', long = "present-mode")]
+    #[arg(short = 'p', long = "present-mode", env = "TEST_WGPU_PRESENT_MODE")]
     present_mode: Option<PresentModeConfig>,
 
+    /// Frames queued for presentation, lower reduces the input latency
+    #[arg(
+        long = "frame-latency",
+        env = "TEST_WGPU_FRAME_LATENCY",
+        default_value_t = DEFAULT_FRAME_LATENCY,
+        value_parser = clap::value_parser!(u32).range(
+            *FRAME_LATENCIES.start() as i64..=*FRAME_LATENCIES.end() as i64
+        )
+    )]
+    frame_latency: u32,
+
+    /// When the frames are drawn
+    #[arg(
+        long = "redraw",
+        env = "TEST_WGPU_REDRAW",
+        default_value = "continuous"
+    )]
+    redraw: RedrawMode,
+
+    /// Highest number of frames per second, 0 does not limit them
+    #[arg(long = "max-fps", env = "TEST_WGPU_MAX_FPS", default_value_t = 0)]
+    max_fps: u32,
+
     /// Backend selection
-    #[arg(short = 'b', long = "backend")]
+    #[arg(short = 'b', long = "backend", env = "TEST_WGPU_BACKEND")]
     backend: Option<BackendSelection>,
 
     /// Disable EGUI Rendering
-    #[arg(short = 'd', long = "disable-egui")]
+    #[arg(short = 'd', long = "disable-egui", env = "TEST_WGPU_DISABLE_EGUI")]
     disable_egui: bool,
+
+    /// Draw the debug interface in its own window
+    #[arg(long = "detach-debug", env = "TEST_WGPU_DETACH_DEBUG")]
+    detach_debug: bool,
+
+    /// Disable the shadows of the directional light
+    #[arg(long = "disable-shadows", env = "TEST_WGPU_DISABLE_SHADOWS")]
+    disable_shadows: bool,
+
+    /// Size (in texels) of the shadow map
+    #[arg(
+        long = "shadow-resolution",
+        env = "TEST_WGPU_SHADOW_RESOLUTION",
+        default_value_t = DEFAULT_SHADOW_RESOLUTION
+    )]
+    shadow_resolution: u32,
+
+    /// Depth bias applied when sampling the shadow map
+    #[arg(
+        long = "shadow-bias",
+        env = "TEST_WGPU_SHADOW_BIAS",
+        default_value_t = DEFAULT_SHADOW_BIAS
+    )]
+    shadow_bias: f32,
+
+    /// Background drawn behind the geometry
+    #[arg(
+        long = "background",
+        env = "TEST_WGPU_BACKGROUND",
+        default_value = "color"
+    )]
+    background: BackgroundMode,
+
+    /// Skybox images, either one equirectangular image or six cube faces
+    /// (+X, -X, +Y, -Y, +Z, -Z)
+    #[arg(
+        long = "skybox",
+        env = "TEST_WGPU_SKYBOX",
+        num_args = 1..=6,
+        value_delimiter = ',',
+        value_name = "FILE"
+    )]
+    skybox: Vec<PathBuf>,
+
+    /// Draw the infinite ground grid
+    #[arg(long = "grid", env = "TEST_WGPU_GRID")]
+    grid: bool,
+
+    /// Draw the world axes
+    #[arg(long = "axes", env = "TEST_WGPU_AXES")]
+    axes: bool,
+
+    /// Draw the orientation gizmo in a corner of the view
+    #[arg(long = "gizmo", env = "TEST_WGPU_GIZMO")]
+    gizmo: bool,
+
+    /// Extra views of the scene rendered from fixed cameras
+    #[arg(
+        long = "viewports",
+        env = "TEST_WGPU_VIEWPORTS",
+        default_value = "none"
+    )]
+    viewports: ViewportLayout,
+
+    /// Number of samples per pixel of the scene, 1 disables the
+    /// multisampling
+    #[arg(
+        long = "msaa",
+        env = "TEST_WGPU_MSAA",
+        default_value_t = 1,
+        value_parser = msaa_parser()
+    )]
+    msaa: u32,
+
+    /// Configuration file, by default `test-wgpu.toml` in the working
+    /// directory or `test-wgpu/config.toml` in the user configuration
+    /// directory
+    #[arg(
+        short = 'c',
+        long = "config",
+        env = "TEST_WGPU_CONFIG",
+        value_name = "FILE"
+    )]
+    config: Option<PathBuf>,
+
+    /// Print the effective configuration as TOML and exit
+    #[arg(long = "dump-config")]
+    dump_config: bool,
+
+    /// Move the camera around the scene for this long (10s, 600f), write
+    /// the frame timings and exit
+    #[arg(long = "benchmark", value_name = "SECONDS|FRAMES")]
+    benchmark: Option<BenchmarkLength>,
+
+    /// Report of the benchmark, in JSON with a `.json` extension and in CSV
+    /// otherwise, with the statistics in a `.summary.csv` file next to it
+    #[arg(
+        long = "benchmark-output",
+        default_value = "benchmark.csv",
+        value_name = "FILE"
+    )]
+    benchmark_output: PathBuf,
+
+    /// Write the inputs of the main window and the time step of each frame
+    /// to this file
+    #[arg(long = "record", value_name = "FILE", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Replace the inputs of the user and the time steps by the ones of a
+    /// recording, and exit at its end
+    #[arg(long = "replay", value_name = "FILE")]
+    replay: Option<PathBuf>,
+
+    /// Print the adapters of the selected backends and exit
+    #[arg(long = "list-adapters")]
+    list_adapters: bool,
+
+    /// Adapter to use, by index in `--list-adapters` or by part of its name
+    #[arg(
+        short = 'a',
+        long = "adapter",
+        env = "TEST_WGPU_ADAPTER",
+        value_name = "INDEX|NAME"
+    )]
+    adapter: Option<String>,
+
+    /// Kind of adapter preferred when none is selected
+    #[arg(
+        long = "power-preference",
+        env = "TEST_WGPU_POWER_PREFERENCE",
+        default_value = "high"
+    )]
+    power_preference: PowerPreference,
+
+    /// Use the software adapter, to run without a GPU
+    #[arg(
+        long = "force-fallback-adapter",
+        env = "TEST_WGPU_FORCE_FALLBACK_ADAPTER"
+    )]
+    force_fallback_adapter: bool,
+
+    /// Print the monitors and their video modes and exit
+    #[arg(long = "list-monitors")]
+    list_monitors: bool,
+
+    /// Monitor used in fullscreen, by index in `--list-monitors` or by part
+    /// of its name, the monitor of the window otherwise
+    #[arg(
+        long = "monitor",
+        env = "TEST_WGPU_MONITOR",
+        value_name = "INDEX|NAME"
+    )]
+    monitor: Option<String>,
+
+    /// Video mode of the exclusive fullscreen, by index in `--list-monitors`
+    /// or by size (1920x1080, 1920x1080@144), the largest one otherwise
+    #[arg(
+        long = "video-mode",
+        env = "TEST_WGPU_VIDEO_MODE",
+        value_name = "INDEX|WIDTHxHEIGHT[@HZ]"
+    )]
+    video_mode: Option<VideoModeSelection>,
+
+    /// Fullscreen toggled by the fullscreen key
+    #[arg(
+        long = "fullscreen",
+        env = "TEST_WGPU_FULLSCREEN",
+        default_value = "borderless"
+    )]
+    fullscreen: FullscreenMode,
 }
 
+#[derive(Clone)]
 pub struct Config {
     pub backends: wgpu::Backends,
+    pub adapter: AdapterConfig,
     pub disable_egui: bool,
     pub present_mode: Option<PresentModeConfig>,
+    /// Frames queued for presentation, see `FRAME_LATENCIES`
+    pub frame_latency: u32,
+    pub redraw: RedrawMode,
+    /// Highest number of frames per second, 0 when not limited
+    pub max_fps: u32,
     pub window_title: String,
     pub window_size: WindowSizeConfig,
+    pub display: DisplayConfig,
+    /// Configuration file which has been read, if any
+    pub config_file: Option<PathBuf>,
+    pub shadow: ShadowConfig,
+    pub background: BackgroundConfig,
+    pub helpers: HelpersConfig,
+    pub viewports: ViewportLayout,
+    /// Samples per pixel of the scene, see `MSAA_SAMPLE_COUNTS`
+    pub msaa: u32,
+    pub camera: CameraConfig,
+    pub keys: Bindings,
+    pub debug: DebugConfig,
+    /// Run a benchmark instead of the interactive mode
+    pub benchmark: Option<BenchmarkConfig>,
+    /// Recording written while running, see `--record`
+    pub record: Option<PathBuf>,
+    /// Recording replayed instead of the inputs of the user, see `--replay`
+    pub replay: Option<PathBuf>,
+}
+
+/// Monitor and video mode of the fullscreen
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DisplayConfig {
+    /// Index in `--list-monitors` or part of the name of the monitor
+    pub monitor: Option<String>,
+    pub video_mode: Option<VideoModeSelection>,
+    pub fullscreen: FullscreenMode,
+}
+
+impl DisplayConfig {
+    /// Write the monitor, the video mode and the fullscreen mode into the
+    /// `[window]` section of a configuration file, the other settings of the
+    /// file are kept along with its comments and layout
+    pub fn save(&self, path: &Path) -> Result<(), crate::Error> {
+        let mut document = match std::fs::read_to_string(path) {
+            Ok(text) => text
+                .parse::<toml_edit::DocumentMut>()
+                .map_err(|error| crate::Error::asset(path, error))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                toml_edit::DocumentMut::new()
+            }
+            Err(error) => return Err(crate::Error::asset(path, error)),
+        };
+        let window = document
+            .entry("window")
+            .or_insert(toml_edit::table())
+            .as_table_like_mut()
+            .ok_or_else(|| {
+                crate::Error::asset(path, "window is not a table")
+            })?;
+        let values = [
+            ("monitor", self.monitor.clone()),
+            ("video_mode", self.video_mode.map(String::from)),
+            (
+                "fullscreen",
+                self.fullscreen
+                    .to_possible_value()
+                    .map(|value| value.get_name().to_string()),
+            ),
+        ];
+        for (key, value) in values {
+            match value {
+                Some(value) => {
+                    // Keeps the comments around the previous value
+                    let mut value = toml_edit::Value::from(value);
+                    if let Some(previous) =
+                        window.get(key).and_then(toml_edit::Item::as_value)
+                    {
+                        *value.decor_mut() = previous.decor().clone();
+                    }
+                    window.insert(key, value.into());
+                }
+                None => {
+                    window.remove(key);
+                }
+            };
+        }
+        path.parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, document.to_string()))
+            .map_err(|source| crate::Error::SaveConfig {
+                path: path.to_path_buf(),
+                source,
+            })
+    }
+}
+
+/// Video mode of a monitor, by index or by size and refresh rate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum VideoModeSelection {
+    Index(usize),
+    Size {
+        width: u32,
+        height: u32,
+        /// In Hz, the highest one otherwise
+        refresh_rate: Option<u32>,
+    },
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    ValueEnum,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum FullscreenMode {
+    #[default]
+    #[clap(help = "Window covering the monitor, without changing its mode.")]
+    Borderless,
+    #[clap(help = "Switch the monitor to the selected video mode.")]
+    Exclusive,
+}
+
+/// How the adapter is chosen among the adapters of the backends
+#[derive(Debug, Clone, Default)]
+pub struct AdapterConfig {
+    /// Index in `--list-adapters` or part of the name of the adapter, the
+    /// preferences below are ignored when it is set
+    pub select: Option<String>,
+    pub power_preference: PowerPreference,
+    pub force_fallback: bool,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    ValueEnum,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum PowerPreference {
+    #[clap(help = "No preference.")]
+    None,
+    #[clap(help = "Integrated GPU, to save power.")]
+    Low,
+    #[default]
+    #[clap(help = "Dedicated GPU.")]
+    High,
+}
+
+/// Starting point and settings of the main camera, the angles are in
+/// degrees
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraConfig {
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fovy: f32,
+    pub znear: f32,
+    pub zfar: f32,
+    pub speed: f32,
+    pub sensitivity: f32,
+    pub zoom_sensitivity: f32,
+}
+
+/// Windows of the debug interface opened at startup
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DebugConfig {
+    pub window_open: bool,
+    pub console_open: bool,
+    /// Draw the debug interface in its own window instead of over the scene
+    pub detached: bool,
+}
+
+/// What a window opened with `App::add_window` draws
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WindowContent {
+    /// The scenes added to the window with `App::add_scene_to`
+    #[default]
+    Scenes,
+    /// The shapes of the scenes of the main window, seen from the camera of
+    /// this window
+    MainScenes,
+    /// Nothing but the debug interface of the window
+    Interface,
+}
+
+/// Window opened next to the main window, drawn with the same device
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowConfig {
+    pub title: String,
+    pub size: (u32, u32),
+    pub content: WindowContent,
+    /// Give the window its own debug interface, showing the debug items of
+    /// its scenes
+    pub debug: bool,
+}
+
+impl WindowConfig {
+    pub fn new<S: ToString>(title: S) -> Self {
+        Self {
+            title: title.to_string(),
+            size: (DEFAULT_WINDOW_WIDTH, DEFAULT_WINDOW_HEIGHT),
+            content: WindowContent::default(),
+            debug: false,
+        }
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.size = (width, height);
+        self
+    }
+
+    pub fn with_content(mut self, content: WindowContent) -> Self {
+        self.content = content;
+        self
+    }
+
+    pub fn with_debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+}
+
+/// Content of the configuration file
+///
+/// The options of the command line are optional here, the command line
+/// fills the missing ones.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    window: WindowFile,
+    adapter: AdapterFile,
+    graphics: GraphicsFile,
+    camera: CameraConfig,
+    keys: Bindings,
+    debug: DebugFile,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct WindowFile {
+    title: Option<String>,
+    mode: Option<WindowSizeHint>,
+    width: Option<u32>,
+    height: Option<u32>,
+    present_mode: Option<PresentModeConfig>,
+    frame_latency: Option<u32>,
+    redraw: Option<RedrawMode>,
+    max_fps: Option<u32>,
+    monitor: Option<String>,
+    video_mode: Option<VideoModeSelection>,
+    fullscreen: Option<FullscreenMode>,
+    backend: Option<BackendSelection>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AdapterFile {
+    select: Option<String>,
+    power_preference: Option<PowerPreference>,
+    force_fallback: Option<bool>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GraphicsFile {
+    msaa: Option<u32>,
+    shadows: Option<bool>,
+    shadow_resolution: Option<u32>,
+    shadow_bias: Option<f32>,
+    background: Option<BackgroundMode>,
+    skybox: Option<Vec<PathBuf>>,
+    grid: Option<bool>,
+    axes: Option<bool>,
+    gizmo: Option<bool>,
+    viewports: Option<ViewportLayout>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DebugFile {
+    enabled: Option<bool>,
+    window_open: bool,
+    console_open: bool,
+    detached: Option<bool>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ShadowConfig {
+    pub enabled: bool,
+    pub resolution: u32,
+    pub bias: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BackgroundConfig {
+    pub mode: BackgroundMode,
+    pub skybox: Vec<PathBuf>,
+}
+
+/// Visual helpers drawn over the scene, all hidden by default
+#[derive(Debug, Clone, Default)]
+pub struct HelpersConfig {
+    pub grid: bool,
+    pub axes: bool,
+    pub gizmo: bool,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    ValueEnum,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackgroundMode {
+    #[default]
+    #[clap(help = "Plain clear color.")]
+    Color,
+    #[clap(help = "Vertical gradient between two colors.")]
+    Gradient,
+    #[clap(help = "Procedural sky with a sun.")]
+    Sky,
+    #[clap(help = "Skybox loaded from the --skybox images.")]
+    Skybox,
+}
+
+/// Extra views showing the scene from the top, the front and the side
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    ValueEnum,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ViewportLayout {
+    #[default]
+    #[clap(help = "Only the main view.")]
+    None,
+    #[clap(help = "Top view in a corner of the window.")]
+    PictureInPicture,
+    #[clap(help = "Top, front and side views in a column on the right.")]
+    Split,
+    #[clap(help = "Top, front and side views in the debug window.")]
+    Debug,
+}
+
+/// When the frames are drawn, see `utils::FramePacer`
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    ValueEnum,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum RedrawMode {
+    #[default]
+    #[clap(help = "Draw a frame as soon as the previous one is presented.")]
+    Continuous,
+    #[clap(
+        help = "Only draw after an input, a change of the camera, the debug window or a scene."
+    )]
+    Reactive,
 }
 
 /// Enum to hold the different window sizes
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(Debug, Clone, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum WindowSizeHint {
-    #[clap(help = "Fullscreen mode with borders.")]
+    #[clap(help = "Exclusive fullscreen, in the selected video mode.")]
     Fullscreen,
     #[clap(help = "Fullscreen mode without borders.")]
     FullscreenBorderless,
@@ -61,13 +699,14 @@ pub enum WindowSizeHint {
     Windowed,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct WindowSizeConfig {
     pub hint: WindowSizeHint,
     pub size: (u32, u32),
 }
 
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(Debug, Clone, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum PresentModeConfig {
     #[clap(help = "Chooses FifoRelaxed -> Fifo based on availability.")]
     AutoVsync,
@@ -89,7 +728,8 @@ pub enum PresentModeConfig {
     Mailbox,
 }
 
-#[derive(Debug, Clone, ValueEnum)]
+#[derive(Debug, Clone, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum BackendSelection {
     #[clap(
         help = "Use the Vulkan backend. (Windows, Linux, Android, MacOS via vulkan-portability/MoltenVK)"
@@ -117,6 +757,209 @@ fn to_wgpu_backend(value: Option<BackendSelection>) -> wgpu::Backends {
     }
 }
 
+impl PowerPreference {
+    pub fn to_wgpu(self) -> wgpu::PowerPreference {
+        match self {
+            PowerPreference::None => wgpu::PowerPreference::None,
+            PowerPreference::Low => wgpu::PowerPreference::LowPower,
+            PowerPreference::High => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+fn from_wgpu_backend(backends: wgpu::Backends) -> Option<BackendSelection> {
+    match backends {
+        wgpu::Backends::VULKAN => Some(BackendSelection::Vulkan),
+        wgpu::Backends::METAL => Some(BackendSelection::Metal),
+        wgpu::Backends::DX12 => Some(BackendSelection::DirectX12),
+        wgpu::Backends::GL => Some(BackendSelection::OpenGL),
+        _ => None,
+    }
+}
+
+impl VideoModeSelection {
+    /// Whether the video mode at `index` in the list of its monitor is
+    /// selected, the refresh rate is in mHz
+    pub fn matches(
+        &self,
+        index: usize,
+        size: (u32, u32),
+        refresh_rate_millihertz: u32,
+    ) -> bool {
+        match *self {
+            Self::Index(selected) => index == selected,
+            Self::Size {
+                width,
+                height,
+                refresh_rate,
+            } => {
+                size == (width, height)
+                    && refresh_rate.is_none_or(|refresh_rate| {
+                        (refresh_rate_millihertz + 500) / 1000 == refresh_rate
+                    })
+            }
+        }
+    }
+}
+
+/// `3` for an index, `1920x1080` or `1920x1080@144` for a size
+impl std::str::FromStr for VideoModeSelection {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Ok(index) = value.parse::<usize>() {
+            return Ok(Self::Index(index));
+        }
+        let invalid = || {
+            format!(
+                "invalid video mode {:?}, expected an index or \
+                 WIDTHxHEIGHT[@HZ]",
+                value
+            )
+        };
+        let (size, refresh_rate) = match value.split_once('@') {
+            Some((size, refresh_rate)) => (
+                size,
+                Some(refresh_rate.parse::<u32>().map_err(|_| invalid())?),
+            ),
+            None => (value, None),
+        };
+        let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+        Ok(Self::Size {
+            width: width.parse().map_err(|_| invalid())?,
+            height: height.parse().map_err(|_| invalid())?,
+            refresh_rate,
+        })
+    }
+}
+
+impl TryFrom<String> for VideoModeSelection {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl std::fmt::Display for VideoModeSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "{}", index),
+            Self::Size {
+                width,
+                height,
+                refresh_rate: None,
+            } => write!(f, "{}x{}", width, height),
+            Self::Size {
+                width,
+                height,
+                refresh_rate: Some(refresh_rate),
+            } => write!(f, "{}x{}@{}", width, height, refresh_rate),
+        }
+    }
+}
+
+impl From<VideoModeSelection> for String {
+    fn from(value: VideoModeSelection) -> Self {
+        value.to_string()
+    }
+}
+
+/// Only accepts `MSAA_SAMPLE_COUNTS`
+fn msaa_parser() -> impl clap::builder::TypedValueParser<Value = u32> {
+    PossibleValuesParser::new(["1", "4"])
+        .map(|count| count.parse::<u32>().unwrap_or(1))
+}
+
+/// Value given on the command line or in the environment, else the one of
+/// the file, else the default of the argument
+fn pick<T>(matches: &ArgMatches, id: &str, cli: T, file: Option<T>) -> T {
+    match matches.value_source(id) {
+        Some(ValueSource::CommandLine | ValueSource::EnvVariable) => cli,
+        _ => file.unwrap_or(cli),
+    }
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            position: DEFAULT_CAMERA_POSITION,
+            yaw: DEFAULT_CAMERA_YAW.0,
+            pitch: DEFAULT_CAMERA_PITCH.0,
+            fovy: DEFAULT_CAMERA_FOVY.0,
+            znear: DEFAULT_CAMERA_ZNEAR,
+            zfar: DEFAULT_CAMERA_ZFAR,
+            speed: DEFAULT_CAMERA_SPEED,
+            sensitivity: DEFAULT_CAMERA_SENSITIVITY,
+            zoom_sensitivity: DEFAULT_CAMERA_ZOOM_SENSITIVITY,
+        }
+    }
+}
+
+impl FileConfig {
+    /// The file given with `--config`, else the first file found in the
+    /// working directory and the user configuration directory, with its path
+    fn find(
+        path: Option<&Path>,
+    ) -> Result<Option<(PathBuf, Self)>, crate::Error> {
+        if let Some(path) = path {
+            return Self::load(path)
+                .map(|file| Some((path.to_path_buf(), file)));
+        }
+        std::iter::once(PathBuf::from(CONFIG_FILE_NAME))
+            .chain(Self::user_file())
+            .find(|path| path.is_file())
+            .map(|path| Self::load(&path).map(|file| (path, file)))
+            .transpose()
+    }
+
+    fn user_file() -> Option<PathBuf> {
+        dirs::config_dir().map(|directory| {
+            directory.join(env!("CARGO_PKG_NAME")).join("config.toml")
+        })
+    }
+
+    fn load(path: &Path) -> Result<Self, crate::Error> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| crate::Error::asset(path, error))?;
+        let file = toml::from_str::<Self>(&text)
+            .map_err(|error| crate::Error::asset(path, error))?;
+        if let Some(msaa) = file.graphics.msaa {
+            if !MSAA_SAMPLE_COUNTS.contains(&msaa) {
+                return Err(crate::Error::asset(
+                    path,
+                    format!(
+                        "msaa must be one of {:?}, not {}",
+                        MSAA_SAMPLE_COUNTS, msaa
+                    ),
+                ));
+            }
+        }
+        if let Some(frame_latency) = file.window.frame_latency {
+            if !FRAME_LATENCIES.contains(&frame_latency) {
+                return Err(crate::Error::asset(
+                    path,
+                    format!(
+                        "frame_latency must be in {:?}, not {}",
+                        FRAME_LATENCIES, frame_latency
+                    ),
+                ));
+            }
+        }
+        Ok(file)
+    }
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            resolution: DEFAULT_SHADOW_RESOLUTION,
+            bias: DEFAULT_SHADOW_BIAS,
+        }
+    }
+}
+
 impl Default for WindowSizeConfig {
     fn default() -> Self {
         Self {
@@ -127,8 +970,96 @@ impl Default for WindowSizeConfig {
 }
 
 impl Config {
+    /// Read the command line, the environment and the configuration file
+    ///
+    /// Exits after printing the configuration with `--dump-config`, the
+    /// adapters with `--list-adapters`, the monitors with `--list-monitors`,
+    /// or the error when the configuration file cannot be read.
     pub fn init() -> Self {
-        ClapConfig::init().compute()
+        let matches = ClapConfig::command().get_matches();
+        let clap_config = ClapConfig::from_arg_matches(&matches)
+            .unwrap_or_else(|error| error.exit());
+        let dump_config = clap_config.dump_config;
+        let list_adapters = clap_config.list_adapters;
+        let list_monitors = clap_config.list_monitors;
+        let config = clap_config.compute(&matches).unwrap_or_else(|error| {
+            eprintln!("error: {}", error);
+            std::process::exit(2);
+        });
+        if dump_config {
+            print!("{}", config.to_toml());
+            std::process::exit(0);
+        }
+        if list_adapters {
+            print!("{}", crate::render::describe_adapters(config.backends));
+            std::process::exit(0);
+        }
+        if list_monitors {
+            match crate::render::describe_monitors() {
+                Ok(monitors) => print!("{}", monitors),
+                Err(error) => {
+                    eprintln!("error: {}", error);
+                    std::process::exit(2);
+                }
+            }
+            std::process::exit(0);
+        }
+        config
+    }
+
+    /// File where the choices of the debug window are saved, the one which
+    /// has been read, else the one of the user configuration directory
+    pub fn settings_file(&self) -> PathBuf {
+        self.config_file
+            .clone()
+            .or_else(FileConfig::user_file)
+            .unwrap_or_else(|| PathBuf::from(CONFIG_FILE_NAME))
+    }
+
+    /// The configuration in the format of the configuration file
+    pub fn to_toml(&self) -> String {
+        let file = FileConfig {
+            window: WindowFile {
+                title: Some(self.window_title.clone()),
+                mode: Some(self.window_size.hint.clone()),
+                width: Some(self.window_size.size.0),
+                height: Some(self.window_size.size.1),
+                present_mode: self.present_mode.clone(),
+                frame_latency: Some(self.frame_latency),
+                redraw: Some(self.redraw),
+                max_fps: Some(self.max_fps),
+                monitor: self.display.monitor.clone(),
+                video_mode: self.display.video_mode,
+                fullscreen: Some(self.display.fullscreen),
+                backend: from_wgpu_backend(self.backends),
+            },
+            adapter: AdapterFile {
+                select: self.adapter.select.clone(),
+                power_preference: Some(self.adapter.power_preference),
+                force_fallback: Some(self.adapter.force_fallback),
+            },
+            graphics: GraphicsFile {
+                msaa: Some(self.msaa),
+                shadows: Some(self.shadow.enabled),
+                shadow_resolution: Some(self.shadow.resolution),
+                shadow_bias: Some(self.shadow.bias),
+                background: Some(self.background.mode),
+                skybox: Some(self.background.skybox.clone()),
+                grid: Some(self.helpers.grid),
+                axes: Some(self.helpers.axes),
+                gizmo: Some(self.helpers.gizmo),
+                viewports: Some(self.viewports),
+            },
+            camera: self.camera.clone(),
+            keys: self.keys.clone(),
+            debug: DebugFile {
+                enabled: Some(!self.disable_egui),
+                window_open: self.debug.window_open,
+                console_open: self.debug.console_open,
+                detached: Some(self.debug.detached),
+            },
+        };
+        toml::to_string_pretty(&file).unwrap_or_default()
     }
 
     pub fn to_wgpu_present_mode(
@@ -174,20 +1105,310 @@ impl Config {
 }
 
 impl ClapConfig {
-    fn compute(self) -> Config {
+    /// Fill the options missing from the command line and the environment
+    /// with the configuration file
+    fn compute(self, matches: &ArgMatches) -> Result<Config, crate::Error> {
+        let (path, file) = FileConfig::find(self.config.as_deref())?.unzip();
+        let mut config = self.merge(matches, file.unwrap_or_default());
+        config.config_file = path;
+        Ok(config)
+    }
+
+    fn merge(self, matches: &ArgMatches, file: FileConfig) -> Config {
+        let window = file.window;
+        let adapter = file.adapter;
+        let graphics = file.graphics;
         Config {
-            backends: to_wgpu_backend(self.backend),
-            disable_egui: self.disable_egui,
-            present_mode: self.present_mode,
-            window_title: self.window_title,
+            backends: to_wgpu_backend(pick(
+                matches,
+                "backend",
+                self.backend,
+                window.backend.map(Some),
+            )),
+            adapter: AdapterConfig {
+                select: pick(
+                    matches,
+                    "adapter",
+                    self.adapter,
+                    adapter.select.map(Some),
+                ),
+                power_preference: pick(
+                    matches,
+                    "power_preference",
+                    self.power_preference,
+                    adapter.power_preference,
+                ),
+                force_fallback: pick(
+                    matches,
+                    "force_fallback_adapter",
+                    self.force_fallback_adapter,
+                    adapter.force_fallback,
+                ),
+            },
+            disable_egui: pick(
+                matches,
+                "disable_egui",
+                self.disable_egui,
+                file.debug.enabled.map(|enabled| !enabled),
+            ),
+            present_mode: pick(
+                matches,
+                "present_mode",
+                self.present_mode,
+                window.present_mode.map(Some),
+            ),
+            frame_latency: pick(
+                matches,
+                "frame_latency",
+                self.frame_latency,
+                window.frame_latency,
+            ),
+            redraw: pick(matches, "redraw", self.redraw, window.redraw),
+            max_fps: pick(matches, "max_fps", self.max_fps, window.max_fps),
+            window_title: pick(
+                matches,
+                "window_title",
+                self.window_title,
+                window.title,
+            ),
             window_size: WindowSizeConfig {
-                hint: self.screen_mode,
-                size: (self.window_width, self.window_height),
+                hint: pick(
+                    matches,
+                    "screen_mode",
+                    self.screen_mode,
+                    window.mode,
+                ),
+                size: (
+                    pick(
+                        matches,
+                        "window_width",
+                        self.window_width,
+                        window.width,
+                    ),
+                    pick(
+                        matches,
+                        "window_height",
+                        self.window_height,
+                        window.height,
+                    ),
+                ),
+            },
+            config_file: None,
+            display: DisplayConfig {
+                monitor: pick(
+                    matches,
+                    "monitor",
+                    self.monitor,
+                    window.monitor.map(Some),
+                ),
+                video_mode: pick(
+                    matches,
+                    "video_mode",
+                    self.video_mode,
+                    window.video_mode.map(Some),
+                ),
+                fullscreen: pick(
+                    matches,
+                    "fullscreen",
+                    self.fullscreen,
+                    window.fullscreen,
+                ),
+            },
+            shadow: ShadowConfig {
+                enabled: !pick(
+                    matches,
+                    "disable_shadows",
+                    self.disable_shadows,
+                    graphics.shadows.map(|enabled| !enabled),
+                ),
+                resolution: pick(
+                    matches,
+                    "shadow_resolution",
+                    self.shadow_resolution,
+                    graphics.shadow_resolution,
+                ),
+                bias: pick(
+                    matches,
+                    "shadow_bias",
+                    self.shadow_bias,
+                    graphics.shadow_bias,
+                ),
             },
+            background: BackgroundConfig {
+                mode: pick(
+                    matches,
+                    "background",
+                    self.background,
+                    graphics.background,
+                ),
+                skybox: pick(matches, "skybox", self.skybox, graphics.skybox),
+            },
+            helpers: HelpersConfig {
+                grid: pick(matches, "grid", self.grid, graphics.grid),
+                axes: pick(matches, "axes", self.axes, graphics.axes),
+                gizmo: pick(matches, "gizmo", self.gizmo, graphics.gizmo),
+            },
+            viewports: pick(
+                matches,
+                "viewports",
+                self.viewports,
+                graphics.viewports,
+            ),
+            msaa: pick(matches, "msaa", self.msaa, graphics.msaa),
+            camera: file.camera,
+            keys: file.keys,
+            debug: DebugConfig {
+                window_open: file.debug.window_open,
+                console_open: file.debug.console_open,
+                detached: pick(
+                    matches,
+                    "detach_debug",
+                    self.detach_debug,
+                    file.debug.detached,
+                ),
+            },
+            benchmark: self.benchmark.map(|length| BenchmarkConfig {
+                length,
+                output: self.benchmark_output,
+            }),
+            record: self.record,
+            replay: self.replay,
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::keyboard::KeyCode;
+
+    use super::*;
+    use crate::input::{Action, Input};
+
+    fn merge(args: &[&str], file: &str) -> Config {
+        let matches = ClapConfig::command()
+            .try_get_matches_from(
+                std::iter::once("test-wgpu").chain(args.iter().copied()),
+            )
+            .unwrap();
+        let clap_config = ClapConfig::from_arg_matches(&matches).unwrap();
+        clap_config.merge(&matches, toml::from_str(file).unwrap())
+    }
+
+    #[test]
+    fn test_precedence() {
+        let file = r#"
+            [window]
+            title = "From the file"
+            width = 1024
+            redraw = "reactive"
+
+            [graphics]
+            msaa = 4
+            shadows = false
+            background = "sky"
+            grid = true
+
+            [camera]
+            speed = 3.0
+
+            [keys]
+            quit = ["KeyQ"]
+        "#;
+        let config = merge(&["--width", "640"], file);
+        // The command line wins over the file
+        assert_eq!(config.window_size.size, (640, 600));
+        // The file wins over the defaults
+        assert_eq!(config.window_title, "From the file");
+        assert_eq!(config.redraw, RedrawMode::Reactive);
+        assert_eq!(config.max_fps, 0);
+        assert_eq!(config.msaa, 4);
+        assert!(!config.shadow.enabled);
+        assert_eq!(config.background.mode, BackgroundMode::Sky);
+        assert!(config.helpers.grid);
+        assert!(!config.helpers.axes);
+        assert_eq!(config.camera.speed, 3.0);
+        assert_eq!(config.camera.fovy, DEFAULT_CAMERA_FOVY.0);
+        assert_eq!(
+            config.keys.inputs(&Action::Quit),
+            [Input::Key(KeyCode::KeyQ)]
+        );
+        assert_eq!(
+            config.keys.inputs(&Action::ToggleFullscreen),
+            [Input::Key(KeyCode::F11)]
+        );
+
+        let config = merge(&["--disable-shadows"], "");
+        assert!(!config.shadow.enabled);
+        assert_eq!(config.window_title, "Test WGPU");
+    }
+
+    #[test]
+    fn test_dump_round_trip() {
+        let config = merge(&["--msaa", "4", "--mode", "fullscreen"], "");
+        let dumped = merge(&[], &config.to_toml());
+        assert_eq!(dumped.to_toml(), config.to_toml());
+        assert_eq!(dumped.msaa, 4);
+        assert_eq!(dumped.frame_latency, DEFAULT_FRAME_LATENCY);
+        assert!(toml::from_str::<FileConfig>("[window]\nsize = 3").is_err());
+    }
+
+    #[test]
+    fn test_video_mode() {
+        let config = merge(
+            &["--video-mode", "1920x1080@144", "--fullscreen", "exclusive"],
+            "",
+        );
+        let dumped = merge(&[], &config.to_toml());
+        assert_eq!(dumped.display, config.display);
+        assert_eq!(
+            dumped.display.video_mode,
+            Some(VideoModeSelection::Size {
+                width: 1920,
+                height: 1080,
+                refresh_rate: Some(144),
+            })
+        );
+        assert_eq!(dumped.display.fullscreen, FullscreenMode::Exclusive);
+
+        let mode = "2560x1440".parse::<VideoModeSelection>().unwrap();
+        assert!(mode.matches(4, (2560, 1440), 59_951));
+        assert!(!mode.matches(4, (1920, 1080), 59_951));
+        let mode = "2560x1440@60".parse::<VideoModeSelection>().unwrap();
+        assert!(mode.matches(4, (2560, 1440), 59_951));
+        assert!(!mode.matches(4, (2560, 1440), 143_998));
+        assert_eq!("3".parse(), Ok(VideoModeSelection::Index(3)));
+        assert!("1920x".parse::<VideoModeSelection>().is_err());
+        assert!("1920x1080@fast".parse::<VideoModeSelection>().is_err());
+    }
+
+    #[test]
+    fn test_save_display() {
+        let path = std::env::temp_dir().join(format!(
+            "{}-save-display-{}.toml",
+            env!("CARGO_PKG_NAME"),
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            "# Written by hand\n[window]\ntitle = \"Saved\" # kept\n\
+             monitor = \"0\"\n\n[camera]\nspeed = 2.0\n",
+        )
+        .unwrap();
+        let display = DisplayConfig {
+            monitor: None,
+            video_mode: Some(VideoModeSelection::Index(2)),
+            fullscreen: FullscreenMode::Exclusive,
+        };
+        display.save(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
 
-    pub fn init() -> ClapConfig {
-        Self::parse()
+        let config = merge(&[], &text);
+        assert_eq!(config.display, display);
+        // The other settings of the file are kept
+        assert_eq!(config.window_title, "Saved");
+        assert!(text.starts_with("# Written by hand\n[window]\n"));
+        assert!(text.contains("title = \"Saved\" # kept\n"));
+        assert!(text.find("[window]") < text.find("[camera]"));
     }
 }
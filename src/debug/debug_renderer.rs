@@ -9,6 +9,15 @@ pub struct DebugRenderer {
     pub context: Context,
     state: State,
     renderer: Renderer,
+    /// Delay after which egui wants to be drawn again, to end its
+    /// animations
+    repaint_delay: Option<std::time::Duration>,
+    /// Input of the next frame instead of the one of the window, see
+    /// `replay_input`
+    replayed_input: Option<egui::RawInput>,
+    /// Input of the last frame, kept while recording
+    recorded_input: Option<egui::RawInput>,
+    recording: bool,
 }
 
 impl DebugRenderer {
@@ -52,9 +61,25 @@ impl DebugRenderer {
             context: egui_context,
             state: egui_state,
             renderer: egui_renderer,
+            repaint_delay: None,
+            replayed_input: None,
+            recorded_input: None,
+            recording: false,
         }
     }
 
+    pub fn register_texture(
+        &mut self,
+        device: &wgpu::Device,
+        view: &wgpu::TextureView,
+    ) -> egui::TextureId {
+        self.renderer.register_native_texture(
+            device,
+            view,
+            wgpu::FilterMode::Linear,
+        )
+    }
+
     pub fn handle_input(
         &mut self,
         window: &winit::window::Window,
@@ -64,13 +89,45 @@ impl DebugRenderer {
         response.consumed
     }
 
+    /// Keep the input of every frame for `take_recorded_input`
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Input of the last frame drawn while recording
+    pub fn take_recorded_input(&mut self) -> Option<egui::RawInput> {
+        self.recorded_input.take()
+    }
+
+    /// Draw the next frame with a recorded input, the input of the window is
+    /// dropped
+    pub fn replay_input(&mut self, input: egui::RawInput) {
+        self.replayed_input = Some(input);
+    }
+
+    /// Delay after which the last frame of egui must be drawn again, `None`
+    /// if it does not change by itself
+    pub fn repaint_delay(&self) -> Option<std::time::Duration> {
+        self.repaint_delay
+    }
+
     pub fn draw(
         &mut self,
         gpu: &GraphicalProcessUnit,
         pipeline: DrawPipeline,
         run_ui: impl FnOnce(&egui::Context),
     ) {
-        let raw_input = self.state.take_egui_input(pipeline.window);
+        let mut raw_input = self.state.take_egui_input(pipeline.window);
+        if let Some(replayed_input) = self.replayed_input.take() {
+            raw_input = replayed_input;
+        }
+        if self.recording {
+            self.recorded_input = Some(raw_input.clone());
+        }
 
         let full_output = self.context.run(raw_input, |_ui| {
             run_ui(&self.context);
@@ -80,6 +137,11 @@ impl DebugRenderer {
             pipeline.window,
             full_output.platform_output,
         );
+        self.repaint_delay = full_output
+            .viewport_output
+            .get(&self.context.viewport_id())
+            .map(|output| output.repaint_delay)
+            .filter(|delay| *delay != std::time::Duration::MAX);
 
         let tris = self
             .context
@@ -118,7 +180,7 @@ impl DebugRenderer {
                     )],
                     depth_stencil_attachment: None,
                     label: Some("Debug View Render Pass"),
-                    timestamp_writes: None,
+                    timestamp_writes: pipeline.timestamp_writes,
                     occlusion_query_set: None,
                 });
 
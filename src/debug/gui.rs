@@ -1,10 +1,13 @@
 use std::{cell::RefCell, rc::Rc};
 
 use super::widget::{debug::DebugItem, Logger};
+use crate::config::DebugConfig;
 
 pub struct Debug {
     debug_widget: Logger,
     debug_items: Vec<Rc<RefCell<dyn DebugItem>>>,
+    /// Windows opened the first time they are shown
+    state: DebugConfig,
 }
 
 struct Separator;
@@ -20,10 +23,11 @@ impl DebugItem for Separator {
 }
 
 impl Debug {
-    pub fn init() -> Self {
+    pub fn init(state: &DebugConfig) -> Self {
         Self {
             debug_widget: Logger::new(),
             debug_items: Vec::new(),
+            state: state.clone(),
         }
     }
 
@@ -44,7 +48,7 @@ impl Debug {
     pub fn run_ui(&mut self, ui: &egui::Context) {
         egui::Window::new("Debug Window")
             // .vscroll(true)
-            .default_open(false)
+            .default_open(self.state.window_open)
             .max_width(1000.0)
             .max_height(800.0)
             .default_width(800.0)
@@ -58,7 +62,7 @@ impl Debug {
 
         if !self.debug_widget.is_empty() {
             egui::Window::new("Debug Console")
-                .default_open(false)
+                .default_open(self.state.console_open)
                 .max_width(Logger::MAX_WIDTH)
                 .resizable(true)
                 .show(ui, |ui| {
@@ -0,0 +1,92 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::debug::DebugItem;
+use crate::input::{Action, Bindings, Input};
+
+/// Text field per action with the names of its inputs separated by commas
+pub struct BindingsEditor {
+    name: String,
+    bindings: Bindings,
+    /// Text of each action, with the error of the text if it is invalid
+    rows: Vec<(Action, String, Option<String>)>,
+    has_been_updated: bool,
+}
+
+impl BindingsEditor {
+    pub fn new<S: ToString>(bindings: &Bindings, name: S) -> Rc<RefCell<Self>> {
+        let mut editor = Self {
+            name: name.to_string(),
+            bindings: bindings.clone(),
+            rows: Vec::new(),
+            has_been_updated: false,
+        };
+        editor.set(bindings);
+        Rc::new(RefCell::new(editor))
+    }
+
+    pub fn get(&self) -> &Bindings {
+        &self.bindings
+    }
+
+    pub fn set(&mut self, bindings: &Bindings) {
+        self.bindings = bindings.clone();
+        self.rows = bindings
+            .iter()
+            .map(|(action, inputs)| {
+                let text = inputs
+                    .iter()
+                    .map(Input::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                (action.clone(), text, None)
+            })
+            .collect();
+    }
+
+    pub fn callback_update(&mut self, f: impl FnOnce(&Bindings)) {
+        if self.has_been_updated {
+            log::trace!("Updating {} to {:?}", self.name, self.bindings);
+            f(&self.bindings);
+            self.has_been_updated = false;
+        }
+    }
+}
+
+fn parse_inputs(text: &str) -> Result<Vec<Input>, String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::parse)
+        .collect()
+}
+
+impl DebugItem for BindingsEditor {
+    fn draw(&mut self, ui: &mut egui::Ui) {
+        ui.collapsing(&self.name, |ui| {
+            egui::Grid::new(&self.name).num_columns(2).show(ui, |ui| {
+                for (action, text, error) in self.rows.iter_mut() {
+                    ui.label(action.name());
+                    ui.vertical(|ui| {
+                        if ui.text_edit_singleline(text).changed() {
+                            match parse_inputs(text) {
+                                Ok(inputs) => {
+                                    *error = None;
+                                    self.bindings.set(action.clone(), inputs);
+                                    self.has_been_updated = true;
+                                }
+                                Err(message) => *error = Some(message),
+                            }
+                        }
+                        if let Some(error) = error {
+                            ui.colored_label(
+                                egui::Color32::RED,
+                                error.as_str(),
+                            );
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,71 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::debug::DebugItem;
+
+/// Combo box selecting one option among a list of labels
+pub struct Choice {
+    name: String,
+    options: Vec<String>,
+    selected: usize,
+    has_been_updated: bool,
+}
+
+impl Choice {
+    pub fn new<S: ToString>(
+        options: Vec<String>,
+        selected: usize,
+        name: S,
+    ) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            name: name.to_string(),
+            options,
+            selected,
+            has_been_updated: false,
+        }))
+    }
+
+    pub fn get(&self) -> usize {
+        self.selected
+    }
+
+    pub fn set(&mut self, selected: usize) {
+        self.selected = selected;
+    }
+
+    /// Replace the options, without calling the update callback
+    pub fn set_options(&mut self, options: Vec<String>, selected: usize) {
+        self.options = options;
+        self.selected = selected;
+    }
+
+    pub fn callback_update(&mut self, f: impl FnOnce(usize)) {
+        if self.has_been_updated {
+            log::trace!(
+                "Updating {} to {:?}",
+                self.name,
+                self.options.get(self.selected)
+            );
+            f(self.selected);
+            self.has_been_updated = false;
+        }
+    }
+}
+
+impl DebugItem for Choice {
+    fn draw(&mut self, ui: &mut egui::Ui) {
+        let selected_text =
+            self.options.get(self.selected).cloned().unwrap_or_default();
+        egui::ComboBox::from_label(&self.name)
+            .selected_text(selected_text)
+            .show_ui(ui, |ui| {
+                for (i, option) in self.options.iter().enumerate() {
+                    if ui
+                        .selectable_value(&mut self.selected, i, option)
+                        .changed()
+                    {
+                        self.has_been_updated = true;
+                    }
+                }
+            });
+    }
+}
@@ -0,0 +1,47 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::debug::DebugItem;
+
+pub struct Image {
+    name: String,
+    texture: Option<egui::TextureId>,
+    size: egui::Vec2,
+    /// Set when the image is drawn, until `take_visible`
+    visible: bool,
+}
+
+impl Image {
+    pub fn new<S: ToString>(
+        texture: Option<egui::TextureId>,
+        size: [f32; 2],
+        name: S,
+    ) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            name: name.to_string(),
+            texture,
+            size: size.into(),
+            visible: false,
+        }))
+    }
+
+    pub fn set_texture(&mut self, texture: Option<egui::TextureId>) {
+        self.texture = texture;
+    }
+
+    /// Whether the image has been shown since the last call, to skip the
+    /// update of its texture while it is hidden
+    pub fn take_visible(&mut self) -> bool {
+        std::mem::take(&mut self.visible)
+    }
+}
+
+impl DebugItem for Image {
+    fn draw(&mut self, ui: &mut egui::Ui) {
+        if let Some(texture) = self.texture {
+            let response = ui.collapsing(&self.name, |ui| {
+                ui.image(egui::load::SizedTexture::new(texture, self.size));
+            });
+            self.visible |= response.body_returned.is_some();
+        }
+    }
+}
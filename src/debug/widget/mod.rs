@@ -2,17 +2,25 @@
 pub mod debug;
 
 mod barchart;
+mod bindings_editor;
 mod button;
+mod choice;
 pub mod color;
+mod image;
 mod label;
 mod logger;
+mod ordered_list;
 mod slider;
 mod value;
 
 pub use barchart::BarChart;
+pub use bindings_editor::BindingsEditor;
 pub use button::Button;
+pub use choice::Choice;
 pub use color::ColorPicker;
+pub use image::Image;
 pub use label::Label;
 pub use logger::Logger;
+pub use ordered_list::OrderedList;
 pub use slider::Slider;
 pub use value::Value;
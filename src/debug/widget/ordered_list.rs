@@ -0,0 +1,69 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::debug::DebugItem;
+
+/// List of toggleable items that can be reordered with up/down buttons
+pub struct OrderedList {
+    name: String,
+    items: Vec<(String, bool)>,
+    has_been_updated: bool,
+}
+
+impl OrderedList {
+    pub fn new<S: ToString>(
+        items: Vec<(String, bool)>,
+        name: S,
+    ) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            name: name.to_string(),
+            items,
+            has_been_updated: false,
+        }))
+    }
+
+    pub fn get(&self) -> &[(String, bool)] {
+        &self.items
+    }
+
+    pub fn set(&mut self, items: Vec<(String, bool)>) {
+        self.items = items;
+    }
+
+    pub fn callback_update(&mut self, f: impl FnOnce(&[(String, bool)])) {
+        if self.has_been_updated {
+            log::trace!("Updating {} to {:?}", self.name, self.items);
+            f(&self.items);
+            self.has_been_updated = false;
+        }
+    }
+}
+
+impl DebugItem for OrderedList {
+    fn draw(&mut self, ui: &mut egui::Ui) {
+        let mut swap = None;
+        let count = self.items.len();
+        ui.collapsing(&self.name, |ui| {
+            for (i, (label, enabled)) in self.items.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(i > 0, egui::Button::new("⬆")).clicked()
+                    {
+                        swap = Some((i, i - 1));
+                    }
+                    if ui
+                        .add_enabled(i + 1 < count, egui::Button::new("⬇"))
+                        .clicked()
+                    {
+                        swap = Some((i, i + 1));
+                    }
+                    if ui.checkbox(enabled, label.as_str()).changed() {
+                        self.has_been_updated = true;
+                    }
+                });
+            }
+        });
+        if let Some((a, b)) = swap {
+            self.items.swap(a, b);
+            self.has_been_updated = true;
+        }
+    }
+}
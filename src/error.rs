@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+
+/// Failures of the renderer
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    // Adapter selection
+    #[error("no suitable adapter found")]
+    NoAdapter,
+    #[error("no adapter matches {0:?}, see --list-adapters")]
+    AdapterNotFound(String),
+    #[error("the adapter {0} cannot draw on the window")]
+    AdapterIncompatible(String),
+    #[error("failed to request a device: {0}")]
+    RequestDevice(#[from] wgpu::RequestDeviceError),
+
+    // Surface configuration
+    #[error("failed to create the surface: {0}")]
+    CreateSurface(#[from] wgpu::CreateSurfaceError),
+    #[error("no sRGB format found on the surface")]
+    NoSurfaceFormat,
+    #[error("no usable present mode: {0}")]
+    PresentMode(&'static str),
+    #[error("failed to get the next frame: {0}")]
+    Surface(#[from] wgpu::SurfaceError),
+    /// The device must be recreated, see `Renderer::recover`
+    #[error("the device was lost: {0}")]
+    DeviceLost(String),
+
+    // Shader compilation
+    #[error("failed to compile the shader {label}: {message}")]
+    Shader { label: String, message: String },
+
+    // Asset loading
+    #[error("failed to load {}: {source}", path.display())]
+    Asset {
+        path: PathBuf,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("invalid skybox: {0}")]
+    Skybox(String),
+    #[error("failed to load the font: {0}")]
+    Font(String),
+    #[error("no shape at index {0}")]
+    ShapeNotFound(usize),
+
+    // Benchmark
+    #[error("failed to write the report {}: {source}", path.display())]
+    Report {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    // Configuration
+    #[error("failed to save the configuration {}: {source}", path.display())]
+    SaveConfig {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    // Recording
+    #[error("failed to write the recording {}: {source}", path.display())]
+    Record {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    // Window
+    #[error("the configuration was already used")]
+    NoConfig,
+    #[error("failed to create the event loop: {0}")]
+    EventLoop(#[from] winit::error::EventLoopError),
+    #[error("failed to create the window: {0}")]
+    Window(#[from] winit::error::OsError),
+    #[error("no window at index {0}")]
+    WindowNotFound(usize),
+    #[error("no monitor found for the window")]
+    NoMonitor,
+    #[error("no monitor matches {0:?}, see --list-monitors")]
+    MonitorNotFound(String),
+    #[error("no video mode found on the monitor")]
+    NoVideoMode,
+    #[error("no video mode matches {0}, see --list-monitors")]
+    VideoModeNotFound(String),
+}
+
+impl Error {
+    /// Whether the renderer can keep going once the device is recreated
+    pub fn needs_recovery(&self) -> bool {
+        matches!(
+            self,
+            Error::DeviceLost(_)
+                | Error::Surface(wgpu::SurfaceError::OutOfMemory)
+        )
+    }
+
+    /// Failure to load the file at `path`
+    pub(crate) fn asset<P, E>(path: P, source: E) -> Self
+    where
+        P: Into<PathBuf>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Error::Asset {
+            path: path.into(),
+            source: source.into(),
+        }
+    }
+}
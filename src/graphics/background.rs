@@ -0,0 +1,429 @@
+use std::path::PathBuf;
+
+use super::{camera::CameraBuffer, pipeline::DepthTexture, post::PostProcess};
+use crate::config::BackgroundMode;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct BackgroundUniform {
+    top: [f32; 4],
+    bottom: [f32; 4],
+    sun: [f32; 4],
+    params: [f32; 4],
+}
+
+/// Kind of image loaded for the skybox
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkyboxKind {
+    /// Six faces in the order +X, -X, +Y, -Y, +Z, -Z
+    Cubemap,
+    /// A single panorama image
+    Equirectangular,
+}
+
+/// Background drawn behind the geometry
+///
+/// `BackgroundMode::Color` only clears the target, the other modes draw a
+/// fullscreen triangle at the maximum depth after the scene.
+pub struct Background {
+    mode: BackgroundMode,
+    top: [f32; 3],
+    bottom: [f32; 3],
+    sun: cgmath::Vector3<f32>,
+    skybox: Option<SkyboxKind>,
+    cube_view: wgpu::TextureView,
+    equirectangular_view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    buffer: wgpu::Buffer,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    has_been_updated: bool,
+}
+
+impl Background {
+    pub fn init(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        camera: &CameraBuffer,
+        samples: u32,
+    ) -> Self {
+        // 1x1 placeholders until a skybox is loaded
+        let grey: &[u8] = &[128, 128, 128, 255];
+        let cube_view = Self::create_texture(
+            device,
+            queue,
+            1,
+            1,
+            &[grey; 6],
+            "Skybox Placeholder",
+        );
+        let equirectangular_view = Self::create_texture(
+            device,
+            queue,
+            1,
+            1,
+            &[grey],
+            "Equirectangular Placeholder",
+        );
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Background Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Background Buffer"),
+            size: std::mem::size_of::<BackgroundUniform>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let texture_entry =
+            |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float {
+                        filterable: true,
+                    },
+                    view_dimension,
+                    multisampled: false,
+                },
+                count: None,
+            };
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    texture_entry(1, wgpu::TextureViewDimension::Cube),
+                    texture_entry(2, wgpu::TextureViewDimension::D2),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+                label: Some("background_bind_group_layout"),
+            });
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &buffer,
+            &cube_view,
+            &equirectangular_view,
+            &sampler,
+        );
+
+        let shader =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Background Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("shaders/background.wgsl").into(),
+                ),
+            });
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Background Pipeline Layout"),
+                bind_group_layouts: &[
+                    camera.bind_group_layout(),
+                    &bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Background Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: PostProcess::HDR_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // Drawn at the far plane, only where there is no geometry
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DepthTexture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: samples,
+                    ..Default::default()
+                },
+                multiview: None,
+            });
+
+        Self {
+            mode: BackgroundMode::Color,
+            top: [0.25, 0.45, 0.85],
+            bottom: [0.75, 0.8, 0.9],
+            sun: cgmath::Vector3::new(0.4, 1.0, 0.3),
+            skybox: None,
+            cube_view,
+            equirectangular_view,
+            sampler,
+            buffer,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+            has_been_updated: true,
+        }
+    }
+
+    /// Create a 2D texture, or a cube texture if there are 6 layers
+    fn create_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        layers: &[&[u8]],
+        label: &str,
+    ) -> wgpu::TextureView {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: layers.len() as u32,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for (layer, data) in layers.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(label),
+            dimension: Some(if layers.len() == 6 {
+                wgpu::TextureViewDimension::Cube
+            } else {
+                wgpu::TextureViewDimension::D2
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        cube_view: &wgpu::TextureView,
+        equirectangular_view: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(cube_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        equirectangular_view,
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some("background_bind_group"),
+        })
+    }
+
+    /// Load a skybox from one equirectangular image or six cube faces
+    /// (+X, -X, +Y, -Y, +Z, -Z)
+    pub fn load_skybox(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paths: &[PathBuf],
+    ) -> Result<SkyboxKind, crate::Error> {
+        let images = paths
+            .iter()
+            .map(|path| {
+                log::info!("Loading skybox image {:?}", path);
+                image::open(path)
+                    .map(|image| image.to_rgba8())
+                    .map_err(|error| crate::Error::asset(path, error))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let kind = match images.len() {
+            1 => SkyboxKind::Equirectangular,
+            6 => SkyboxKind::Cubemap,
+            count => {
+                return Err(crate::Error::Skybox(format!(
+                    "a skybox needs 1 or 6 images, {} given",
+                    count
+                )))
+            }
+        };
+        let (width, height) = images[0].dimensions();
+        if images
+            .iter()
+            .any(|image| image.dimensions() != (width, height))
+        {
+            return Err(crate::Error::Skybox(
+                "the faces must have the same size".to_string(),
+            ));
+        }
+        if kind == SkyboxKind::Cubemap && width != height {
+            return Err(crate::Error::Skybox(
+                "the faces must be square".to_string(),
+            ));
+        }
+
+        let layers = images
+            .iter()
+            .map(|image| image.as_raw().as_slice())
+            .collect::<Vec<_>>();
+        let view = Self::create_texture(
+            device, queue, width, height, &layers, "Skybox",
+        );
+        match kind {
+            SkyboxKind::Cubemap => self.cube_view = view,
+            SkyboxKind::Equirectangular => self.equirectangular_view = view,
+        }
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.buffer,
+            &self.cube_view,
+            &self.equirectangular_view,
+            &self.sampler,
+        );
+        self.skybox = Some(kind);
+        self.has_been_updated = true;
+        Ok(kind)
+    }
+
+    pub fn mode(&self) -> BackgroundMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: BackgroundMode) {
+        if mode == BackgroundMode::Skybox && self.skybox.is_none() {
+            log::warn!("No skybox loaded, use --skybox to load one");
+        }
+        self.mode = mode;
+        self.has_been_updated = true;
+    }
+
+    pub fn gradient(&self) -> ([f32; 3], [f32; 3]) {
+        (self.top, self.bottom)
+    }
+
+    /// Colors used by the gradient, and by the sky for the zenith and the
+    /// horizon
+    pub fn set_gradient(&mut self, top: [f32; 3], bottom: [f32; 3]) {
+        self.top = top;
+        self.bottom = bottom;
+        self.has_been_updated = true;
+    }
+
+    pub fn set_sun_direction(&mut self, direction: cgmath::Vector3<f32>) {
+        self.sun = direction;
+        self.has_been_updated = true;
+    }
+
+    /// Refresh the uniform, needed when the background changed
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        if !self.has_been_updated {
+            return;
+        }
+        let mode = match (self.mode, self.skybox) {
+            (BackgroundMode::Color, _) => 0.0,
+            (BackgroundMode::Gradient, _) => 1.0,
+            (BackgroundMode::Sky, _) => 2.0,
+            (BackgroundMode::Skybox, Some(SkyboxKind::Equirectangular)) => 4.0,
+            (BackgroundMode::Skybox, _) => 3.0,
+        };
+        let uniform = BackgroundUniform {
+            top: [self.top[0], self.top[1], self.top[2], 1.0],
+            bottom: [self.bottom[0], self.bottom[1], self.bottom[2], 1.0],
+            sun: [self.sun.x, self.sun.y, self.sun.z, 0.9995],
+            params: [mode, 0.0, 0.0, 0.0],
+        };
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.has_been_updated = false;
+    }
+
+    /// Draw the background seen from a camera in a render pass that already
+    /// contains the scene
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera: &'a CameraBuffer,
+    ) {
+        if self.mode == BackgroundMode::Color {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera.bind_group(), &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -0,0 +1,420 @@
+use wgpu::util::DrawIndexedIndirectArgs;
+
+use super::types::{Buffer, InstanceRaw, Vertex};
+
+/// Features used by the indirect path when the adapter has them
+///
+/// `INDIRECT_FIRST_INSTANCE` is required, each draw reads its instances at
+/// an offset of the shared instance buffer. `MULTI_DRAW_INDIRECT` merges the
+/// draws into a single call.
+pub const INDIRECT_FEATURES: wgpu::Features =
+    wgpu::Features::INDIRECT_FIRST_INSTANCE
+        .union(wgpu::Features::MULTI_DRAW_INDIRECT);
+
+const VERTEX_SIZE: u64 = std::mem::size_of::<Vertex>() as u64;
+const INSTANCE_SIZE: u64 = std::mem::size_of::<InstanceRaw>() as u64;
+const INDEX_SIZE: u64 = std::mem::size_of::<u16>() as u64;
+const ARGS_SIZE: u64 = std::mem::size_of::<DrawIndexedIndirectArgs>() as u64;
+
+/// How the shapes are drawn by the main pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawMode {
+    /// One `draw_indexed` per buffer
+    Direct,
+    /// One `draw_indexed_indirect` per buffer, from the shared buffers
+    Indirect,
+    /// A single `multi_draw_indexed_indirect` for every buffer
+    MultiDrawIndirect,
+}
+
+/// Place of a buffer in the shared buffers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Region {
+    /// Offsets in bytes
+    vertex_offset: u64,
+    index_offset: u64,
+    /// Offset in instances
+    first_instance: u32,
+}
+
+/// Sizes of the shared buffers needed by a set of buffers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Layout {
+    vertex_size: u64,
+    index_size: u64,
+    instance_count: u32,
+}
+
+/// Every shape packed into shared vertex, index and instance buffers
+///
+/// The content of the shape buffers is copied on the GPU when the shapes
+/// change, and the visible instances are copied on each frame, so the
+/// culling keeps working. The main pass then binds the shared buffers once
+/// and issues indirect draws.
+pub struct DrawBatch {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+    indirect_buffer: wgpu::Buffer,
+    capacity: Layout,
+    indirect_capacity: usize,
+    regions: Vec<Region>,
+    draws: Vec<DrawIndexedIndirectArgs>,
+    /// Best mode supported by the device
+    supported: DrawMode,
+    enabled: bool,
+    needs_packing: bool,
+}
+
+/// Compute the place of each buffer in the shared buffers
+///
+/// Takes the size in bytes of the vertex and index buffers and the number
+/// of instances of each buffer. The vertex regions are aligned on whole
+/// vertices so they can be addressed with `base_vertex`.
+fn pack<I>(sizes: I) -> (Vec<Region>, Layout)
+where
+    I: Iterator<Item = (u64, u64, u32)>,
+{
+    let mut layout = Layout::default();
+    let regions = sizes
+        .map(|(vertex_size, index_size, instances)| {
+            let region = Region {
+                vertex_offset: layout.vertex_size,
+                index_offset: layout.index_size,
+                first_instance: layout.instance_count,
+            };
+            layout.vertex_size +=
+                vertex_size.div_ceil(VERTEX_SIZE) * VERTEX_SIZE;
+            // The index buffers are already padded to `COPY_BUFFER_ALIGNMENT`
+            layout.index_size += index_size
+                .div_ceil(wgpu::COPY_BUFFER_ALIGNMENT)
+                * wgpu::COPY_BUFFER_ALIGNMENT;
+            layout.instance_count += instances;
+            region
+        })
+        .collect();
+    (regions, layout)
+}
+
+impl DrawBatch {
+    pub fn init(device: &wgpu::Device) -> Self {
+        let features = device.features();
+        let supported =
+            if !features.contains(wgpu::Features::INDIRECT_FIRST_INSTANCE) {
+                DrawMode::Direct
+            } else if features.contains(wgpu::Features::MULTI_DRAW_INDIRECT) {
+                DrawMode::MultiDrawIndirect
+            } else {
+                DrawMode::Indirect
+            };
+        log::info!("Best draw mode supported: {:?}", supported);
+
+        let capacity = Layout {
+            vertex_size: VERTEX_SIZE,
+            index_size: wgpu::COPY_BUFFER_ALIGNMENT,
+            instance_count: 1,
+        };
+        Self {
+            vertex_buffer: Self::create_buffer(
+                device,
+                "Batch Vertex Buffer",
+                capacity.vertex_size,
+                wgpu::BufferUsages::VERTEX,
+            ),
+            index_buffer: Self::create_buffer(
+                device,
+                "Batch Index Buffer",
+                capacity.index_size,
+                wgpu::BufferUsages::INDEX,
+            ),
+            instance_buffer: Self::create_buffer(
+                device,
+                "Batch Instance Buffer",
+                INSTANCE_SIZE,
+                wgpu::BufferUsages::VERTEX,
+            ),
+            indirect_buffer: Self::create_buffer(
+                device,
+                "Batch Indirect Buffer",
+                ARGS_SIZE,
+                wgpu::BufferUsages::INDIRECT,
+            ),
+            capacity,
+            indirect_capacity: 1,
+            regions: Vec::new(),
+            draws: Vec::new(),
+            supported,
+            enabled: supported != DrawMode::Direct,
+            needs_packing: true,
+        }
+    }
+
+    fn create_buffer(
+        device: &wgpu::Device,
+        label: &str,
+        size: u64,
+        usage: wgpu::BufferUsages,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Mode used to draw the shapes
+    pub fn mode(&self) -> DrawMode {
+        if self.enabled {
+            self.supported
+        } else {
+            DrawMode::Direct
+        }
+    }
+
+    /// Best mode supported by the device
+    pub fn supported(&self) -> DrawMode {
+        self.supported
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Use the indirect path when the device supports it
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled != enabled {
+            self.enabled = enabled;
+            self.needs_packing = true;
+        }
+    }
+
+    /// Pack the buffers again before the next frame, needed when a shape
+    /// buffer has been created or replaced
+    pub fn invalidate(&mut self) {
+        self.needs_packing = true;
+    }
+
+    /// Number of draws recorded by the last call to `prepare`
+    pub fn draw_count(&self) -> usize {
+        self.draws.len()
+    }
+
+    /// Copy the buffers into the shared buffers and write the draw commands
+    ///
+    /// Must be called after the culling, each frame, when the indirect path
+    /// is used.
+    pub fn prepare<'a, I>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: I,
+    ) where
+        I: Iterator<Item = &'a Buffer> + Clone,
+    {
+        if self.mode() == DrawMode::Direct {
+            return;
+        }
+
+        if self.needs_packing {
+            self.pack(device, encoder, buffers.clone());
+        }
+
+        // The visible instances change with the culling, they are copied on
+        // every frame
+        self.draws.clear();
+        let mut regions = self.regions.iter();
+        for buffer in buffers {
+            // The instances of a buffer follow the region of its first mesh
+            let mut first_instance = None;
+            for ((_, _, num_indices, instances), region) in
+                buffer.meshes().zip(regions.by_ref())
+            {
+                let first_instance =
+                    *first_instance.get_or_insert(region.first_instance);
+                self.draws.push(DrawIndexedIndirectArgs {
+                    index_count: num_indices,
+                    instance_count: instances.len() as u32,
+                    first_index: (region.index_offset / INDEX_SIZE) as u32,
+                    base_vertex: (region.vertex_offset / VERTEX_SIZE) as i32,
+                    first_instance: first_instance + instances.start,
+                });
+            }
+            if let Some(first_instance) =
+                first_instance.filter(|_| buffer.num_instances > 0)
+            {
+                encoder.copy_buffer_to_buffer(
+                    &buffer.instance_buffer,
+                    0,
+                    &self.instance_buffer,
+                    first_instance as u64 * INSTANCE_SIZE,
+                    buffer.num_instances as u64 * INSTANCE_SIZE,
+                );
+            }
+        }
+
+        if self.draws.len() > self.indirect_capacity {
+            self.indirect_capacity = self.draws.len().next_power_of_two();
+            self.indirect_buffer = Self::create_buffer(
+                device,
+                "Batch Indirect Buffer",
+                self.indirect_capacity as u64 * ARGS_SIZE,
+                wgpu::BufferUsages::INDIRECT,
+            );
+        }
+        if !self.draws.is_empty() {
+            let args = self
+                .draws
+                .iter()
+                .flat_map(|draw| draw.as_bytes())
+                .copied()
+                .collect::<Vec<_>>();
+            queue.write_buffer(&self.indirect_buffer, 0, &args);
+        }
+    }
+
+    /// Copy the vertices and the indices of every buffer
+    fn pack<'a, I>(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: I,
+    ) where
+        I: Iterator<Item = &'a Buffer> + Clone,
+    {
+        // One region per mesh, the instances of a buffer are counted in the
+        // region of its first mesh
+        let (regions, layout) = pack(buffers.clone().flat_map(|buffer| {
+            buffer.meshes().enumerate().map(
+                |(i, (vertex_buffer, index_buffer, _, _))| {
+                    (
+                        vertex_buffer.size(),
+                        index_buffer.size(),
+                        if i == 0 { buffer.total_instances() } else { 0 },
+                    )
+                },
+            )
+        }));
+        log::debug!(
+            "Packing {} meshes: {} bytes of vertices, {} bytes of indices",
+            regions.len(),
+            layout.vertex_size,
+            layout.index_size
+        );
+
+        if layout.vertex_size > self.capacity.vertex_size {
+            self.capacity.vertex_size =
+                layout.vertex_size.next_power_of_two().max(VERTEX_SIZE);
+            // Keep whole vertices in the buffer
+            self.capacity.vertex_size =
+                self.capacity.vertex_size.div_ceil(VERTEX_SIZE) * VERTEX_SIZE;
+            self.vertex_buffer = Self::create_buffer(
+                device,
+                "Batch Vertex Buffer",
+                self.capacity.vertex_size,
+                wgpu::BufferUsages::VERTEX,
+            );
+        }
+        if layout.index_size > self.capacity.index_size {
+            self.capacity.index_size = layout.index_size.next_power_of_two();
+            self.index_buffer = Self::create_buffer(
+                device,
+                "Batch Index Buffer",
+                self.capacity.index_size,
+                wgpu::BufferUsages::INDEX,
+            );
+        }
+        if layout.instance_count > self.capacity.instance_count {
+            self.capacity.instance_count =
+                layout.instance_count.next_power_of_two();
+            self.instance_buffer = Self::create_buffer(
+                device,
+                "Batch Instance Buffer",
+                self.capacity.instance_count as u64 * INSTANCE_SIZE,
+                wgpu::BufferUsages::VERTEX,
+            );
+        }
+
+        let meshes = buffers.flat_map(|buffer| buffer.meshes());
+        for ((vertex_buffer, index_buffer, _, _), region) in
+            meshes.zip(&regions)
+        {
+            encoder.copy_buffer_to_buffer(
+                vertex_buffer,
+                0,
+                &self.vertex_buffer,
+                region.vertex_offset,
+                vertex_buffer.size(),
+            );
+            encoder.copy_buffer_to_buffer(
+                index_buffer,
+                0,
+                &self.index_buffer,
+                region.index_offset,
+                index_buffer.size(),
+            );
+        }
+        self.regions = regions;
+        self.needs_packing = false;
+    }
+
+    /// Draw every buffer packed by the last call to `prepare`
+    ///
+    /// The pipeline and the bind groups must already be set.
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.draws.is_empty() {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_index_buffer(
+            self.index_buffer.slice(..),
+            wgpu::IndexFormat::Uint16,
+        );
+        match self.mode() {
+            DrawMode::MultiDrawIndirect => render_pass
+                .multi_draw_indexed_indirect(
+                    &self.indirect_buffer,
+                    0,
+                    self.draws.len() as u32,
+                ),
+            _ => {
+                for i in 0..self.draws.len() as u64 {
+                    render_pass.draw_indexed_indirect(
+                        &self.indirect_buffer,
+                        i * ARGS_SIZE,
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack() {
+        let (regions, layout) = pack(
+            [
+                (8 * VERTEX_SIZE, 36 * INDEX_SIZE, 3),
+                (4, 4, 0),
+                (3 * VERTEX_SIZE, 6 * INDEX_SIZE, 10),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(regions[1].vertex_offset, 8 * VERTEX_SIZE);
+        assert_eq!(regions[1].index_offset, 36 * INDEX_SIZE);
+        assert_eq!(regions[1].first_instance, 3);
+        // The padded empty buffer still takes a whole vertex
+        assert_eq!(regions[2].vertex_offset, 9 * VERTEX_SIZE);
+        assert_eq!(regions[2].index_offset, 36 * INDEX_SIZE + 4);
+        assert_eq!(layout.vertex_size, 12 * VERTEX_SIZE);
+        assert_eq!(layout.instance_count, 13);
+        for region in &regions {
+            assert_eq!(region.index_offset % wgpu::COPY_BUFFER_ALIGNMENT, 0);
+        }
+    }
+}
@@ -1,11 +1,17 @@
-use std::default;
+use std::{default, rc::Rc};
 
-use cgmath::{Deg, InnerSpace, SquareMatrix};
+use cgmath::{Deg, EuclideanSpace, InnerSpace, SquareMatrix};
 use wgpu::util::DeviceExt;
 
+use crate::config::CameraConfig;
+use crate::input::Action;
+
 pub(crate) const DEFAULT_CAMERA_POSITION: [f32; 3] = [-11.0, 15.0, 20.0];
 pub(crate) const DEFAULT_CAMERA_YAW: cgmath::Deg<f32> = cgmath::Deg(-60.0);
 pub(crate) const DEFAULT_CAMERA_PITCH: cgmath::Deg<f32> = cgmath::Deg(-35.0);
+pub(crate) const DEFAULT_CAMERA_FOVY: cgmath::Deg<f32> = cgmath::Deg(60.0);
+pub(crate) const DEFAULT_CAMERA_ZNEAR: f32 = 0.1;
+pub(crate) const DEFAULT_CAMERA_ZFAR: f32 = 100.0;
 pub(crate) const DEFAULT_CAMERA_SPEED: f32 = 7.0;
 pub(crate) const DEFAULT_CAMERA_SENSITIVITY: f32 = 2.0;
 pub(crate) const DEFAULT_CAMERA_ZOOM_SENSITIVITY: f32 = 2.5;
@@ -20,24 +26,37 @@ const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.0, 1.0,
 );
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Camera {
     position: cgmath::Point3<f32>,
     yaw: cgmath::Rad<f32>,
     pitch: cgmath::Rad<f32>,
 }
 
+#[derive(Debug, Clone)]
 pub struct Projection {
     aspect: f32,
     fovy: cgmath::Rad<f32>,
     znear: f32,
     zfar: f32,
+    /// Height of the view in world units for an orthographic projection
+    orthographic: Option<f32>,
+    /// Size of the target in pixels
+    size: (u32, u32),
 }
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
     view_proj: [[f32; 4]; 4],
+    /// Unprojects the points of the target, used by the fullscreen passes
+    inv_view_proj: [[f32; 4]; 4],
+    position: [f32; 4],
+    /// World directions of the right and up axes of the view
+    right: [f32; 4],
+    up: [f32; 4],
+    /// Size of the target in pixels
+    viewport: [f32; 4],
 }
 
 pub struct CameraBuffer {
@@ -45,8 +64,12 @@ pub struct CameraBuffer {
     pub uniform: CameraUniform,
     pub camera: Camera,
     pub projection: Projection,
+    /// Where `reset_camera` brings the camera back
+    home: Camera,
     bind_group: wgpu::BindGroup,
-    bind_group_layout: wgpu::BindGroupLayout,
+    /// Shared by every camera so their bind groups work with the same
+    /// pipelines
+    bind_group_layout: Rc<wgpu::BindGroupLayout>,
     pub has_been_updated: bool,
 }
 
@@ -131,16 +154,63 @@ impl Projection {
             fovy: fovy.into(),
             znear,
             zfar,
+            orthographic: None,
+            size: (width, height),
+        }
+    }
+
+    /// Orthographic projection showing `view_height` world units vertically
+    pub fn orthographic(
+        width: u32,
+        height: u32,
+        view_height: f32,
+        znear: f32,
+        zfar: f32,
+    ) -> Self {
+        Self {
+            orthographic: Some(view_height),
+            ..Self::new(width, height, cgmath::Deg(60.0), znear, zfar)
         }
     }
 
     fn resize(&mut self, width: u32, height: u32) {
         self.aspect = width as f32 / height as f32;
+        self.size = (width, height);
+    }
+
+    /// Diameter of a sphere seen at a distance, over the height of the view
+    fn projected_size(&self, radius: f32, distance: f32) -> f32 {
+        match self.orthographic {
+            Some(view_height) => 2.0 * radius / view_height,
+            None if distance <= radius => f32::INFINITY,
+            None => radius / (distance * (self.fovy.0 * 0.5).tan()),
+        }
     }
 
     fn calc_matrix(&self) -> cgmath::Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX
-            * cgmath::perspective(self.fovy, self.aspect, self.znear, self.zfar)
+        match self.orthographic {
+            Some(view_height) => {
+                let top = view_height * 0.5;
+                let right = top * self.aspect;
+                // `OPENGL_TO_WGPU_MATRIX` relies on the w of a perspective
+                // projection, the depth range is remapped to [0, 1] instead
+                cgmath::Matrix4::from_translation(cgmath::Vector3::new(
+                    0.0, 0.0, 0.5,
+                )) * cgmath::Matrix4::from_nonuniform_scale(1.0, 1.0, 0.5)
+                    * cgmath::ortho(
+                        -right, right, -top, top, self.znear, self.zfar,
+                    )
+            }
+            None => {
+                OPENGL_TO_WGPU_MATRIX
+                    * cgmath::perspective(
+                        self.fovy,
+                        self.aspect,
+                        self.znear,
+                        self.zfar,
+                    )
+            }
+        }
     }
 }
 
@@ -148,16 +218,37 @@ impl CameraUniform {
     pub fn new() -> Self {
         Self {
             view_proj: cgmath::Matrix4::identity().into(),
+            inv_view_proj: cgmath::Matrix4::identity().into(),
+            position: [0.0, 0.0, 0.0, 1.0],
+            right: [1.0, 0.0, 0.0, 0.0],
+            up: [0.0, 1.0, 0.0, 0.0],
+            viewport: [1.0, 1.0, 0.0, 0.0],
         }
     }
 
+    pub fn view_proj(&self) -> cgmath::Matrix4<f32> {
+        self.view_proj.into()
+    }
+
     pub fn update_view_proj(
         &mut self,
         camera: &Camera,
         projection: &Projection,
     ) {
-        self.view_proj =
-            (projection.calc_matrix() * camera.calc_matrix()).into();
+        let view = camera.calc_matrix();
+        let view_proj = projection.calc_matrix() * view;
+        self.view_proj = view_proj.into();
+        self.inv_view_proj = view_proj
+            .invert()
+            .unwrap_or(cgmath::Matrix4::identity())
+            .into();
+        let position = camera.position;
+        self.position = [position.x, position.y, position.z, 1.0];
+        // The rows of the view rotation are the camera axes in world space
+        self.right = [view.x.x, view.y.x, view.z.x, 0.0];
+        self.up = [view.x.y, view.y.y, view.z.y, 0.0];
+        let (width, height) = projection.size;
+        self.viewport = [width as f32, height as f32, 0.0, 0.0];
     }
 }
 
@@ -174,9 +265,10 @@ impl CameraController {
         self.zoom_senstivity = zoom_sensitivity;
     }
 
-    pub fn process_keyboard(
+    /// Start or stop moving, returns whether the action moves the camera
+    pub fn process_action(
         &mut self,
-        key: winit::keyboard::KeyCode,
+        action: &Action,
         state: winit::event::ElementState,
     ) -> bool {
         let amount = if state == winit::event::ElementState::Pressed {
@@ -184,37 +276,17 @@ impl CameraController {
         } else {
             0.0
         };
-        match key {
-            winit::keyboard::KeyCode::KeyW
-            | winit::keyboard::KeyCode::ArrowUp => {
-                self.amount_forward = amount;
-                true
-            }
-            winit::keyboard::KeyCode::KeyS
-            | winit::keyboard::KeyCode::ArrowDown => {
-                self.amount_backward = amount;
-                true
-            }
-            winit::keyboard::KeyCode::KeyA
-            | winit::keyboard::KeyCode::ArrowLeft => {
-                self.amount_left = amount;
-                true
-            }
-            winit::keyboard::KeyCode::KeyD
-            | winit::keyboard::KeyCode::ArrowRight => {
-                self.amount_right = amount;
-                true
-            }
-            winit::keyboard::KeyCode::Space => {
-                self.amount_up = amount;
-                true
-            }
-            winit::keyboard::KeyCode::ShiftLeft => {
-                self.amount_down = amount;
-                true
-            }
-            _ => false,
-        }
+        let value = match action {
+            Action::MoveForward => &mut self.amount_forward,
+            Action::MoveBackward => &mut self.amount_backward,
+            Action::MoveLeft => &mut self.amount_left,
+            Action::MoveRight => &mut self.amount_right,
+            Action::MoveUp => &mut self.amount_up,
+            Action::MoveDown => &mut self.amount_down,
+            _ => return false,
+        };
+        *value = amount;
+        true
     }
 
     pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
@@ -233,6 +305,19 @@ impl CameraController {
         };
     }
 
+    /// A key is held or a mouse motion is waiting to be applied
+    pub fn is_moving(&self) -> bool {
+        self.amount_forward != 0.0
+            || self.amount_backward != 0.0
+            || self.amount_left != 0.0
+            || self.amount_right != 0.0
+            || self.amount_up != 0.0
+            || self.amount_down != 0.0
+            || self.rotate_horizontal != 0.0
+            || self.rotate_vertical != 0.0
+            || self.scroll != 0.0
+    }
+
     pub fn update_camera(
         &mut self,
         camera_buffer: &mut CameraBuffer,
@@ -240,16 +325,7 @@ impl CameraController {
     ) {
         let camera = &mut camera_buffer.camera;
 
-        if self.amount_forward == 0.0
-            && self.amount_backward == 0.0
-            && self.amount_left == 0.0
-            && self.amount_right == 0.0
-            && self.amount_up == 0.0
-            && self.amount_down == 0.0
-            && self.rotate_horizontal == 0.0
-            && self.rotate_vertical == 0.0
-            && self.scroll == 0.0
-        {
+        if !self.is_moving() {
             return;
         } else {
             camera_buffer.has_been_updated = true;
@@ -313,6 +389,17 @@ impl CameraController {
     }
 }
 
+impl CameraController {
+    pub fn new(config: &CameraConfig) -> Self {
+        Self {
+            speed: config.speed,
+            sensitivity: config.sensitivity,
+            zoom_senstivity: config.zoom_sensitivity,
+            ..Self::default()
+        }
+    }
+}
+
 impl Default for CameraController {
     fn default() -> Self {
         Self {
@@ -334,7 +421,25 @@ impl Default for CameraController {
 
 impl CameraBuffer {
     pub fn reset_camera(&mut self) {
-        self.camera = Camera::default();
+        self.camera = self.home.clone();
+    }
+
+    /// Put the camera on the horizontal circle of its starting position
+    /// around the origin, `angle` away from it, looking at the origin
+    pub fn orbit<A: Into<cgmath::Rad<f32>>>(&mut self, angle: A) {
+        let home = self.home.position;
+        let (sin, cos) = angle.into().0.sin_cos();
+        let position = cgmath::Point3::new(
+            home.x * cos - home.z * sin,
+            home.y,
+            home.x * sin + home.z * cos,
+        );
+        let direction = -position.to_vec();
+        let horizontal = (direction.x.powi(2) + direction.z.powi(2)).sqrt();
+        self.camera.position = position;
+        self.camera.yaw = cgmath::Rad(direction.z.atan2(direction.x));
+        self.camera.pitch = cgmath::Rad(direction.y.atan2(horizontal));
+        self.has_been_updated = true;
     }
 
     pub fn get_camera_info(&self) -> (f32, f32, f32, f32, f32) {
@@ -352,29 +457,14 @@ impl CameraBuffer {
     pub fn init(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
+        camera_config: &CameraConfig,
     ) -> Self {
-        let mut uniform = CameraUniform::new();
-        let camera = Camera::default();
-        let projection = Projection::new(
-            config.width,
-            config.height,
-            cgmath::Deg(60.0),
-            0.1,
-            100.0,
-        );
-        uniform.update_view_proj(&camera, &projection);
-        let buffer =
-            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Camera Buffer"),
-                contents: bytemuck::cast_slice(&[uniform]),
-                usage: wgpu::BufferUsages::UNIFORM
-                    | wgpu::BufferUsages::COPY_DST,
-            });
         let bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    // Read by the fragments of the fullscreen passes
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -384,6 +474,46 @@ impl CameraBuffer {
                 }],
                 label: Some("camera_bind_group_layout"),
             });
+        let camera = Camera::new(
+            camera_config.position,
+            Deg(camera_config.yaw),
+            Deg(camera_config.pitch),
+        );
+        let projection = Projection::new(
+            config.width,
+            config.height,
+            Deg(camera_config.fovy),
+            camera_config.znear,
+            camera_config.zfar,
+        );
+        Self::create(device, Rc::new(bind_group_layout), camera, projection)
+    }
+
+    /// Another camera usable with the pipelines of this one
+    pub fn share(
+        &self,
+        device: &wgpu::Device,
+        camera: Camera,
+        projection: Projection,
+    ) -> Self {
+        Self::create(device, self.bind_group_layout.clone(), camera, projection)
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        bind_group_layout: Rc<wgpu::BindGroupLayout>,
+        camera: Camera,
+        projection: Projection,
+    ) -> Self {
+        let mut uniform = CameraUniform::new();
+        uniform.update_view_proj(&camera, &projection);
+        let buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Camera Buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST,
+            });
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &bind_group_layout,
@@ -397,6 +527,7 @@ impl CameraBuffer {
         CameraBuffer {
             buffer,
             uniform,
+            home: camera.clone(),
             camera,
             projection,
             bind_group,
@@ -409,6 +540,29 @@ impl CameraBuffer {
         &self.bind_group_layout
     }
 
+    pub fn position(&self) -> cgmath::Point3<f32> {
+        self.camera.position
+    }
+
+    /// View matrix ignoring the camera position
+    pub fn rotation(&self) -> cgmath::Matrix4<f32> {
+        let mut view = self.camera.calc_matrix();
+        view.w = cgmath::Vector4::new(0.0, 0.0, 0.0, 1.0);
+        view
+    }
+
+    /// Size of a bounding sphere on the screen, as a fraction of the height
+    /// of the view
+    pub fn projected_size(
+        &self,
+        center: cgmath::Vector3<f32>,
+        radius: f32,
+    ) -> f32 {
+        let distance =
+            (cgmath::Point3::from_vec(center) - self.position()).magnitude();
+        self.projection.projected_size(radius, distance)
+    }
+
     pub fn bind_group(&self) -> &wgpu::BindGroup {
         &self.bind_group
     }
@@ -447,3 +601,29 @@ impl CameraBuffer {
         self.has_been_updated = true;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::AbsDiffEq;
+
+    #[test]
+    fn test_camera_uniform_unprojects_the_view() {
+        let projection =
+            Projection::new(800, 600, cgmath::Deg(45.0), 0.1, 100.0);
+        let camera = Camera::new((0.0, 5.0, 10.0), Deg(-90.0), Deg(0.0));
+        let mut uniform = CameraUniform::new();
+        uniform.update_view_proj(&camera, &projection);
+
+        let inv_view_proj: cgmath::Matrix4<f32> = uniform.inv_view_proj.into();
+        assert!((uniform.view_proj() * inv_view_proj)
+            .abs_diff_eq(&cgmath::Matrix4::identity(), 1e-4));
+        assert_eq!(uniform.position, [0.0, 5.0, 10.0, 1.0]);
+        // Looking toward -z
+        assert!(cgmath::Vector4::from(uniform.right)
+            .abs_diff_eq(&cgmath::Vector4::new(1.0, 0.0, 0.0, 0.0), 1e-4));
+        assert!(cgmath::Vector4::from(uniform.up)
+            .abs_diff_eq(&cgmath::Vector4::new(0.0, 1.0, 0.0, 0.0), 1e-4));
+        assert_eq!(uniform.viewport, [800.0, 600.0, 0.0, 0.0]);
+    }
+}
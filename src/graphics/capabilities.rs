@@ -0,0 +1,137 @@
+use super::{
+    batch::INDIRECT_FEATURES, pipeline::DepthTexture, post::PostProcess,
+};
+
+/// Features used when the adapter has them, the renderer works without any
+/// of them
+///
+/// `POLYGON_MODE_LINE` draws the shapes in wireframe, `TIMESTAMP_QUERY`
+/// measures the GPU timings and `INDIRECT_FEATURES` batch the draws.
+pub const OPTIONAL_FEATURES: wgpu::Features = wgpu::Features::POLYGON_MODE_LINE
+    .union(wgpu::Features::TIMESTAMP_QUERY)
+    .union(INDIRECT_FEATURES);
+
+/// What the device has been created with
+///
+/// Negotiated from the adapter before creating the device, the parts of the
+/// renderer depending on an optional feature check it here.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// Features enabled on the device
+    pub features: wgpu::Features,
+    /// Limits of the device
+    pub limits: wgpu::Limits,
+    /// Name of the set of limits reached by the adapter
+    pub tier: &'static str,
+    /// What the adapter cannot do compared to WebGPU
+    pub downlevel: wgpu::DownlevelCapabilities,
+    /// Largest sample count supported by the scene targets
+    pub max_samples: u32,
+}
+
+impl Capabilities {
+    /// Choose the features and the limits to request from the adapter
+    ///
+    /// The limits fall back on the downlevel ones on weaker adapters (GL,
+    /// software), or on the limits of the adapter if it is below all of them.
+    pub fn negotiate(adapter: &wgpu::Adapter) -> Self {
+        let downlevel = adapter.get_downlevel_capabilities();
+        let mut features = adapter.features() & OPTIONAL_FEATURES;
+        // The indirect draws are only used when the adapter can run them
+        if !downlevel
+            .flags
+            .contains(wgpu::DownlevelFlags::INDIRECT_EXECUTION)
+        {
+            features -= INDIRECT_FEATURES;
+        }
+
+        let adapter_limits = adapter.limits();
+        // Tried in order, the texture sizes are the ones of the adapter
+        // whatever the tier
+        let tiers = [
+            ("default", wgpu::Limits::default()),
+            ("downlevel", wgpu::Limits::downlevel_defaults()),
+            ("webgl2", wgpu::Limits::downlevel_webgl2_defaults()),
+        ];
+        let (tier, limits) = tiers
+            .into_iter()
+            .map(|(tier, limits)| {
+                (tier, limits.using_resolution(adapter_limits.clone()))
+            })
+            .find(|(_, limits)| limits.check_limits(&adapter_limits))
+            .unwrap_or(("adapter", adapter_limits));
+
+        let max_samples = [4, 1]
+            .into_iter()
+            .find(|&samples| {
+                [PostProcess::HDR_FORMAT, DepthTexture::DEPTH_FORMAT]
+                    .into_iter()
+                    .all(|format| {
+                        adapter
+                            .get_texture_format_features(format)
+                            .flags
+                            .sample_count_supported(samples)
+                    })
+            })
+            .unwrap_or(1);
+
+        Self {
+            features,
+            limits,
+            tier,
+            downlevel,
+            max_samples,
+        }
+    }
+
+    /// The shapes can be drawn in wireframe
+    pub fn wireframe(&self) -> bool {
+        self.features.contains(wgpu::Features::POLYGON_MODE_LINE)
+    }
+
+    /// The GPU timings can be measured
+    pub fn timestamps(&self) -> bool {
+        self.features.contains(wgpu::Features::TIMESTAMP_QUERY)
+    }
+
+    /// The shapes can be drawn from indirect buffers
+    pub fn indirect(&self) -> bool {
+        self.features
+            .contains(wgpu::Features::INDIRECT_FIRST_INSTANCE)
+    }
+
+    /// Sample count to use instead of `samples` if the targets do not
+    /// support it
+    pub fn samples(&self, samples: u32) -> u32 {
+        if samples > self.max_samples {
+            log::warn!(
+                "{}x MSAA is not supported, using {}x",
+                samples,
+                self.max_samples
+            );
+            self.max_samples
+        } else {
+            samples
+        }
+    }
+
+    /// Largest side of a texture
+    pub fn max_texture_size(&self) -> u32 {
+        self.limits.max_texture_dimension_2d
+    }
+
+    pub fn log(&self) {
+        log::info!("Device features: {:?}", self.features);
+        log::info!(
+            "Device limits: {} (texture {}, bind groups {}, uniform buffer {})",
+            self.tier,
+            self.limits.max_texture_dimension_2d,
+            self.limits.max_bind_groups,
+            self.limits.max_uniform_buffer_binding_size
+        );
+        let missing = OPTIONAL_FEATURES - self.features;
+        if !missing.is_empty() {
+            log::info!("Unavailable optional features: {:?}", missing);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use cgmath::{InnerSpace, Vector3, Vector4};
+
+/// Axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+/// The six clipping planes of a view-projection matrix
+///
+/// Each plane is stored as `(normal, distance)` with the normal pointing
+/// inside the frustum.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Aabb {
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// Smallest box containing every point, `None` if there is no point
+    pub fn from_points<I>(points: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = Vector3<f32>>,
+    {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, point| Self {
+            min: Vector3::new(
+                aabb.min.x.min(point.x),
+                aabb.min.y.min(point.y),
+                aabb.min.z.min(point.z),
+            ),
+            max: Vector3::new(
+                aabb.max.x.max(point.x),
+                aabb.max.y.max(point.y),
+                aabb.max.z.max(point.z),
+            ),
+        }))
+    }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn extents(&self) -> Vector3<f32> {
+        (self.max - self.min) / 2.0
+    }
+
+    /// Bounding box of this box once transformed by the matrix
+    pub fn transform(&self, matrix: &cgmath::Matrix4<f32>) -> Self {
+        let center = self.center();
+        let extents = self.extents();
+
+        let new_center = (matrix * center.extend(1.0)).truncate();
+        // Each axis of the new box is the sum of the absolute contributions
+        // of every axis of the old box (Arvo's method)
+        let new_extents = Vector3::new(
+            matrix.x.x.abs() * extents.x
+                + matrix.y.x.abs() * extents.y
+                + matrix.z.x.abs() * extents.z,
+            matrix.x.y.abs() * extents.x
+                + matrix.y.y.abs() * extents.y
+                + matrix.z.y.abs() * extents.z,
+            matrix.x.z.abs() * extents.x
+                + matrix.y.z.abs() * extents.y
+                + matrix.z.z.abs() * extents.z,
+        );
+
+        Self::new(new_center - new_extents, new_center + new_extents)
+    }
+}
+
+impl Frustum {
+    /// Extract the planes of a view-projection matrix
+    ///
+    /// The matrix is expected to map depth to `[0, 1]`, as wgpu does.
+    pub fn from_matrix(view_proj: &cgmath::Matrix4<f32>) -> Self {
+        let row = |i: usize| {
+            Vector4::new(
+                view_proj.x[i],
+                view_proj.y[i],
+                view_proj.z[i],
+                view_proj.w[i],
+            )
+        };
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+
+        let normalize = |plane: Vector4<f32>| {
+            let length = plane.truncate().magnitude();
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        };
+
+        Self {
+            planes: [
+                normalize(r3 + r0), // Left
+                normalize(r3 - r0), // Right
+                normalize(r3 + r1), // Bottom
+                normalize(r3 - r1), // Top
+                normalize(r2),      // Near
+                normalize(r3 - r2), // Far
+            ],
+        }
+    }
+
+    /// Returns false only if the box is fully outside one of the planes
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // Corner of the box the furthest along the plane normal
+            let corner = Vector3::new(
+                if plane.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.truncate().dot(corner) + plane.w >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Maps the OpenGL depth range [-1, 1] to [0, 1]
+    #[rustfmt::skip]
+    const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 0.5, 0.0,
+        0.0, 0.0, 0.5, 1.0,
+    );
+
+    fn frustum() -> Frustum {
+        // Camera at the origin looking toward -z
+        let projection = OPENGL_TO_WGPU_MATRIX
+            * cgmath::perspective(cgmath::Deg(60.0), 1.0, 0.1, 100.0);
+        Frustum::from_matrix(&projection)
+    }
+
+    fn unit_box(center: Vector3<f32>) -> Aabb {
+        Aabb::new(
+            center - Vector3::new(0.5, 0.5, 0.5),
+            center + Vector3::new(0.5, 0.5, 0.5),
+        )
+    }
+
+    #[test]
+    fn test_frustum_culling() {
+        let frustum = frustum();
+
+        assert!(frustum.intersects(&unit_box(Vector3::new(0.0, 0.0, -5.0))));
+        // Behind the camera
+        assert!(!frustum.intersects(&unit_box(Vector3::new(0.0, 0.0, 5.0))));
+        // Too far on the side
+        assert!(!frustum.intersects(&unit_box(Vector3::new(50.0, 0.0, -5.0))));
+        // Beyond the far plane
+        assert!(!frustum.intersects(&unit_box(Vector3::new(0.0, 0.0, -200.0))));
+        // Crossing the left plane
+        assert!(frustum.intersects(&unit_box(Vector3::new(-3.2, 0.0, -5.0))));
+    }
+
+    #[test]
+    fn test_aabb_transform() {
+        let aabb = unit_box(Vector3::new(0.0, 0.0, 0.0));
+        let matrix =
+            cgmath::Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0))
+                * cgmath::Matrix4::from_nonuniform_scale(2.0, 1.0, 1.0);
+
+        let transformed = aabb.transform(&matrix);
+        assert_eq!(transformed.min, Vector3::new(0.0, 1.5, 2.5));
+        assert_eq!(transformed.max, Vector3::new(2.0, 2.5, 3.5));
+    }
+}
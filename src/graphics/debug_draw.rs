@@ -0,0 +1,74 @@
+use super::{
+    lines::{self, LineSegment},
+    text::{self, Text},
+};
+
+/// Lines and texts queued with the `debug_*` functions by the scenes of a
+/// window
+///
+/// The queues of the `debug_*` functions are shared by every window, they
+/// are moved here after each call into the scenes of the window, so a window
+/// only draws what its own scenes queued.
+#[derive(Debug, Default)]
+pub struct DebugDraws {
+    /// Collected since the last frame of the scenes
+    queued: Frame,
+    /// Drawn until the scenes are updated again
+    frame: Frame,
+}
+
+#[derive(Debug, Default)]
+struct Frame {
+    lines: Vec<LineSegment>,
+    texts: Vec<Text>,
+}
+
+impl DebugDraws {
+    /// Take the lines and texts queued since the last call
+    pub(crate) fn collect(&mut self) {
+        self.queued.lines.extend(lines::take_debug_lines());
+        self.queued.texts.extend(text::take_debug_texts());
+    }
+
+    /// Draw what has been collected since the last frame, once the scenes
+    /// have been updated
+    pub(crate) fn end_frame(&mut self) {
+        self.collect();
+        self.frame = std::mem::take(&mut self.queued);
+    }
+
+    pub(crate) fn lines(&self) -> &[LineSegment] {
+        &self.frame.lines
+    }
+
+    pub(crate) fn texts(&self) -> &[Text] {
+        &self.frame.texts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_keeps_the_queued_draws() {
+        lines::take_debug_lines();
+        text::take_debug_texts();
+
+        let mut draws = DebugDraws::default();
+        // Queued by an action between two frames
+        lines::debug_line([0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0; 3]);
+        draws.collect();
+        // Queued by the update of the scenes
+        text::hud_text([0.0, 0.0], "label", [1.0; 3]);
+        draws.end_frame();
+        assert_eq!(draws.lines().len(), 1);
+        assert_eq!(draws.texts().len(), 1);
+
+        // Drawn again until the next frame of the scenes
+        assert_eq!(draws.lines().len(), 1);
+        draws.end_frame();
+        assert!(draws.lines().is_empty());
+        assert!(draws.texts().is_empty());
+    }
+}
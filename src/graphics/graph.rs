@@ -0,0 +1,307 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use cgmath::SquareMatrix;
+
+use super::{shapes::Shape, types::Instance};
+
+/// A node of the scene graph
+///
+/// Each node holds a local transform (relative to its parent), an optional
+/// shape to draw at that transform and a list of children.
+#[derive(Debug)]
+pub struct Node {
+    name: String,
+    transform: Instance,
+    shape: Option<Rc<RefCell<Shape>>>,
+    children: Vec<Rc<RefCell<Node>>>,
+    has_been_updated: bool,
+}
+
+/// Tree of nodes whose world transforms are propagated into the instances
+/// of the shapes they reference.
+///
+/// A shape can be referenced by several nodes, each node then adds one
+/// instance to the shape.
+pub struct SceneGraph {
+    root: Rc<RefCell<Node>>,
+    shapes: Vec<Rc<RefCell<Shape>>>,
+}
+
+impl Node {
+    pub fn new<S: ToString>(name: S) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            name: name.to_string(),
+            transform: Instance::identity(),
+            shape: None,
+            children: Vec::new(),
+            has_been_updated: true,
+        }))
+    }
+
+    pub fn with_shape<S: ToString>(
+        name: S,
+        shape: Rc<RefCell<Shape>>,
+    ) -> Rc<RefCell<Self>> {
+        let node = Self::new(name);
+        node.borrow_mut().shape = Some(shape);
+        node
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn shape(&self) -> Option<&Rc<RefCell<Shape>>> {
+        self.shape.as_ref()
+    }
+
+    pub fn set_shape(&mut self, shape: Option<Rc<RefCell<Shape>>>) {
+        self.shape = shape;
+        self.has_been_updated = true;
+    }
+
+    pub fn transform(&self) -> &Instance {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Instance) {
+        self.transform = transform;
+        self.has_been_updated = true;
+    }
+
+    pub fn set_translation<P>(&mut self, position: P)
+    where
+        P: Into<cgmath::Vector3<f32>>,
+    {
+        self.set_transform(self.transform.clone().with_translation(position));
+    }
+
+    pub fn set_rotation<R>(&mut self, rotation: R)
+    where
+        R: Into<cgmath::Quaternion<f32>>,
+    {
+        self.set_transform(self.transform.clone().with_rotation(rotation));
+    }
+
+    pub fn set_scale<S>(&mut self, scale: S)
+    where
+        S: Into<cgmath::Vector3<f32>>,
+    {
+        self.set_transform(self.transform.clone().with_scale(scale));
+    }
+
+    pub fn children(&self) -> &[Rc<RefCell<Node>>] {
+        &self.children
+    }
+
+    pub fn add_child(&mut self, child: Rc<RefCell<Node>>) -> &mut Self {
+        self.children.push(child);
+        self.has_been_updated = true;
+        self
+    }
+
+    pub fn remove_child(&mut self, child: &Rc<RefCell<Node>>) {
+        self.children.retain(|node| !Rc::ptr_eq(node, child));
+        self.has_been_updated = true;
+    }
+
+    fn collect_shapes(&self, shapes: &mut Vec<Rc<RefCell<Shape>>>) {
+        if let Some(shape) = &self.shape {
+            if !shapes.iter().any(|known| Rc::ptr_eq(known, shape)) {
+                shapes.push(shape.clone());
+            }
+        }
+        for child in self.children.iter() {
+            child.borrow().collect_shapes(shapes);
+        }
+    }
+
+    /// Returns true if this node or one of its descendants changed since the
+    /// last propagation
+    fn is_dirty(&self) -> bool {
+        self.has_been_updated
+            || self.children.iter().any(|child| child.borrow().is_dirty())
+    }
+
+    /// Compute the world matrix of this node and its descendants, and
+    /// collect one instance per node that holds a shape.
+    fn propagate(
+        &mut self,
+        parent: cgmath::Matrix4<f32>,
+        instances: &mut Vec<(Rc<RefCell<Shape>>, Instance)>,
+    ) {
+        if let Some(shape) = &self.shape {
+            instances.push((
+                shape.clone(),
+                self.transform.clone().with_parent(parent),
+            ));
+        }
+
+        let world = parent * self.transform.local_matrix();
+        for child in self.children.iter() {
+            child.borrow_mut().propagate(world, instances);
+        }
+        self.has_been_updated = false;
+    }
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self {
+            root: Node::new("root"),
+            shapes: Vec::new(),
+        }
+    }
+
+    pub fn root(&self) -> Rc<RefCell<Node>> {
+        self.root.clone()
+    }
+
+    /// Add a node as a child of the root node
+    pub fn add(&mut self, node: Rc<RefCell<Node>>) -> &mut Self {
+        self.root.borrow_mut().add_child(node);
+        self
+    }
+
+    /// Returns every shape referenced by the graph
+    ///
+    /// This is meant to be returned by `Scene::shapes`, the world transforms
+    /// are written into these shapes on `SceneGraph::update`.
+    pub fn shapes(&self) -> Vec<Rc<RefCell<Shape>>> {
+        let mut shapes = Vec::new();
+        self.root.borrow().collect_shapes(&mut shapes);
+        shapes
+    }
+
+    /// Propagate the world matrices through the graph and update the
+    /// instances of the shapes if a node changed.
+    pub fn update(&mut self) {
+        if !self.root.borrow().is_dirty() {
+            return;
+        }
+        let mut instances = Vec::new();
+        self.root
+            .borrow_mut()
+            .propagate(cgmath::Matrix4::identity(), &mut instances);
+        self.apply(instances);
+    }
+
+    fn apply(&mut self, instances: Vec<(Rc<RefCell<Shape>>, Instance)>) {
+        let mut grouped: HashMap<*const RefCell<Shape>, Vec<Instance>> =
+            HashMap::new();
+        for (shape, instance) in instances {
+            let key = Rc::as_ptr(&shape);
+            if !self.shapes.iter().any(|known| Rc::ptr_eq(known, &shape)) {
+                self.shapes.push(shape);
+            }
+            grouped.entry(key).or_default().push(instance);
+        }
+
+        // Shapes that are no longer referenced get no instance
+        for shape in self.shapes.iter() {
+            let instances =
+                grouped.remove(&Rc::as_ptr(shape)).unwrap_or_default();
+            shape.borrow_mut().set_instances(instances);
+        }
+    }
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Rotation3, Transform};
+
+    fn unit_shape() -> Rc<RefCell<Shape>> {
+        Rc::new(RefCell::new(Shape::rect(
+            cgmath::Vector3::new(0.0, 0.0, 0.0),
+            cgmath::Vector3::new(1.0, 1.0, 1.0),
+            [1.0, 1.0, 1.0],
+            vec![],
+        )))
+    }
+
+    fn origin_of(instance: &Instance) -> cgmath::Point3<f32> {
+        instance
+            .matrix()
+            .transform_point(cgmath::Point3::new(0.0, 0.0, 0.0))
+    }
+
+    #[test]
+    fn test_world_transform_propagation() {
+        let shape = unit_shape();
+        let mut graph = SceneGraph::new();
+
+        let parent = Node::with_shape("parent", shape.clone());
+        let child = Node::with_shape("child", shape.clone());
+        parent.borrow_mut().set_translation([1.0, 0.0, 0.0]);
+        child.borrow_mut().set_translation([0.0, 2.0, 0.0]);
+        parent.borrow_mut().add_child(child.clone());
+        graph.add(parent.clone());
+
+        assert_eq!(graph.shapes().len(), 1);
+        graph.update();
+        let instances = shape.borrow().instances().clone();
+        assert_eq!(instances.len(), 2);
+        assert_eq!(
+            origin_of(&instances[0]),
+            cgmath::Point3::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            origin_of(&instances[1]),
+            cgmath::Point3::new(1.0, 2.0, 0.0)
+        );
+
+        // Moving the parent moves the child
+        parent.borrow_mut().set_translation([-1.0, 0.0, 0.0]);
+        graph.update();
+        let instances = shape.borrow().instances().clone();
+        assert_eq!(
+            origin_of(&instances[1]),
+            cgmath::Point3::new(-1.0, 2.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_parent_rotation_and_scale() {
+        let shape = unit_shape();
+        let mut graph = SceneGraph::new();
+
+        let parent = Node::new("parent");
+        let child = Node::with_shape("child", shape.clone());
+        parent
+            .borrow_mut()
+            .set_rotation(cgmath::Quaternion::from_angle_z(cgmath::Deg(90.0)));
+        parent.borrow_mut().set_scale([2.0, 2.0, 2.0]);
+        child.borrow_mut().set_translation([1.0, 0.0, 0.0]);
+        parent.borrow_mut().add_child(child);
+        graph.add(parent);
+        graph.update();
+
+        let instances = shape.borrow().instances().clone();
+        let origin = origin_of(&instances[0]);
+        assert!((origin.x - 0.0).abs() < 1e-5);
+        assert!((origin.y - 2.0).abs() < 1e-5);
+        assert!((origin.z - 0.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_removed_node_clears_instances() {
+        let shape = unit_shape();
+        let mut graph = SceneGraph::new();
+
+        let node = Node::with_shape("node", shape.clone());
+        graph.add(node.clone());
+        graph.update();
+        assert_eq!(shape.borrow().instances().len(), 1);
+
+        graph.root().borrow_mut().remove_child(&node);
+        graph.update();
+        assert!(shape.borrow().instances().is_empty());
+    }
+}
@@ -0,0 +1,368 @@
+use wgpu::util::DeviceExt;
+
+use crate::config::HelpersConfig;
+
+use super::{
+    camera::CameraBuffer, pipeline::DepthTexture, post::PostProcess,
+    shadow::OPENGL_TO_WGPU_DEPTH,
+};
+
+/// Size of a grid cell, a major line is drawn every 10 cells
+const GRID_CELL_SIZE: f32 = 1.0;
+/// Distance from the camera at which the grid has faded out
+const GRID_FADE_DISTANCE: f32 = 60.0;
+/// Half length of the world axes
+const AXIS_LENGTH: f32 = 100.0;
+/// Size in pixels of the orientation gizmo
+const GIZMO_SIZE: f32 = 100.0;
+const GIZMO_MARGIN: f32 = 10.0;
+
+const AXIS_COLORS: [[f32; 3]; 3] =
+    [[1.0, 0.1, 0.1], [0.1, 1.0, 0.1], [0.1, 0.3, 1.0]];
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+impl LineVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+/// Built-in visual helpers: ground grid, world axes and orientation gizmo
+///
+/// The grid and the axes are drawn with the camera of the render pass, the
+/// gizmo follows the main camera.
+pub struct Helpers {
+    grid: bool,
+    axes: bool,
+    gizmo: bool,
+    grid_bind_group: wgpu::BindGroup,
+    grid_pipeline: wgpu::RenderPipeline,
+    axes_buffer: wgpu::Buffer,
+    axes_pipeline: wgpu::RenderPipeline,
+    gizmo_buffer: wgpu::Buffer,
+    gizmo_uniform_buffer: wgpu::Buffer,
+    gizmo_bind_group: wgpu::BindGroup,
+    gizmo_pipeline: wgpu::RenderPipeline,
+    size: (f32, f32),
+    has_been_updated: bool,
+}
+
+impl Helpers {
+    pub fn init(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        camera: &CameraBuffer,
+        samples: u32,
+        helpers: &HelpersConfig,
+    ) -> Self {
+        // The grid is unprojected with the camera uniform, only its own
+        // parameters are stored here
+        let grid_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Grid Buffer"),
+                contents: bytemuck::cast_slice(&[
+                    GRID_CELL_SIZE,
+                    GRID_FADE_DISTANCE,
+                    0.0,
+                    0.0,
+                ]),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let grid_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("grid_bind_group_layout"),
+            });
+        let grid_bind_group =
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &grid_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: grid_buffer.as_entire_binding(),
+                }],
+                label: Some("grid_bind_group"),
+            });
+        let grid_shader =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Grid Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("shaders/grid.wgsl").into(),
+                ),
+            });
+        let grid_pipeline = Self::create_pipeline(
+            device,
+            "Grid",
+            &grid_shader,
+            &[camera.bind_group_layout(), &grid_bind_group_layout],
+            None,
+            wgpu::CompareFunction::LessEqual,
+            samples,
+        );
+
+        // The axes are drawn with the camera matrix, the gizmo with its own
+        // matrix sharing the same layout
+        let line_shader =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Line Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("shaders/line.wgsl").into(),
+                ),
+            });
+        let axes_pipeline = Self::create_pipeline(
+            device,
+            "Axes",
+            &line_shader,
+            &[camera.bind_group_layout()],
+            Some(LineVertex::desc()),
+            wgpu::CompareFunction::LessEqual,
+            samples,
+        );
+        // The gizmo is drawn on top of everything
+        let gizmo_pipeline = Self::create_pipeline(
+            device,
+            "Gizmo",
+            &line_shader,
+            &[camera.bind_group_layout()],
+            Some(LineVertex::desc()),
+            wgpu::CompareFunction::Always,
+            samples,
+        );
+
+        let axes_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Axes Vertex Buffer"),
+                contents: bytemuck::cast_slice(&Self::axes_vertices(
+                    -AXIS_LENGTH,
+                    AXIS_LENGTH,
+                )),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let gizmo_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Gizmo Vertex Buffer"),
+                contents: bytemuck::cast_slice(&Self::axes_vertices(0.0, 1.0)),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let gizmo_uniform_buffer =
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Gizmo Buffer"),
+                size: std::mem::size_of::<[[f32; 4]; 4]>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+        let gizmo_bind_group =
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: camera.bind_group_layout(),
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: gizmo_uniform_buffer.as_entire_binding(),
+                }],
+                label: Some("gizmo_bind_group"),
+            });
+
+        Self {
+            grid: helpers.grid,
+            axes: helpers.axes,
+            gizmo: helpers.gizmo,
+            grid_bind_group,
+            grid_pipeline,
+            axes_buffer,
+            axes_pipeline,
+            gizmo_buffer,
+            gizmo_uniform_buffer,
+            gizmo_bind_group,
+            gizmo_pipeline,
+            size: (config.width as f32, config.height as f32),
+            has_been_updated: true,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        label: &str,
+        shader: &wgpu::ShaderModule,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        vertex_layout: Option<wgpu::VertexBufferLayout>,
+        depth_compare: wgpu::CompareFunction,
+        samples: u32,
+    ) -> wgpu::RenderPipeline {
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(&format!("{} Pipeline Layout", label)),
+                bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+        let topology = if vertex_layout.is_some() {
+            wgpu::PrimitiveTopology::LineList
+        } else {
+            wgpu::PrimitiveTopology::TriangleList
+        };
+        let buffers = vertex_layout.as_slice();
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&format!("{} Pipeline", label)),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: PostProcess::HDR_FORMAT,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology,
+                ..Default::default()
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DepthTexture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: samples,
+                ..Default::default()
+            },
+            multiview: None,
+        })
+    }
+
+    /// One line per axis, from `start` to `end` along the axis
+    fn axes_vertices(start: f32, end: f32) -> Vec<LineVertex> {
+        AXIS_COLORS
+            .iter()
+            .enumerate()
+            .flat_map(|(axis, color)| {
+                [start, end].map(|value| {
+                    let mut position = [0.0; 3];
+                    position[axis] = value;
+                    LineVertex {
+                        position,
+                        color: *color,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    pub fn grid(&self) -> bool {
+        self.grid
+    }
+
+    pub fn set_grid(&mut self, grid: bool) {
+        self.grid = grid;
+    }
+
+    pub fn axes(&self) -> bool {
+        self.axes
+    }
+
+    pub fn set_axes(&mut self, axes: bool) {
+        self.axes = axes;
+    }
+
+    pub fn gizmo(&self) -> bool {
+        self.gizmo
+    }
+
+    pub fn set_gizmo(&mut self, gizmo: bool) {
+        self.gizmo = gizmo;
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width as f32, height as f32);
+    }
+
+    /// Refresh the gizmo, needed when the main camera changed
+    pub fn update(
+        &mut self,
+        queue: &wgpu::Queue,
+        camera: &CameraBuffer,
+        camera_has_changed: bool,
+    ) {
+        if !(self.has_been_updated || camera_has_changed) {
+            return;
+        }
+        // Orthographic view of the unit axes, following the camera rotation
+        let projection = OPENGL_TO_WGPU_DEPTH
+            * cgmath::ortho(-1.2, 1.2, -1.2, 1.2, -2.0, 2.0);
+        let gizmo: [[f32; 4]; 4] = (projection * camera.rotation()).into();
+        queue.write_buffer(
+            &self.gizmo_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[gizmo]),
+        );
+        self.has_been_updated = false;
+    }
+
+    /// Draw the grid and the axes seen from a camera in a render pass that
+    /// already contains the scene
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera: &'a CameraBuffer,
+    ) {
+        if self.grid {
+            render_pass.set_pipeline(&self.grid_pipeline);
+            render_pass.set_bind_group(0, camera.bind_group(), &[]);
+            render_pass.set_bind_group(1, &self.grid_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        if self.axes {
+            render_pass.set_pipeline(&self.axes_pipeline);
+            render_pass.set_bind_group(0, camera.bind_group(), &[]);
+            render_pass.set_vertex_buffer(0, self.axes_buffer.slice(..));
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+
+    /// Draw the orientation gizmo, must be the last draw of the render pass
+    /// as it changes the viewport
+    pub fn render_gizmo<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        let size = GIZMO_SIZE.min(self.size.0).min(self.size.1);
+        if self.gizmo && size >= 1.0 {
+            render_pass.set_viewport(
+                (self.size.0 - size - GIZMO_MARGIN).max(0.0),
+                (self.size.1 - size - GIZMO_MARGIN).max(0.0),
+                size,
+                size,
+                0.0,
+                1.0,
+            );
+            render_pass.set_pipeline(&self.gizmo_pipeline);
+            render_pass.set_bind_group(0, &self.gizmo_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.gizmo_buffer.slice(..));
+            render_pass.draw(0..6, 0..1);
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use std::cell::RefCell;
+
+use cgmath::Vector3;
+
+use super::{
+    camera::CameraBuffer, culling::Aabb, pipeline::DepthTexture,
+    post::PostProcess,
+};
+
+/// Thickness in pixels of the lines drawn by the `debug_*` functions
+const DEBUG_LINE_THICKNESS: f32 = 2.0;
+/// Number of segments of each circle of `debug_sphere`
+const DEBUG_SPHERE_SEGMENTS: usize = 32;
+/// Number of segments reserved when the line buffer is created
+const INITIAL_CAPACITY: usize = 1024;
+
+thread_local! {
+    /// Segments queued since the last frame
+    static DEBUG_LINES: RefCell<Vec<LineSegment>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// How a polyline is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineStyle {
+    Solid,
+    /// Lengths in pixels of the dashes and of the gaps between them, the
+    /// pattern starts over on each segment
+    Dashed {
+        dash: f32,
+        gap: f32,
+    },
+}
+
+/// Connected line segments with a color per point
+#[derive(Debug, Clone)]
+pub struct Polyline {
+    points: Vec<(Vector3<f32>, [f32; 3])>,
+    thickness: f32,
+    style: LineStyle,
+    closed: bool,
+}
+
+/// A segment as uploaded to the GPU, one instance per segment
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct LineSegment {
+    /// xyz: position, w: thickness in pixels
+    start: [f32; 4],
+    end: [f32; 4],
+    start_color: [f32; 4],
+    end_color: [f32; 4],
+    /// x: dash length, y: gap length
+    style: [f32; 4],
+}
+
+/// Draws the lines queued with the `debug_*` functions
+///
+/// The queue is emptied every time the scenes are updated, so the lines
+/// must be queued again on each frame, see `DebugDraws`.
+pub struct LineRenderer {
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    count: u32,
+}
+
+impl Polyline {
+    pub fn new(thickness: f32) -> Self {
+        Self {
+            points: Vec::new(),
+            thickness,
+            style: LineStyle::Solid,
+            closed: false,
+        }
+    }
+
+    pub fn with_style(mut self, style: LineStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Connect the last point to the first one
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = closed;
+        self
+    }
+
+    pub fn with_point<P: Into<Vector3<f32>>>(
+        mut self,
+        position: P,
+        color: [f32; 3],
+    ) -> Self {
+        self.points.push((position.into(), color));
+        self
+    }
+
+    pub fn push<P: Into<Vector3<f32>>>(
+        &mut self,
+        position: P,
+        color: [f32; 3],
+    ) {
+        self.points.push((position.into(), color));
+    }
+
+    pub(crate) fn segments(&self) -> impl Iterator<Item = LineSegment> + '_ {
+        let closing = (self.closed && self.points.len() > 2)
+            .then(|| (self.points.last(), self.points.first()));
+        let style = match self.style {
+            LineStyle::Solid => [0.0; 4],
+            LineStyle::Dashed { dash, gap } => [dash, gap, 0.0, 0.0],
+        };
+        self.points
+            .windows(2)
+            .map(|pair| (pair.first(), pair.last()))
+            .chain(closing)
+            .filter_map(|(start, end)| Some((start?, end?)))
+            .map(
+                move |((start, start_color), (end, end_color))| LineSegment {
+                    start: start.extend(self.thickness).into(),
+                    end: end.extend(0.0).into(),
+                    start_color: [
+                        start_color[0],
+                        start_color[1],
+                        start_color[2],
+                        1.0,
+                    ],
+                    end_color: [end_color[0], end_color[1], end_color[2], 1.0],
+                    style,
+                },
+            )
+    }
+}
+
+/// Queue a polyline to be drawn on the next frame
+pub fn debug_polyline(polyline: &Polyline) {
+    DEBUG_LINES.with(|lines| lines.borrow_mut().extend(polyline.segments()));
+}
+
+/// Queue a line to be drawn on the next frame
+pub fn debug_line<P: Into<Vector3<f32>>>(a: P, b: P, color: [f32; 3]) {
+    debug_polyline(
+        &Polyline::new(DEBUG_LINE_THICKNESS)
+            .with_point(a, color)
+            .with_point(b, color),
+    );
+}
+
+/// Queue the edges of a box to be drawn on the next frame
+pub fn debug_aabb(aabb: &Aabb, color: [f32; 3]) {
+    let corner = |i: usize| {
+        Vector3::new(
+            if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+            if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+            if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+        )
+    };
+    // Every pair of corners differing by a single axis is an edge
+    for i in 0..8 {
+        for axis in [1, 2, 4] {
+            if i & axis == 0 {
+                debug_line(corner(i), corner(i | axis), color);
+            }
+        }
+    }
+}
+
+/// Queue three circles outlining a sphere to be drawn on the next frame
+pub fn debug_sphere<P: Into<Vector3<f32>>>(
+    center: P,
+    radius: f32,
+    color: [f32; 3],
+) {
+    let center = center.into();
+    for axis in 0..3 {
+        let circle = (0..DEBUG_SPHERE_SEGMENTS).fold(
+            Polyline::new(DEBUG_LINE_THICKNESS).with_closed(true),
+            |circle, i| {
+                let angle = i as f32 / DEBUG_SPHERE_SEGMENTS as f32
+                    * std::f32::consts::TAU;
+                let (sin, cos) = angle.sin_cos();
+                let mut offset = [0.0; 3];
+                offset[axis] = cos * radius;
+                offset[(axis + 1) % 3] = sin * radius;
+                circle.with_point(center + Vector3::from(offset), color)
+            },
+        );
+        debug_polyline(&circle);
+    }
+}
+
+/// Remove every queued segment
+pub(crate) fn take_debug_lines() -> Vec<LineSegment> {
+    DEBUG_LINES.with(|lines| std::mem::take(&mut *lines.borrow_mut()))
+}
+
+impl LineSegment {
+    const ATTRIBS: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Float32x4,
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Float32x4,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+impl LineRenderer {
+    pub fn init(
+        device: &wgpu::Device,
+        camera: &CameraBuffer,
+        samples: u32,
+    ) -> Self {
+        let shader =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Polyline Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("shaders/polyline.wgsl").into(),
+                ),
+            });
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Line Pipeline Layout"),
+                bind_group_layouts: &[camera.bind_group_layout()],
+                push_constant_ranges: &[],
+            });
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Line Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[LineSegment::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: PostProcess::HDR_FORMAT,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                // The quads can face either way depending on the segment
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DepthTexture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: samples,
+                    ..Default::default()
+                },
+                multiview: None,
+            });
+
+        Self {
+            pipeline,
+            buffer: Self::create_buffer(device, INITIAL_CAPACITY),
+            capacity: INITIAL_CAPACITY,
+            count: 0,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Line Segment Buffer"),
+            size: (capacity * std::mem::size_of::<LineSegment>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Number of segments drawn on the last frame
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Upload the segments to draw on the next frames
+    pub(crate) fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        segments: &[LineSegment],
+    ) {
+        if segments.len() > self.capacity {
+            self.capacity = segments.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        if !segments.is_empty() {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(segments));
+        }
+        self.count = segments.len() as u32;
+    }
+
+    /// Draw the lines in a render pass that already contains the scene
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera: &'a CameraBuffer,
+    ) {
+        if self.count == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera.bind_group(), &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.draw(0..6, 0..self.count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_polyline_segments() {
+        let polyline = Polyline::new(1.0)
+            .with_point([0.0, 0.0, 0.0], [1.0, 0.0, 0.0])
+            .with_point([1.0, 0.0, 0.0], [0.0, 1.0, 0.0])
+            .with_point([1.0, 1.0, 0.0], [0.0, 0.0, 1.0]);
+        assert_eq!(polyline.segments().count(), 2);
+        assert_eq!(polyline.clone().with_closed(true).segments().count(), 3);
+
+        let segment = polyline.segments().nth(1).unwrap();
+        assert_eq!(segment.start, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(segment.end_color, [0.0, 0.0, 1.0, 1.0]);
+
+        // A single point has no segment
+        let point = Polyline::new(1.0)
+            .with_point([0.0, 0.0, 0.0], [1.0, 1.0, 1.0])
+            .with_closed(true);
+        assert_eq!(point.segments().count(), 0);
+    }
+
+    #[test]
+    fn test_debug_lines_are_cleared() {
+        take_debug_lines();
+        debug_aabb(
+            &Aabb::new(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 1.0, 1.0),
+            ),
+            [1.0, 1.0, 1.0],
+        );
+        debug_sphere([0.0, 0.0, 0.0], 1.0, [1.0, 1.0, 1.0]);
+        assert_eq!(take_debug_lines().len(), 12 + 3 * DEBUG_SPHERE_SEGMENTS);
+        assert!(take_debug_lines().is_empty());
+    }
+}
@@ -0,0 +1,67 @@
+use super::types::Vertex;
+
+/// Relative margin around the thresholds, an instance must get this much
+/// smaller or bigger than a threshold to change of level, so the levels do
+/// not flicker when the camera moves around a threshold
+const HYSTERESIS: f32 = 0.15;
+
+/// A simplified mesh of a shape
+///
+/// It replaces the previous levels for the instances whose projected size,
+/// the diameter of their bounding sphere over the height of the view, is
+/// under `max_size`.
+#[derive(Debug)]
+pub struct Lod {
+    pub(crate) vertices: Vec<Vertex>,
+    pub(crate) indices: Vec<u16>,
+    pub(crate) max_size: f32,
+}
+
+/// The GPU buffers of a `Lod`
+pub struct LodMesh {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_indices: u32,
+    pub max_size: f32,
+    /// Number of visible instances drawn with this mesh
+    pub num_instances: u32,
+}
+
+/// Pick the level of an instance from its projected size
+///
+/// `max_sizes` holds the thresholds of the levels after the first one, in
+/// decreasing order. Level 0 is the shape itself.
+pub(crate) fn select(max_sizes: &[f32], size: f32, current: usize) -> usize {
+    let mut level = current.min(max_sizes.len());
+    while level < max_sizes.len()
+        && size < max_sizes[level] * (1.0 - HYSTERESIS)
+    {
+        level += 1;
+    }
+    while level > 0 && size > max_sizes[level - 1] * (1.0 + HYSTERESIS) {
+        level -= 1;
+    }
+    level
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let max_sizes = [0.1, 0.01];
+        assert_eq!(select(&max_sizes, 0.5, 0), 0);
+        assert_eq!(select(&max_sizes, 0.05, 0), 1);
+        // Skips the levels in between
+        assert_eq!(select(&max_sizes, 0.001, 0), 2);
+        assert_eq!(select(&max_sizes, 0.5, 2), 0);
+        // Around a threshold the current level is kept
+        assert_eq!(select(&max_sizes, 0.095, 0), 0);
+        assert_eq!(select(&max_sizes, 0.105, 1), 1);
+        assert_eq!(select(&max_sizes, 0.08, 0), 1);
+        assert_eq!(select(&max_sizes, 0.12, 1), 0);
+        // Without levels the shape is always used
+        assert_eq!(select(&[], 0.0, 0), 0);
+    }
+}
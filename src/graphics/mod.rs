@@ -1,7 +1,23 @@
-mod camera;
-mod pipeline;
+pub mod background;
+pub mod batch;
+pub(crate) mod camera;
+pub mod capabilities;
+pub mod culling;
+pub mod debug_draw;
+pub mod graph;
+pub mod helpers;
+pub mod lines;
+pub mod lod;
+pub mod pipeline;
+pub mod points;
+pub mod post;
+pub mod profiler;
+pub mod shadow;
 pub mod shapes;
+pub mod text;
 pub mod types;
+pub mod viewport;
 
+pub use capabilities::Capabilities;
 pub use pipeline::Pipeline;
 pub use types::Vertex;
@@ -1,26 +1,110 @@
 use core::num;
-use std::{cell::RefCell, error::Error, rc::Rc};
+use std::{cell::RefCell, rc::Rc};
 
 use wgpu::Device;
 
-use crate::render::GraphicalProcessUnit;
+use crate::{
+    config::Config,
+    input::{Action, Bindings, InputEvent},
+    render::GraphicalProcessUnit,
+};
 
 use super::{
+    background::Background,
+    batch::{DrawBatch, DrawMode},
     camera::{self, CameraBuffer},
+    capabilities::Capabilities,
+    culling::Frustum,
+    helpers::Helpers,
+    lines::LineRenderer,
+    points::{PointBuffer, PointRenderer},
+    post::PostProcess,
+    profiler::{GpuPass, GpuProfiler},
+    shadow::ShadowMap,
     shapes::{self, Shape},
+    text::TextRenderer,
     types::{Buffer, Instance, InstanceRaw, Vertex},
+    viewport::Viewports,
 };
 
 pub struct Pipeline {
     render_pipeline: wgpu::RenderPipeline,
+    /// Same as `render_pipeline` drawing the edges of the triangles, when
+    /// the device has `POLYGON_MODE_LINE`
+    wireframe_pipeline: Option<wgpu::RenderPipeline>,
+    wireframe: bool,
+    /// What the device has been created with
+    pub capabilities: Capabilities,
     background_color: wgpu::Color,
     pub camera: CameraBuffer,
     pub camera_controller: camera::CameraController,
+    /// Inputs of the actions, the ones of the scenes included
+    pub bindings: Bindings,
     depth_texture: DepthTexture,
+    /// Samples per pixel of the scene
+    samples: u32,
+    /// Multisampled color texture resolved into the HDR texture, when the
+    /// scene is multisampled
+    msaa_view: Option<wgpu::TextureView>,
+    pub shadow: ShadowMap,
+    pub post: PostProcess,
+    pub background: Background,
+    pub helpers: Helpers,
+    pub lines: LineRenderer,
+    pub points: PointRenderer,
+    pub text: TextRenderer,
+    pub batch: DrawBatch,
+    pub profiler: GpuProfiler,
+    pub viewports: Viewports,
+    frustum: Frustum,
+    culling: bool,
+    frustum_has_changed: bool,
+    culling_stats: (u32, u32),
+}
+
+pub(crate) struct DepthTexture {
+    pub view: wgpu::TextureView,
 }
 
-struct DepthTexture {
-    view: wgpu::TextureView,
+/// The color and depth textures a view of the scene is drawn into
+///
+/// The color texture is in `PostProcess::HDR_FORMAT`, and it has the samples
+/// of the pipeline as well as the depth texture.
+pub struct RenderTarget<'a> {
+    pub color: &'a wgpu::TextureView,
+    /// Receives the color when `color` is multisampled
+    pub resolve: Option<&'a wgpu::TextureView>,
+    pub depth: &'a wgpu::TextureView,
+    pub clear: wgpu::Color,
+}
+
+/// Multisampled color texture in `PostProcess::HDR_FORMAT`, `None` without
+/// multisampling
+pub(crate) fn create_msaa_view(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+    samples: u32,
+    label: &str,
+) -> Option<wgpu::TextureView> {
+    (samples > 1).then(|| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: samples,
+                dimension: wgpu::TextureDimension::D2,
+                format: PostProcess::HDR_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    })
 }
 
 impl Pipeline {
@@ -31,118 +115,254 @@ impl Pipeline {
     pub fn init(
         gpu: &GraphicalProcessUnit,
         shader: &'static str,
-    ) -> Result<Self, Box<dyn Error>> {
+        app_config: &Config,
+    ) -> Result<Self, crate::Error> {
+        let shadow_config = &app_config.shadow;
+        let capabilities = gpu.capabilities.clone();
+        let samples = capabilities.samples(app_config.msaa);
+        // Catch the compilation errors instead of the uncaptured error
+        // handler
+        gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
         let shader =
             &gpu.device
                 .create_shader_module(wgpu::ShaderModuleDescriptor {
                     label: Some("Shader"),
                     source: wgpu::ShaderSource::Wgsl(shader.into()),
                 });
+        if let Some(error) = pollster::block_on(gpu.device.pop_error_scope()) {
+            return Err(crate::Error::Shader {
+                label: "Shader".to_string(),
+                message: error.to_string(),
+            });
+        }
 
-        let camera = CameraBuffer::init(&gpu.device, &gpu.config);
+        let camera =
+            CameraBuffer::init(&gpu.device, &gpu.config, &app_config.camera);
+        let shadow = ShadowMap::init(
+            &gpu.device,
+            shadow_config
+                .resolution
+                .min(capabilities.max_texture_size()),
+            shadow_config.bias,
+            shadow_config.enabled,
+        );
+        let mut background =
+            Background::init(&gpu.device, &gpu.queue, &camera, samples);
+        if !app_config.background.skybox.is_empty() {
+            background.load_skybox(
+                &gpu.device,
+                &gpu.queue,
+                &app_config.background.skybox,
+            )?;
+        }
+        background.set_mode(app_config.background.mode);
+        let mut viewports = Viewports::init(&gpu.device, &gpu.config, samples);
+        viewports.add_layout(&gpu.device, &camera, app_config.viewports);
 
         let render_pipeline_layout = &gpu.device.create_pipeline_layout(
             &wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&camera.bind_group_layout()],
+                bind_group_layouts: &[
+                    &camera.bind_group_layout(),
+                    shadow.bind_group_layout(),
+                ],
                 push_constant_ranges: &[],
             },
         );
 
-        let render_pipeline = gpu.device.create_render_pipeline(
-            &wgpu::RenderPipelineDescriptor {
-                label: Some("Render Pipeline"),
-                layout: Some(&render_pipeline_layout),
-                vertex: wgpu::VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &[Vertex::desc(), InstanceRaw::desc()],
-                },
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: gpu.config.format,
-                        blend: Some(wgpu::BlendState {
-                            color: wgpu::BlendComponent::REPLACE,
-                            alpha: wgpu::BlendComponent::REPLACE,
-                        }),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    // cull_mode: Some(wgpu::Face::Front),
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    unclipped_depth: false,
-                    conservative: false,
-                },
-                // depth_stencil: None,
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: DepthTexture::DEPTH_FORMAT,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
-                    stencil: Default::default(),
-                    bias: Default::default(),
-                }),
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
-            },
-        );
+        let create_render_pipeline = |label, polygon_mode| {
+            gpu.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&render_pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: PostProcess::HDR_FORMAT,
+                            blend: Some(wgpu::BlendState {
+                                color: wgpu::BlendComponent::REPLACE,
+                                alpha: wgpu::BlendComponent::REPLACE,
+                            }),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        strip_index_format: None,
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: Some(wgpu::Face::Back),
+                        // cull_mode: Some(wgpu::Face::Front),
+                        polygon_mode,
+                        unclipped_depth: false,
+                        conservative: false,
+                    },
+                    // depth_stencil: None,
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: DepthTexture::DEPTH_FORMAT,
+                        depth_write_enabled: true,
+                        depth_compare: wgpu::CompareFunction::Less,
+                        stencil: Default::default(),
+                        bias: Default::default(),
+                    }),
+                    multisample: wgpu::MultisampleState {
+                        count: samples,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                })
+        };
+        let render_pipeline =
+            create_render_pipeline("Render Pipeline", wgpu::PolygonMode::Fill);
+        let wireframe_pipeline = capabilities.wireframe().then(|| {
+            create_render_pipeline(
+                "Wireframe Render Pipeline",
+                wgpu::PolygonMode::Line,
+            )
+        });
 
         Ok(Self {
             render_pipeline,
+            wireframe_pipeline,
+            wireframe: false,
             background_color: wgpu::Color {
                 r: 0.1,
                 g: 0.2,
                 b: 0.3,
                 a: 1.0,
             },
-            camera,
-            camera_controller: camera::CameraController::default(),
-            depth_texture: DepthTexture::create_depth_structure(
-                gpu,
+            camera_controller: camera::CameraController::new(
+                &app_config.camera,
+            ),
+            bindings: app_config.keys.clone(),
+            depth_texture: DepthTexture::create_multisampled(
+                &gpu.device,
+                gpu.config.width,
+                gpu.config.height,
+                samples,
                 "Depth Texture",
             ),
+            samples,
+            msaa_view: create_msaa_view(
+                &gpu.device,
+                gpu.config.width,
+                gpu.config.height,
+                samples,
+                "Multisampled Texture",
+            ),
+            shadow,
+            post: PostProcess::init(&gpu.device, &gpu.config),
+            background,
+            helpers: Helpers::init(
+                &gpu.device,
+                &gpu.config,
+                &camera,
+                samples,
+                &app_config.helpers,
+            ),
+            lines: LineRenderer::init(&gpu.device, &camera, samples),
+            points: PointRenderer::init(&gpu.device, &camera, samples),
+            text: TextRenderer::init(
+                &gpu.device,
+                &gpu.queue,
+                &gpu.config,
+                &camera,
+            )?,
+            batch: DrawBatch::init(&gpu.device),
+            profiler: GpuProfiler::init(&gpu.device, &gpu.queue),
+            viewports,
+            frustum: Frustum::from_matrix(&camera.uniform.view_proj()),
+            culling: true,
+            frustum_has_changed: true,
+            culling_stats: (0, 0),
+            camera,
+            capabilities,
         })
     }
 
+    /// Keep the camera and the settings changed at runtime of a pipeline
+    /// whose device was lost
+    pub fn restore(&mut self, device: &wgpu::Device, lost: &Pipeline) {
+        self.camera.camera = lost.camera.camera.clone();
+        self.camera.has_been_updated = true;
+        self.background_color = lost.background_color;
+        self.bindings = lost.bindings.clone();
+        self.set_culling(lost.culling);
+        self.set_wireframe(lost.wireframe);
+        self.batch.set_enabled(lost.batch.enabled());
+
+        self.shadow.set_light(lost.shadow.light().clone());
+        self.shadow.set_enabled(lost.shadow.enabled());
+        self.shadow.set_bias(lost.shadow.bias());
+        let resolution = lost
+            .shadow
+            .resolution()
+            .min(self.capabilities.max_texture_size());
+        if self.shadow.resolution() != resolution {
+            self.shadow.set_resolution(device, resolution);
+        }
+
+        self.background.set_mode(lost.background.mode());
+        let (top, bottom) = lost.background.gradient();
+        self.background.set_gradient(top, bottom);
+        self.helpers.set_grid(lost.helpers.grid());
+        self.helpers.set_axes(lost.helpers.axes());
+        self.helpers.set_gizmo(lost.helpers.gizmo());
+
+        let effects = lost.post.effects();
+        self.post.configure(
+            &effects
+                .iter()
+                .map(|effect| (effect.name().to_string(), effect.enabled()))
+                .collect::<Vec<_>>(),
+        );
+        for effect in effects {
+            self.post.set_params(effect.name(), effect.params());
+        }
+
+        self.viewports
+            .restore(device, &self.camera, &lost.viewports);
+    }
+
+    /// Move the camera with the inputs bound to its actions, returns
+    /// whether the event has been used
     pub fn process_input(
         &mut self,
-        event: &winit::event::WindowEvent,
+        event: &InputEvent,
         mouse_pressed: &mut bool,
     ) -> bool {
-        match event {
-            winit::event::WindowEvent::KeyboardInput {
-                event:
-                    winit::event::KeyEvent {
-                        physical_key: winit::keyboard::PhysicalKey::Code(key),
-                        state,
-                        ..
-                    },
-                ..
-            } => self.camera_controller.process_keyboard(*key, *state),
-            winit::event::WindowEvent::MouseWheel { delta, .. } => {
-                self.camera_controller.process_scroll(delta);
-                true
+        let (input, state) = match *event {
+            InputEvent::Scroll { delta } => {
+                self.camera_controller.process_scroll(&delta);
+                return true;
             }
-            winit::event::WindowEvent::MouseInput {
-                button: winit::event::MouseButton::Left,
-                state,
-                ..
-            } => {
-                *mouse_pressed = *state == winit::event::ElementState::Pressed;
-                true
+            InputEvent::Input { input, state } => (input, state),
+            _ => return false,
+        };
+        let mut handled = false;
+        let mut for_scenes = false;
+        for action in self.bindings.actions(input) {
+            match action {
+                Action::RotateCamera => {
+                    *mouse_pressed =
+                        state == winit::event::ElementState::Pressed;
+                    handled = true;
+                }
+                // Left to the application, which calls the scenes
+                Action::Scene(_) => for_scenes = true,
+                _ => {
+                    handled |=
+                        self.camera_controller.process_action(action, state)
+                }
             }
-            _ => false,
         }
+        handled && !for_scenes
     }
 
     pub fn process_mouse_motion(&mut self, position: (f64, f64)) {
@@ -151,33 +371,128 @@ impl Pipeline {
 
     pub fn resize(&mut self, gpu: &GraphicalProcessUnit) {
         self.camera.resize(gpu.config.width, gpu.config.height);
-        self.depth_texture =
-            DepthTexture::create_depth_structure(&gpu, "Depth Texture");
+        self.depth_texture = DepthTexture::create_multisampled(
+            &gpu.device,
+            gpu.config.width,
+            gpu.config.height,
+            self.samples,
+            "Depth Texture",
+        );
+        self.msaa_view = create_msaa_view(
+            &gpu.device,
+            gpu.config.width,
+            gpu.config.height,
+            self.samples,
+            "Multisampled Texture",
+        );
+        self.post.resize(&gpu.device, &gpu.config);
+        self.helpers.resize(gpu.config.width, gpu.config.height);
+        self.text.resize(gpu.config.width, gpu.config.height);
+        self.viewports
+            .resize(&gpu.device, gpu.config.width, gpu.config.height);
+    }
+
+    /// The next frames differ from the last one without any input
+    pub fn is_animating(&self) -> bool {
+        self.camera_controller.is_moving()
     }
 
     pub fn update(&mut self, queue: &wgpu::Queue, dt: std::time::Duration) {
         // Update camera
         self.camera_controller.update_camera(&mut self.camera, dt);
-        self.camera.update(queue);
+        let camera_has_changed = self.camera.has_been_updated;
+        if camera_has_changed {
+            self.camera.update(queue);
+            self.frustum =
+                Frustum::from_matrix(&self.camera.uniform.view_proj());
+            self.frustum_has_changed = true;
+        }
+        self.shadow.update(queue);
+        self.background.update(queue);
+        self.helpers.update(queue, &self.camera, camera_has_changed);
+        self.post.update(queue);
+        self.viewports.update(queue);
     }
 
-    pub fn render<'a, I>(
-        &self,
-        view: &wgpu::TextureView,
-        encoder: &mut wgpu::CommandEncoder,
+    pub fn culling(&self) -> bool {
+        self.culling
+    }
+
+    pub fn set_culling(&mut self, culling: bool) {
+        if self.culling != culling {
+            self.culling = culling;
+            self.frustum_has_changed = true;
+        }
+    }
+
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    /// Draw the shapes in wireframe, ignored when the device does not
+    /// support it
+    pub fn set_wireframe(&mut self, wireframe: bool) {
+        self.wireframe = wireframe && self.wireframe_pipeline.is_some();
+    }
+
+    /// Returns the number of visible instances and the total number of
+    /// instances computed by the last culling pass
+    pub fn culling_stats(&self) -> (u32, u32) {
+        self.culling_stats
+    }
+
+    /// Skip the instances outside of the camera frustum
+    ///
+    /// Only the buffers that have been reloaded are culled, unless the
+    /// camera moved since the last call.
+    pub fn cull<'a, I>(&mut self, queue: &wgpu::Queue, buffers: I)
+    where
+        I: Iterator<Item = &'a mut Buffer>,
+    {
+        let frustum = self.culling.then_some(&self.frustum);
+        let mut visible = 0;
+        let mut total = 0;
+        for buffer in buffers {
+            if self.frustum_has_changed || buffer.needs_culling {
+                buffer.cull(queue, frustum, &self.camera);
+            }
+            visible += buffer.num_instances;
+            total += buffer.total_instances();
+        }
+        self.culling_stats = (visible, total);
+        self.frustum_has_changed = false;
+    }
+
+    /// Begin a pass drawing the scene seen from a camera into a target: the
+    /// shapes, the point clouds, the background, the grid, the axes and the
+    /// debug lines
+    ///
+    /// The buffers are culled against the main camera, the other cameras
+    /// draw every instance instead.
+    #[allow(clippy::too_many_arguments)]
+    fn render_scene<'a, 'e, I, P>(
+        &'e self,
+        encoder: &'e mut wgpu::CommandEncoder,
+        target: RenderTarget<'e>,
+        camera: &'e CameraBuffer,
         buffers: I,
-    ) where
+        point_clouds: P,
+        culled: bool,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'e>>,
+    ) -> wgpu::RenderPass<'e>
+    where
+        'a: 'e,
         I: Iterator<Item = &'a Buffer>,
+        P: Iterator<Item = &'a PointBuffer>,
     {
-        // Draw the buffer if it exists
         let mut render_pass =
             encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view,
-                    resolve_target: None,
+                    view: target.color,
+                    resolve_target: target.resolve,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(self.background_color),
+                        load: wgpu::LoadOp::Clear(target.clear),
                         // load: wgpu::LoadOp::Load,
                         store: wgpu::StoreOp::Store,
                     },
@@ -185,7 +500,7 @@ impl Pipeline {
                 // depth_stencil_attachment: None,
                 depth_stencil_attachment: Some(
                     wgpu::RenderPassDepthStencilAttachment {
-                        view: &self.depth_texture.view,
+                        view: target.depth,
                         depth_ops: Some(wgpu::Operations {
                             load: wgpu::LoadOp::Clear(1.0),
                             store: wgpu::StoreOp::Store,
@@ -194,26 +509,113 @@ impl Pipeline {
                     },
                 ),
                 occlusion_query_set: None,
-                timestamp_writes: None,
+                timestamp_writes,
             });
 
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_bind_group(0, &self.camera.bind_group(), &[]);
-        for buffer in buffers {
-            let vertex_buffer = &buffer.vertex_buffer;
-            let index_buffer = &buffer.index_buffer;
-            let instance_buffer = &buffer.instance_buffer;
-            let num_indices = buffer.num_indices;
-            let num_instances = buffer.num_instances;
-
-            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
-            render_pass.set_index_buffer(
-                index_buffer.slice(..),
-                wgpu::IndexFormat::Uint16,
+        render_pass.set_pipeline(match &self.wireframe_pipeline {
+            Some(wireframe_pipeline) if self.wireframe => wireframe_pipeline,
+            _ => &self.render_pipeline,
+        });
+        render_pass.set_bind_group(0, camera.bind_group(), &[]);
+        render_pass.set_bind_group(1, self.shadow.bind_group(), &[]);
+        if !culled {
+            for buffer in buffers {
+                buffer.render_all(&mut render_pass);
+            }
+        } else if self.batch.mode() != DrawMode::Direct {
+            // Every buffer has been packed by `DrawBatch::prepare`
+            self.batch.render(&mut render_pass);
+        } else {
+            for buffer in buffers {
+                buffer.render(&mut render_pass);
+            }
+        }
+        self.points.render(&mut render_pass, camera, point_clouds);
+        // Drawn last so the depth test discards the covered pixels
+        self.background.render(&mut render_pass, camera);
+        // Blended over the scene and the background
+        self.helpers.render(&mut render_pass, camera);
+        self.lines.render(&mut render_pass, camera);
+        render_pass
+    }
+
+    /// Draw the scene seen from a camera into a target
+    ///
+    /// Every instance is drawn, the culling only applies to the main camera.
+    /// The camera must share the layout of `self.camera`, see
+    /// `CameraBuffer::share`, and the shadow map of the last `render` is
+    /// used. The gizmo and the texts are only drawn in the window.
+    pub fn render_to<'a, I, P>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: RenderTarget,
+        camera: &CameraBuffer,
+        buffers: I,
+        point_clouds: P,
+    ) where
+        I: Iterator<Item = &'a Buffer>,
+        P: Iterator<Item = &'a PointBuffer>,
+    {
+        self.render_scene(
+            encoder,
+            target,
+            camera,
+            buffers,
+            point_clouds,
+            false,
+            None,
+        );
+    }
+
+    pub fn render<'a, I, P>(
+        &self,
+        view: &wgpu::TextureView,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: I,
+        point_clouds: P,
+    ) where
+        I: Iterator<Item = &'a Buffer> + Clone,
+        P: Iterator<Item = &'a PointBuffer> + Clone,
+    {
+        // Render the shadow map first, it is sampled by the other passes
+        self.shadow.render(encoder, buffers.clone(), &self.profiler);
+
+        for viewport in self.viewports.iter().filter(|v| v.enabled()) {
+            self.render_to(
+                encoder,
+                viewport.target(self.background_color),
+                &viewport.camera,
+                buffers.clone(),
+                point_clouds.clone(),
             );
-            render_pass.draw_indexed(0..num_indices, 0, 0..num_instances);
         }
+
+        // The scene is drawn into the HDR texture, then post-processed into
+        // the view
+        let target = RenderTarget {
+            color: self.msaa_view.as_ref().unwrap_or(self.post.hdr_view()),
+            resolve: self.msaa_view.as_ref().map(|_| self.post.hdr_view()),
+            depth: &self.depth_texture.view,
+            clear: self.background_color,
+        };
+        let mut render_pass = self.render_scene(
+            encoder,
+            target,
+            &self.camera,
+            buffers,
+            point_clouds,
+            true,
+            self.profiler.timestamp_writes(GpuPass::Main),
+        );
+        self.helpers.render_gizmo(&mut render_pass);
+        drop(render_pass);
+
+        self.post.render(encoder, view, &self.profiler);
+        // Drawn over the tonemapped image, they are not post-processed
+        self.viewports.composite(encoder, view);
+        // Drawn over the post-processed image to stay sharp
+        self.text
+            .render(encoder, view, &self.camera, &self.profiler);
     }
 }
 
@@ -221,27 +623,46 @@ impl DepthTexture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat =
         wgpu::TextureFormat::Depth32Float; // 1.
 
-    pub fn create_depth_structure(
-        gpu: &GraphicalProcessUnit,
+    pub fn create(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        label: &str,
+    ) -> Self {
+        Self::create_multisampled(device, width, height, 1, label)
+    }
+
+    pub fn create_multisampled(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        samples: u32,
         label: &str,
     ) -> Self {
         let size = wgpu::Extent3d {
-            width: gpu.config.width,
-            height: gpu.config.height,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
         let desc = wgpu::TextureDescriptor {
             label: Some(label),
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count: samples,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
-                | wgpu::TextureUsages::TEXTURE_BINDING,
+            // Only the single sampled depth textures are read by the shaders,
+            // the GL backend cannot attach a multisampled texture next to
+            // the multisampled color renderbuffer
+            usage: if samples > 1 {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+            } else {
+                wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+            },
             view_formats: &[],
         };
-        let texture = gpu.device.create_texture(&desc);
+        let texture = device.create_texture(&desc);
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
         // let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
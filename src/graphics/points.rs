@@ -0,0 +1,317 @@
+use std::path::Path;
+
+use wgpu::util::DeviceExt;
+
+use super::{camera::CameraBuffer, pipeline::DepthTexture, post::PostProcess};
+use crate::utils::point_cloud;
+
+/// Unit of the size of the points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointSizing {
+    /// Diameter in pixels, the points keep the same size on screen
+    Screen,
+    /// Diameter in world units, the points get smaller with the distance
+    World,
+}
+
+/// Shape of the sprite drawn for each point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointShape {
+    Square,
+    Circle,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Point {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+    pub size: f32,
+}
+
+/// Set of points drawn as camera facing sprites
+///
+/// Point clouds are much cheaper than shapes for large numbers of small
+/// objects, such as particles: each point is a single quad.
+pub struct PointCloud {
+    points: Vec<Point>,
+    sizing: PointSizing,
+    shape: PointShape,
+    /// Incremented at each change, see `Shape::buffer`
+    revision: u64,
+}
+
+/// GPU side of a point cloud
+pub struct PointBuffer {
+    buffer: wgpu::Buffer,
+    num_points: u32,
+    bind_group: wgpu::BindGroup,
+}
+
+/// Draws the point clouds
+///
+/// The sprites face the camera they are drawn with, using the axes and the
+/// target size of its uniform.
+pub struct PointRenderer {
+    pipeline: wgpu::RenderPipeline,
+    cloud_bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl Point {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3,
+        2 => Float32,
+    ];
+
+    pub fn new(position: [f32; 3], color: [f32; 3], size: f32) -> Self {
+        Self {
+            position,
+            color,
+            size,
+        }
+    }
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+impl PointCloud {
+    pub fn new(points: Vec<Point>) -> Self {
+        Self {
+            points,
+            sizing: PointSizing::Screen,
+            shape: PointShape::Circle,
+            revision: 1,
+        }
+    }
+
+    /// Load a `.ply` or `.xyz` file, every point gets the same size
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        size: f32,
+    ) -> Result<Self, crate::Error> {
+        let path = path.as_ref();
+        log::info!("Loading point cloud {:?}", path);
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        let points = match extension.as_deref() {
+            Some("ply") => std::fs::read(path)
+                .map_err(Into::into)
+                .and_then(|data| point_cloud::parse_ply(&data)),
+            Some("xyz") => std::fs::read_to_string(path)
+                .map_err(Into::into)
+                .and_then(|text| point_cloud::parse_xyz(&text)),
+            _ => Err("unsupported point cloud format".into()),
+        }
+        .map_err(|error| crate::Error::asset(path, error))?;
+        log::info!("Loaded {} points", points.len());
+        Ok(Self::new(
+            points
+                .into_iter()
+                .map(|point| Point::new(point.position, point.color, size))
+                .collect(),
+        ))
+    }
+
+    pub fn with_sizing(mut self, sizing: PointSizing) -> Self {
+        self.sizing = sizing;
+        self
+    }
+
+    pub fn with_shape(mut self, shape: PointShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    /// Mutable access to the points, the cloud is uploaded again
+    pub fn points_mut(&mut self) -> &mut Vec<Point> {
+        self.revision += 1;
+        &mut self.points
+    }
+
+    pub fn sizing(&self) -> PointSizing {
+        self.sizing
+    }
+
+    pub fn set_sizing(&mut self, sizing: PointSizing) {
+        self.sizing = sizing;
+        self.revision += 1;
+    }
+
+    pub fn shape(&self) -> PointShape {
+        self.shape
+    }
+
+    pub fn set_shape(&mut self, shape: PointShape) {
+        self.shape = shape;
+        self.revision += 1;
+    }
+
+    /// Returns a new buffer if the cloud changed since `loaded`, see
+    /// `Shape::buffer`
+    pub fn buffer(
+        &self,
+        device: &wgpu::Device,
+        renderer: &PointRenderer,
+        loaded: &mut u64,
+    ) -> Option<PointBuffer> {
+        if *loaded != self.revision {
+            *loaded = self.revision;
+            Some(renderer.load_buffer(device, self))
+        } else {
+            None
+        }
+    }
+}
+
+impl PointRenderer {
+    pub fn init(
+        device: &wgpu::Device,
+        camera: &CameraBuffer,
+        samples: u32,
+    ) -> Self {
+        let cloud_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("point_cloud_bind_group_layout"),
+            });
+
+        let shader =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Points Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("shaders/points.wgsl").into(),
+                ),
+            });
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Points Pipeline Layout"),
+                bind_group_layouts: &[
+                    camera.bind_group_layout(),
+                    &cloud_bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Points Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[Point::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: PostProcess::HDR_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DepthTexture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: samples,
+                    ..Default::default()
+                },
+                multiview: None,
+            });
+
+        Self {
+            pipeline,
+            cloud_bind_group_layout,
+        }
+    }
+
+    fn load_buffer(
+        &self,
+        device: &wgpu::Device,
+        cloud: &PointCloud,
+    ) -> PointBuffer {
+        let buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Point Buffer"),
+                contents: bytemuck::cast_slice(&cloud.points),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let params = [
+            if cloud.sizing == PointSizing::World {
+                1.0
+            } else {
+                0.0
+            },
+            if cloud.shape == PointShape::Circle {
+                1.0
+            } else {
+                0.0
+            },
+            0.0f32,
+            0.0,
+        ];
+        let params_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Point Cloud Buffer"),
+                contents: bytemuck::cast_slice(&params),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.cloud_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+            label: Some("point_cloud_bind_group"),
+        });
+        PointBuffer {
+            buffer,
+            num_points: cloud.points.len() as u32,
+            bind_group,
+        }
+    }
+
+    pub fn render<'a, 'b: 'a, I>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        camera: &'a CameraBuffer,
+        clouds: I,
+    ) where
+        I: Iterator<Item = &'b PointBuffer>,
+    {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera.bind_group(), &[]);
+        for cloud in clouds.filter(|cloud| cloud.num_points > 0) {
+            render_pass.set_bind_group(1, &cloud.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, cloud.buffer.slice(..));
+            render_pass.draw(0..6, 0..cloud.num_points);
+        }
+    }
+}
@@ -0,0 +1,554 @@
+use wgpu::util::DeviceExt;
+
+use super::profiler::{GpuPass, GpuProfiler};
+
+/// Code shared by every effect: bindings and fullscreen vertex shader
+pub(crate) const POST_COMMON: &str = include_str!("shaders/post/common.wgsl");
+
+pub(crate) const TONEMAP: &str = "Tonemapping";
+pub(crate) const GAMMA: &str = "Gamma";
+pub(crate) const FXAA: &str = "FXAA";
+pub(crate) const BLOOM: &str = "Bloom";
+pub(crate) const VIGNETTE: &str = "Vignette";
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct PostUniform {
+    resolution: [f32; 4],
+    params: [f32; 4],
+}
+
+/// A fullscreen pass of the post-processing chain
+///
+/// The WGSL source of an effect only has to provide a `fs_main` fragment
+/// shader, the bindings (`input_texture`, `input_sampler` and `post`) and the
+/// vertex shader are declared in `shaders/post/common.wgsl`.
+pub struct PostEffect {
+    name: String,
+    enabled: bool,
+    params: [f32; 4],
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    // One bind group per ping-pong texture used as input
+    bind_groups: [wgpu::BindGroup; 2],
+    has_been_updated: bool,
+}
+
+struct HdrTexture {
+    view: wgpu::TextureView,
+}
+
+/// Offscreen HDR target and the chain of effects applied before presenting
+///
+/// The scene is drawn into the first HDR texture, every enabled effect reads
+/// one texture and writes into the other, then the result is copied into the
+/// surface.
+pub struct PostProcess {
+    targets: [HdrTexture; 2],
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    effects: Vec<PostEffect>,
+    blit: PostEffect,
+    resolution: [f32; 4],
+}
+
+impl HdrTexture {
+    fn create(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("HDR Texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: PostProcess::HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self { view }
+    }
+}
+
+impl PostEffect {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        targets: &[HdrTexture; 2],
+        name: &str,
+        source: &str,
+        format: wgpu::TextureFormat,
+        params: [f32; 4],
+        resolution: [f32; 4],
+    ) -> Self {
+        let shader =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(name),
+                source: wgpu::ShaderSource::Wgsl(
+                    format!("{}\n{}", POST_COMMON, source).into(),
+                ),
+            });
+        let pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Post Pipeline Layout"),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(name),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        let buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Post Buffer"),
+                contents: bytemuck::cast_slice(&[PostUniform {
+                    resolution,
+                    params,
+                }]),
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+        let bind_groups =
+            Self::create_bind_groups(device, layout, sampler, targets, &buffer);
+
+        Self {
+            name: name.to_string(),
+            enabled: true,
+            params,
+            pipeline,
+            buffer,
+            bind_groups,
+            has_been_updated: false,
+        }
+    }
+
+    fn create_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        targets: &[HdrTexture; 2],
+        buffer: &wgpu::Buffer,
+    ) -> [wgpu::BindGroup; 2] {
+        targets.each_ref().map(|target| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(
+                            &target.view,
+                        ),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: buffer.as_entire_binding(),
+                    },
+                ],
+                label: Some("post_bind_group"),
+            })
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn params(&self) -> [f32; 4] {
+        self.params
+    }
+
+    fn update(&mut self, queue: &wgpu::Queue, resolution: [f32; 4]) {
+        if !self.has_been_updated {
+            return;
+        }
+        queue.write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[PostUniform {
+                resolution,
+                params: self.params,
+            }]),
+        );
+        self.has_been_updated = false;
+    }
+
+    fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        input: usize,
+        output: &wgpu::TextureView,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(&self.name),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes,
+            });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_groups[input], &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+impl PostProcess {
+    pub const HDR_FORMAT: wgpu::TextureFormat =
+        wgpu::TextureFormat::Rgba16Float;
+
+    pub fn init(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Self {
+        let targets = [
+            HdrTexture::create(device, config.width, config.height),
+            HdrTexture::create(device, config.width, config.height),
+        ];
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+                label: Some("post_bind_group_layout"),
+            });
+        let resolution = Self::resolution(config.width, config.height);
+
+        let blit = PostEffect::new(
+            device,
+            &bind_group_layout,
+            &sampler,
+            &targets,
+            "Blit",
+            include_str!("shaders/post/blit.wgsl"),
+            config.format,
+            [0.0; 4],
+            resolution,
+        );
+
+        let mut post = Self {
+            targets,
+            sampler,
+            bind_group_layout,
+            effects: Vec::new(),
+            blit,
+            resolution,
+        };
+
+        post.push_effect(
+            device,
+            BLOOM,
+            include_str!("shaders/post/bloom.wgsl"),
+            [1.0, 0.6, 6.0, 0.0],
+        );
+        post.push_effect(
+            device,
+            TONEMAP,
+            include_str!("shaders/post/tonemap.wgsl"),
+            [1.0, 1.0, 0.0, 0.0],
+        );
+        post.push_effect(
+            device,
+            FXAA,
+            include_str!("shaders/post/fxaa.wgsl"),
+            [0.125, 0.125, 0.0, 0.0],
+        );
+        post.push_effect(
+            device,
+            VIGNETTE,
+            include_str!("shaders/post/vignette.wgsl"),
+            [0.5, 0.4, 0.0, 0.0],
+        );
+        post.push_effect(
+            device,
+            GAMMA,
+            include_str!("shaders/post/gamma.wgsl"),
+            [2.2, 0.0, 0.0, 0.0],
+        );
+        // The built-in effects are opt-in, the scene is drawn unchanged by
+        // default. The surface is sRGB, the gamma correction is only needed
+        // for linear surfaces.
+        for name in [BLOOM, TONEMAP, FXAA, VIGNETTE, GAMMA] {
+            post.set_enabled(name, false);
+        }
+
+        post
+    }
+
+    fn resolution(width: u32, height: u32) -> [f32; 4] {
+        [
+            width as f32,
+            height as f32,
+            1.0 / width as f32,
+            1.0 / height as f32,
+        ]
+    }
+
+    /// The view the scene is rendered into
+    pub fn hdr_view(&self) -> &wgpu::TextureView {
+        &self.targets[0].view
+    }
+
+    /// Append an effect at the end of the chain
+    ///
+    /// The source must define `fs_main(in: PostInput) -> @location(0)
+    /// vec4<f32>`, see `shaders/post/common.wgsl` for the available bindings.
+    /// The effect is not added if it does not compile.
+    pub fn add_effect(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        source: &str,
+        params: [f32; 4],
+    ) -> Result<(), crate::Error> {
+        // Catch the compilation errors instead of the uncaptured error
+        // handler
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        self.push_effect(device, name, source, params);
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            self.effects.pop();
+            return Err(crate::Error::Shader {
+                label: name.to_string(),
+                message: error.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Append an effect known to compile, the built-in ones
+    fn push_effect(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        source: &str,
+        params: [f32; 4],
+    ) {
+        let effect = PostEffect::new(
+            device,
+            &self.bind_group_layout,
+            &self.sampler,
+            &self.targets,
+            name,
+            source,
+            Self::HDR_FORMAT,
+            params,
+            self.resolution,
+        );
+        self.effects.push(effect);
+    }
+
+    pub fn effects(&self) -> &[PostEffect] {
+        &self.effects
+    }
+
+    fn effect_mut(&mut self, name: &str) -> Option<&mut PostEffect> {
+        self.effects.iter_mut().find(|effect| effect.name == name)
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        if let Some(effect) = self.effect_mut(name) {
+            effect.enabled = enabled;
+        }
+    }
+
+    pub fn params(&self, name: &str) -> Option<[f32; 4]> {
+        self.effects
+            .iter()
+            .find(|effect| effect.name == name)
+            .map(|effect| effect.params)
+    }
+
+    pub fn set_params(&mut self, name: &str, params: [f32; 4]) {
+        if let Some(effect) = self.effect_mut(name) {
+            effect.params = params;
+            effect.has_been_updated = true;
+        }
+    }
+
+    /// Reorder the chain and toggle the effects from a list of
+    /// `(name, enabled)`, unknown names are ignored and the effects missing
+    /// from the list are moved at the end.
+    pub fn configure(&mut self, order: &[(String, bool)]) {
+        for (name, enabled) in order {
+            self.set_enabled(name, *enabled);
+        }
+        self.effects.sort_by_key(|effect| {
+            order
+                .iter()
+                .position(|(name, _)| *name == effect.name)
+                .unwrap_or(usize::MAX)
+        });
+    }
+
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+    ) {
+        self.targets = [
+            HdrTexture::create(device, config.width, config.height),
+            HdrTexture::create(device, config.width, config.height),
+        ];
+        self.resolution = Self::resolution(config.width, config.height);
+        for effect in self.effects.iter_mut().chain(Some(&mut self.blit)) {
+            effect.bind_groups = PostEffect::create_bind_groups(
+                device,
+                &self.bind_group_layout,
+                &self.sampler,
+                &self.targets,
+                &effect.buffer,
+            );
+            effect.has_been_updated = true;
+        }
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        for effect in self.effects.iter_mut().chain(Some(&mut self.blit)) {
+            effect.update(queue, self.resolution);
+        }
+    }
+
+    /// Apply the enabled effects to the HDR texture and write the result into
+    /// the output view
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        output: &wgpu::TextureView,
+        profiler: &GpuProfiler,
+    ) {
+        // The chain is timed as a whole, from the first effect to the blit
+        let timestamp_writes = profiler.timestamp_writes(GpuPass::Post);
+        let mut input = 0;
+        let mut first = true;
+        for effect in self.effects.iter().filter(|effect| effect.enabled) {
+            effect.render(
+                encoder,
+                input,
+                &self.targets[1 - input].view,
+                GpuProfiler::split_timestamp_writes(
+                    timestamp_writes.clone(),
+                    first,
+                    false,
+                ),
+            );
+            input = 1 - input;
+            first = false;
+        }
+        self.blit.render(
+            encoder,
+            input,
+            output,
+            GpuProfiler::split_timestamp_writes(timestamp_writes, first, true),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::naga;
+
+    fn validate(source: &str) {
+        let module = naga::front::wgsl::parse_str(&format!(
+            "{}\n{}",
+            POST_COMMON, source
+        ))
+        .expect("Failed to parse the shader");
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .expect("Failed to validate the shader");
+    }
+
+    #[test]
+    fn test_builtin_effects_are_valid() {
+        validate(include_str!("shaders/post/blit.wgsl"));
+        validate(include_str!("shaders/post/tonemap.wgsl"));
+        validate(include_str!("shaders/post/gamma.wgsl"));
+        validate(include_str!("shaders/post/fxaa.wgsl"));
+        validate(include_str!("shaders/post/bloom.wgsl"));
+        validate(include_str!("shaders/post/vignette.wgsl"));
+    }
+}
@@ -0,0 +1,288 @@
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+/// Number of frames that can wait for their timestamps at the same time
+const READBACK_FRAMES: usize = 3;
+/// Offset between the timestamps of two passes in the resolve buffer
+const PASS_STRIDE: u64 = wgpu::QUERY_RESOLVE_BUFFER_ALIGNMENT;
+const TIMESTAMP_SIZE: u64 = std::mem::size_of::<u64>() as u64;
+
+/// Render passes timed by the profiler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuPass {
+    Shadow,
+    Main,
+    Post,
+    Text,
+    Egui,
+}
+
+/// A buffer the resolved timestamps are copied into, to be read on the CPU
+struct Readback {
+    buffer: wgpu::Buffer,
+    /// Passes written during the frame copied into the buffer
+    passes: u32,
+    /// Set by the `map_async` callback once the buffer can be read
+    mapped: Arc<AtomicBool>,
+    in_flight: bool,
+}
+
+/// Measures the GPU time of the render passes with timestamp queries
+///
+/// Each pass writes a timestamp at its beginning and at its end. The
+/// timestamps are resolved at the end of the frame and read back a few
+/// frames later, without waiting for the GPU. Nothing is measured when the
+/// device does not support `TIMESTAMP_QUERY`.
+pub struct GpuProfiler {
+    query_set: Option<wgpu::QuerySet>,
+    resolve_buffer: Option<wgpu::Buffer>,
+    readbacks: Vec<Readback>,
+    /// Readback used by the frame being recorded
+    current: Option<usize>,
+    /// Passes which wrote their timestamps during the frame being recorded
+    written: Cell<u32>,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+    /// Last measured duration of each pass in milliseconds
+    timings: [Option<f32>; GpuPass::ALL.len()],
+}
+
+impl GpuPass {
+    pub const ALL: [GpuPass; 5] = [
+        GpuPass::Shadow,
+        GpuPass::Main,
+        GpuPass::Post,
+        GpuPass::Text,
+        GpuPass::Egui,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GpuPass::Shadow => "Shadow",
+            GpuPass::Main => "Main",
+            GpuPass::Post => "Post",
+            GpuPass::Text => "Text",
+            GpuPass::Egui => "EGUI",
+        }
+    }
+
+    fn index(&self) -> u32 {
+        *self as u32
+    }
+}
+
+/// Duration in milliseconds between two timestamps
+fn elapsed_ms(begin: u64, end: u64, period: f32) -> f32 {
+    end.saturating_sub(begin) as f32 * period / 1_000_000.0
+}
+
+impl GpuProfiler {
+    pub fn init(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let passes = GpuPass::ALL.len() as u64;
+        let supported =
+            device.features().contains(wgpu::Features::TIMESTAMP_QUERY);
+        if !supported {
+            log::info!("Timestamp queries are not supported, no GPU timings");
+        }
+        let query_set = supported.then(|| {
+            device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Timestamp Query Set"),
+                ty: wgpu::QueryType::Timestamp,
+                count: 2 * passes as u32,
+            })
+        });
+        let resolve_buffer = supported.then(|| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp Resolve Buffer"),
+                size: passes * PASS_STRIDE,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE
+                    | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            })
+        });
+        let readbacks = (0..READBACK_FRAMES)
+            .filter(|_| supported)
+            .map(|_| Readback {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timestamp Readback Buffer"),
+                    size: passes * PASS_STRIDE,
+                    usage: wgpu::BufferUsages::MAP_READ
+                        | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                passes: 0,
+                mapped: Arc::new(AtomicBool::new(false)),
+                in_flight: false,
+            })
+            .collect();
+
+        Self {
+            query_set,
+            resolve_buffer,
+            readbacks,
+            current: None,
+            written: Cell::new(0),
+            period: queue.get_timestamp_period(),
+            timings: [None; GpuPass::ALL.len()],
+        }
+    }
+
+    pub fn supported(&self) -> bool {
+        self.query_set.is_some()
+    }
+
+    /// Last measured duration of a pass in milliseconds, `None` if the pass
+    /// did not run or timestamps are not supported
+    pub fn timing(&self, pass: GpuPass) -> Option<f32> {
+        self.timings[pass.index() as usize]
+    }
+
+    /// Start recording a frame, the passes are only timed if a readback
+    /// buffer is free
+    pub fn begin_frame(&mut self) {
+        self.written.set(0);
+        self.current = self
+            .readbacks
+            .iter()
+            .position(|readback| !readback.in_flight);
+    }
+
+    /// Timestamp writes to give to the descriptor of a render pass
+    pub fn timestamp_writes(
+        &self,
+        pass: GpuPass,
+    ) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.current?;
+        let query_set = self.query_set.as_ref()?;
+        self.written.set(self.written.get() | 1 << pass.index());
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set,
+            beginning_of_pass_write_index: Some(2 * pass.index()),
+            end_of_pass_write_index: Some(2 * pass.index() + 1),
+        })
+    }
+
+    /// Split the timestamp writes of a pass over a chain of passes, the
+    /// first one writes the beginning and the last one the end
+    pub fn split_timestamp_writes(
+        writes: Option<wgpu::RenderPassTimestampWrites<'_>>,
+        first: bool,
+        last: bool,
+    ) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        let writes = writes?;
+        (first || last).then_some(wgpu::RenderPassTimestampWrites {
+            query_set: writes.query_set,
+            beginning_of_pass_write_index: writes
+                .beginning_of_pass_write_index
+                .filter(|_| first),
+            end_of_pass_write_index: writes
+                .end_of_pass_write_index
+                .filter(|_| last),
+        })
+    }
+
+    /// Resolve the timestamps written during the frame
+    ///
+    /// Must be called once every pass has been recorded.
+    pub fn resolve(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        let (Some(current), Some(query_set), Some(resolve_buffer)) =
+            (self.current, &self.query_set, &self.resolve_buffer)
+        else {
+            return;
+        };
+        let written = self.written.get();
+        // Only the written queries are resolved, the others hold no value
+        for pass in GpuPass::ALL {
+            if written & 1 << pass.index() != 0 {
+                let first = 2 * pass.index();
+                encoder.resolve_query_set(
+                    query_set,
+                    first..first + 2,
+                    resolve_buffer,
+                    pass.index() as u64 * PASS_STRIDE,
+                );
+            }
+        }
+        let readback = &mut self.readbacks[current];
+        encoder.copy_buffer_to_buffer(
+            resolve_buffer,
+            0,
+            &readback.buffer,
+            0,
+            resolve_buffer.size(),
+        );
+        readback.passes = written;
+    }
+
+    /// Ask for the timestamps of the frame, once it has been submitted
+    pub fn map(&mut self) {
+        let Some(current) = self.current.take() else {
+            return;
+        };
+        let readback = &mut self.readbacks[current];
+        readback.in_flight = true;
+        readback.mapped.store(false, Ordering::Release);
+        let mapped = readback.mapped.clone();
+        readback.buffer.slice(..).map_async(
+            wgpu::MapMode::Read,
+            move |result| {
+                if result.is_ok() {
+                    mapped.store(true, Ordering::Release);
+                }
+            },
+        );
+    }
+
+    /// Read the timestamps of the frames finished by the GPU
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        if self.readbacks.iter().all(|readback| !readback.in_flight) {
+            return;
+        }
+        device.poll(wgpu::Maintain::Poll);
+        for readback in self.readbacks.iter_mut() {
+            if !readback.in_flight || !readback.mapped.load(Ordering::Acquire) {
+                continue;
+            }
+            {
+                let data = readback.buffer.slice(..).get_mapped_range();
+                for pass in GpuPass::ALL {
+                    let index = pass.index() as usize;
+                    self.timings[index] = (readback.passes & 1 << index != 0)
+                        .then(|| {
+                            let offset = index * PASS_STRIDE as usize;
+                            let timestamps: &[u64] = bytemuck::cast_slice(
+                                &data[offset
+                                    ..offset + 2 * TIMESTAMP_SIZE as usize],
+                            );
+                            elapsed_ms(
+                                timestamps[0],
+                                timestamps[1],
+                                self.period,
+                            )
+                        });
+                }
+            }
+            readback.buffer.unmap();
+            readback.in_flight = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elapsed_ms() {
+        assert_eq!(elapsed_ms(1_000, 3_000_000, 1.0), 2.999);
+        // A timestamp period in nanoseconds per tick
+        assert_eq!(elapsed_ms(0, 500_000, 2.0), 1.0);
+        // Out of order timestamps do not underflow
+        assert_eq!(elapsed_ms(10, 5, 1.0), 0.0);
+    }
+}
@@ -0,0 +1,104 @@
+// This file contains the shader drawing the background behind the geometry.
+// A single triangle covering the screen is drawn at the maximum depth, so only the pixels
+// not covered by the scene are filled.
+
+// CameraUniform is a struct that contains the view projection matrix and the parameters of the view.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+    // World direction of the right and up axes of the camera
+    right: vec4<f32>,
+    up: vec4<f32>,
+    // xy: size of the target in pixels
+    viewport: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// BackgroundUniform is a struct that contains the parameters of the background.
+struct BackgroundUniform {
+    top: vec4<f32>,
+    bottom: vec4<f32>,
+    // xyz: direction toward the sun, w: angular size of the sun (cosine)
+    sun: vec4<f32>,
+    // x: mode (1.0: gradient, 2.0: sky, 3.0: cubemap, 4.0: equirectangular)
+    params: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> background: BackgroundUniform;
+@group(1) @binding(1)
+var cube_texture: texture_cube<f32>;
+@group(1) @binding(2)
+var equirectangular_texture: texture_2d<f32>;
+@group(1) @binding(3)
+var background_sampler: sampler;
+
+const PI: f32 = 3.14159265359;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+// Vertex shader
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    out.clip_position = vec4<f32>(out.ndc, 1.0, 1.0);
+    return out;
+}
+
+// Direction of the view ray going through the given point of the screen
+// Both points are unprojected close to the near plane, so they stay in front of the camera
+// whatever the depth range of the projection. The camera position cancels out in the difference.
+fn view_direction(ndc: vec2<f32>) -> vec3<f32> {
+    let near = camera.inv_view_proj * vec4<f32>(ndc, 0.1, 1.0);
+    let far = camera.inv_view_proj * vec4<f32>(ndc, 0.2, 1.0);
+    return normalize(far.xyz / far.w - near.xyz / near.w);
+}
+
+fn sky(direction: vec3<f32>) -> vec3<f32> {
+    let height = direction.y;
+    let horizon = background.bottom.rgb;
+    let zenith = background.top.rgb;
+    var color = mix(horizon, zenith, pow(clamp(height, 0.0, 1.0), 0.5));
+    // Darker ground below the horizon
+    if height < 0.0 {
+        color = mix(horizon, horizon * 0.3, clamp(-height * 4.0, 0.0, 1.0));
+    }
+    // Sun disk and halo
+    let sun = dot(direction, normalize(background.sun.xyz));
+    if sun > background.sun.w {
+        color = vec3<f32>(10.0, 9.0, 8.0);
+    } else {
+        color += vec3<f32>(1.0, 0.8, 0.6) * pow(max(sun, 0.0), 64.0) * 0.5;
+    }
+    return color;
+}
+
+fn equirectangular(direction: vec3<f32>) -> vec3<f32> {
+    let uv = vec2<f32>(
+        atan2(direction.z, direction.x) / (2.0 * PI) + 0.5,
+        acos(clamp(direction.y, -1.0, 1.0)) / PI,
+    );
+    return textureSampleLevel(equirectangular_texture, background_sampler, uv, 0.0).rgb;
+}
+
+// Fragment shader
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = view_direction(in.ndc);
+    let mode = background.params.x;
+    if mode < 1.5 {
+        let t = direction.y * 0.5 + 0.5;
+        return vec4<f32>(mix(background.bottom.rgb, background.top.rgb, t), 1.0);
+    } else if mode < 2.5 {
+        return vec4<f32>(sky(direction), 1.0);
+    } else if mode < 3.5 {
+        return vec4<f32>(textureSampleLevel(cube_texture, background_sampler, direction, 0.0).rgb, 1.0);
+    }
+    return vec4<f32>(equirectangular(direction), 1.0);
+}
@@ -0,0 +1,95 @@
+// This file contains the shader drawing the infinite ground grid on the XZ plane.
+// A single triangle covering the screen is drawn, each pixel intersects its view ray with the
+// ground and writes the depth of the intersection so the grid is hidden by the geometry.
+
+// CameraUniform is a struct that contains the view projection matrix and the parameters of the view.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+    // World direction of the right and up axes of the camera
+    right: vec4<f32>,
+    up: vec4<f32>,
+    // xy: size of the target in pixels
+    viewport: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// GridUniform is a struct that contains the grid parameters.
+struct GridUniform {
+    // x: size of a cell, y: distance at which the grid disappears
+    params: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> grid: GridUniform;
+
+const GRID_COLOR: vec3<f32> = vec3<f32>(0.5, 0.5, 0.5);
+const MAJOR_LINES: f32 = 10.0;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+// Vertex shader
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    out.clip_position = vec4<f32>(out.ndc, 0.5, 1.0);
+    return out;
+}
+
+fn unproject(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    let point = camera.inv_view_proj * vec4<f32>(ndc, depth, 1.0);
+    return point.xyz / point.w;
+}
+
+// Coverage of the lines every `size` units, antialiased with the screen derivatives
+fn lines(coord: vec2<f32>, size: f32) -> f32 {
+    let scaled = coord / size;
+    let derivative = max(fwidth(scaled), vec2<f32>(1e-6));
+    let distance = abs(fract(scaled - 0.5) - 0.5) / derivative;
+    return 1.0 - min(min(distance.x, distance.y), 1.0);
+}
+
+// Fragment shader
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    // Both points are close to the near plane, in front of the camera
+    let origin = unproject(in.ndc, 0.1);
+    let direction = unproject(in.ndc, 0.2) - origin;
+    var t = -1.0;
+    if abs(direction.y) > 1e-6 {
+        t = -origin.y / direction.y;
+    }
+    let position = origin + direction * max(t, 0.0);
+
+    let clip = camera.view_proj * vec4<f32>(position, 1.0);
+    let depth = clip.z / clip.w;
+
+    let coverage = max(
+        lines(position.xz, grid.params.x) * 0.5,
+        lines(position.xz, grid.params.x * MAJOR_LINES),
+    );
+    let distance = length(position.xz - camera.position.xz);
+    let fade = 1.0 - smoothstep(grid.params.y * 0.5, grid.params.y, distance);
+    let alpha = coverage * fade;
+
+    // Discard after the derivatives have been computed
+    if t < 0.0 || clip.w <= 0.0 || depth < 0.0 || depth > 1.0 || alpha <= 0.0 {
+        discard;
+    }
+
+    var out: FragmentOutput;
+    out.color = vec4<f32>(GRID_COLOR, alpha);
+    out.depth = depth;
+    return out;
+}
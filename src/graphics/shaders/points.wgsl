@@ -0,0 +1,77 @@
+// This file contains the shader drawing point clouds as camera facing sprites.
+// Each point is an instance expanded into a quad, sized either in pixels or in world units.
+
+// CameraUniform is a struct that contains the view projection matrix and the parameters of the view.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+    // World direction of the right and up axes of the camera
+    right: vec4<f32>,
+    up: vec4<f32>,
+    // xy: size of the target in pixels
+    viewport: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// CloudUniform is a struct that contains the parameters of a point cloud.
+struct CloudUniform {
+    // x: 1.0 if the size is in world units (pixels otherwise), y: 1.0 for circles (squares otherwise)
+    params: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> cloud: CloudUniform;
+
+struct PointInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) size: f32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    // Position in the quad, from -1 to 1
+    @location(1) uv: vec2<f32>,
+};
+
+// Vertex shader
+// The six vertices of the quad are generated from the vertex index.
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    point: PointInput,
+) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[index];
+
+    var out: VertexOutput;
+    out.color = point.color;
+    out.uv = corner;
+    if cloud.params.x > 0.5 {
+        let offset = (camera.right.xyz * corner.x + camera.up.xyz * corner.y) * point.size * 0.5;
+        out.clip_position = camera.view_proj * vec4<f32>(point.position + offset, 1.0);
+    } else {
+        let center = camera.view_proj * vec4<f32>(point.position, 1.0);
+        let offset = corner * point.size / camera.viewport.xy;
+        out.clip_position = vec4<f32>(center.xy + offset * center.w, center.zw);
+    }
+    return out;
+}
+
+// Fragment shader
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    if cloud.params.y > 0.5 && dot(in.uv, in.uv) > 1.0 {
+        discard;
+    }
+    return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,103 @@
+// This file contains the shader drawing thick line segments.
+// Each segment is an instance expanded into a quad in screen space, so the thickness is given in
+// pixels whatever the distance to the camera.
+
+// CameraUniform is a struct that contains the view projection matrix and the parameters of the view.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    inv_view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+    // World direction of the right and up axes of the camera
+    right: vec4<f32>,
+    up: vec4<f32>,
+    // xy: size of the target in pixels
+    viewport: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct SegmentInput {
+    // xyz: start of the segment, w: thickness in pixels
+    @location(0) start: vec4<f32>,
+    @location(1) end: vec4<f32>,
+    @location(2) start_color: vec4<f32>,
+    @location(3) end_color: vec4<f32>,
+    // x: length of a dash in pixels (0.0 for a solid line), y: length of a gap in pixels
+    @location(4) style: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // Distance in pixels from the start of the segment
+    @location(1) distance: f32,
+    @location(2) style: vec2<f32>,
+};
+
+// Vertex shader
+// The six vertices of the quad are generated from the vertex index.
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    segment: SegmentInput,
+) -> VertexOutput {
+    var a = camera.view_proj * vec4<f32>(segment.start.xyz, 1.0);
+    var b = camera.view_proj * vec4<f32>(segment.end.xyz, 1.0);
+    var color_a = segment.start_color;
+    var color_b = segment.end_color;
+
+    var out: VertexOutput;
+    // Fully behind the near plane, the quad is clipped
+    if a.z < 0.0 && b.z < 0.0 {
+        out.clip_position = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+        return out;
+    }
+    // Clip the segment to the near plane before the perspective division
+    if a.z < 0.0 {
+        let t = a.z / (a.z - b.z);
+        a = mix(a, b, t);
+        color_a = mix(color_a, color_b, t);
+    } else if b.z < 0.0 {
+        let t = b.z / (b.z - a.z);
+        b = mix(b, a, t);
+        color_b = mix(color_b, color_a, t);
+    }
+
+    let half_viewport = camera.viewport.xy * 0.5;
+    let screen_a = a.xy / a.w * half_viewport;
+    let screen_b = b.xy / b.w * half_viewport;
+    let length = distance(screen_a, screen_b);
+    var direction = vec2<f32>(1.0, 0.0);
+    if length > 1e-6 {
+        direction = (screen_b - screen_a) / length;
+    }
+    let normal = vec2<f32>(-direction.y, direction.x);
+
+    // Corners of the two triangles: (0, 1, 2) and (2, 1, 3)
+    var corners = array<u32, 6>(0u, 1u, 2u, 2u, 1u, 3u);
+    let corner = corners[index];
+    let along = f32(corner & 1u);
+    let side = f32(corner >> 1u) * 2.0 - 1.0;
+
+    var position = a;
+    if (corner & 1u) == 1u {
+        position = b;
+    }
+    let offset = normal * side * segment.start.w * 0.5 / half_viewport;
+    out.clip_position = vec4<f32>(position.xy + offset * position.w, position.zw);
+    out.color = mix(color_a, color_b, along);
+    out.distance = along * length;
+    out.style = segment.style.xy;
+    return out;
+}
+
+// Fragment shader
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dash = in.style.x;
+    let gap = in.style.y;
+    if dash > 0.0 && in.distance % (dash + gap) > dash {
+        discard;
+    }
+    return in.color;
+}
@@ -0,0 +1,6 @@
+// Copies the input into the target, used to write the result of the chain into the surface.
+
+@fragment
+fn fs_main(in: PostInput) -> @location(0) vec4<f32> {
+    return textureSample(input_texture, input_sampler, in.uv);
+}
@@ -0,0 +1,24 @@
+// Adds a glow around the bright parts of the image.
+// params.x: brightness threshold, params.y: intensity, params.z: radius in pixels
+
+const BLOOM_SAMPLES: i32 = 4;
+
+@fragment
+fn fs_main(in: PostInput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(input_texture, input_sampler, in.uv, 0.0);
+    let step = post.resolution.zw * post.params.z / f32(BLOOM_SAMPLES);
+
+    var glow = vec3<f32>(0.0);
+    var total = 0.0;
+    for (var x = -BLOOM_SAMPLES; x <= BLOOM_SAMPLES; x++) {
+        for (var y = -BLOOM_SAMPLES; y <= BLOOM_SAMPLES; y++) {
+            let offset = vec2<f32>(f32(x), f32(y));
+            let weight = exp(-dot(offset, offset) / f32(BLOOM_SAMPLES * BLOOM_SAMPLES));
+            let sample = textureSampleLevel(input_texture, input_sampler, in.uv + offset * step, 0.0).rgb;
+            // Only the part above the threshold contributes to the glow
+            glow += max(sample - vec3<f32>(post.params.x), vec3<f32>(0.0)) * weight;
+            total += weight;
+        }
+    }
+    return vec4<f32>(color.rgb + glow / total * post.params.y, color.a);
+}
@@ -0,0 +1,38 @@
+// This file is prepended to every post-processing effect.
+// It declares the bindings shared by the effects and the fullscreen vertex shader,
+// an effect only has to provide the `fs_main` fragment shader.
+
+// PostUniform is a struct that contains the size of the target and the parameters of the effect.
+struct PostUniform {
+    // xy: size of the target in pixels, zw: size of a pixel in uv coordinates
+    resolution: vec4<f32>,
+    // Free parameters, their meaning depends on the effect
+    params: vec4<f32>,
+};
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var input_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> post: PostUniform;
+
+// PostInput is a struct that contains the clip position and the uv coordinates of a fragment.
+struct PostInput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Vertex shader
+// Draws a single triangle covering the whole target.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> PostInput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: PostInput;
+    out.uv = uv;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
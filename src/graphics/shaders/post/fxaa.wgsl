@@ -0,0 +1,44 @@
+// Fast approximate anti-aliasing, simplified version of FXAA 3.11 (Timothy Lottes).
+// params.x: edge threshold, params.y: subpixel blending
+
+const FXAA_SPAN_MAX: f32 = 8.0;
+const FXAA_REDUCE_MIN: f32 = 0.0078125;
+
+@fragment
+fn fs_main(in: PostInput) -> @location(0) vec4<f32> {
+    let texel = post.resolution.zw;
+    let center = textureSampleLevel(input_texture, input_sampler, in.uv, 0.0);
+    let luma_nw = luminance(textureSampleLevel(input_texture, input_sampler, in.uv + vec2<f32>(-1.0, -1.0) * texel, 0.0).rgb);
+    let luma_ne = luminance(textureSampleLevel(input_texture, input_sampler, in.uv + vec2<f32>(1.0, -1.0) * texel, 0.0).rgb);
+    let luma_sw = luminance(textureSampleLevel(input_texture, input_sampler, in.uv + vec2<f32>(-1.0, 1.0) * texel, 0.0).rgb);
+    let luma_se = luminance(textureSampleLevel(input_texture, input_sampler, in.uv + vec2<f32>(1.0, 1.0) * texel, 0.0).rgb);
+    let luma_m = luminance(center.rgb);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+    if luma_max - luma_min < max(FXAA_REDUCE_MIN, luma_max * post.params.x) {
+        return center;
+    }
+
+    var direction = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        ((luma_nw + luma_sw) - (luma_ne + luma_se)),
+    );
+    let direction_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * post.params.y, FXAA_REDUCE_MIN);
+    let inverse_min = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+    direction = clamp(direction * inverse_min, vec2<f32>(-FXAA_SPAN_MAX), vec2<f32>(FXAA_SPAN_MAX)) * texel;
+
+    let color_a = 0.5 * (
+        textureSampleLevel(input_texture, input_sampler, in.uv + direction * (1.0 / 3.0 - 0.5), 0.0).rgb
+        + textureSampleLevel(input_texture, input_sampler, in.uv + direction * (2.0 / 3.0 - 0.5), 0.0).rgb
+    );
+    let color_b = color_a * 0.5 + 0.25 * (
+        textureSampleLevel(input_texture, input_sampler, in.uv + direction * -0.5, 0.0).rgb
+        + textureSampleLevel(input_texture, input_sampler, in.uv + direction * 0.5, 0.0).rgb
+    );
+    let luma_b = luminance(color_b);
+    if luma_b < luma_min || luma_b > luma_max {
+        return vec4<f32>(color_a, center.a);
+    }
+    return vec4<f32>(color_b, center.a);
+}
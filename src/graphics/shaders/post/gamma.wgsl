@@ -0,0 +1,9 @@
+// Applies a gamma correction.
+// params.x: gamma
+// The surface is already sRGB, this is only needed for non-sRGB outputs or artistic tweaks.
+
+@fragment
+fn fs_main(in: PostInput) -> @location(0) vec4<f32> {
+    let color = textureSample(input_texture, input_sampler, in.uv);
+    return vec4<f32>(pow(max(color.rgb, vec3<f32>(0.0)), vec3<f32>(1.0 / post.params.x)), color.a);
+}
@@ -0,0 +1,26 @@
+// Maps the HDR colors into the [0, 1] range.
+// params.x: operator (0.0: Reinhard, 1.0: ACES), params.y: exposure
+
+fn reinhard(color: vec3<f32>) -> vec3<f32> {
+    return color / (color + vec3<f32>(1.0));
+}
+
+// Narkowicz 2015, "ACES Filmic Tone Mapping Curve"
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((color * (a * color + b)) / (color * (c * color + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+@fragment
+fn fs_main(in: PostInput) -> @location(0) vec4<f32> {
+    let color = textureSample(input_texture, input_sampler, in.uv);
+    let exposed = color.rgb * post.params.y;
+    if post.params.x > 0.5 {
+        return vec4<f32>(aces(exposed), color.a);
+    }
+    return vec4<f32>(reinhard(exposed), color.a);
+}
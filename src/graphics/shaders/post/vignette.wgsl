@@ -0,0 +1,10 @@
+// Darkens the corners of the image.
+// params.x: strength, params.y: radius (distance to the center where the darkening starts)
+
+@fragment
+fn fs_main(in: PostInput) -> @location(0) vec4<f32> {
+    let color = textureSample(input_texture, input_sampler, in.uv);
+    let distance = length(in.uv - vec2<f32>(0.5));
+    let vignette = 1.0 - smoothstep(post.params.y, post.params.y + 0.5, distance) * post.params.x;
+    return vec4<f32>(color.rgb * vignette, color.a);
+}
@@ -7,6 +7,23 @@ struct CameraUniform {
 @group(0) @binding(0) // 1.
 var<uniform> camera: CameraUniform;
 
+// LightUniform is a struct that contains the view-projection matrix of the directional light.
+struct LightUniform {
+    view_proj: mat4x4<f32>,
+    direction: vec4<f32>,
+    // x: bias, y: texel size, z: enabled (0.0 or 1.0), w: unused
+    params: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> light: LightUniform;
+@group(1) @binding(1)
+var shadow_map: texture_depth_2d;
+@group(1) @binding(2)
+var shadow_sampler: sampler_comparison;
+
+// Color factor applied to the fragments in the shadow
+const SHADOW_AMBIENT: f32 = 0.35;
+
 // VertexInput is a struct that contains the position and color of a vertex.
 // The @location attribute specifies the location of the input data.
 struct VertexInput {
@@ -27,6 +44,7 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec3<f32>,
     @location(1) position: vec3<f32>,
+    @location(2) world_position: vec4<f32>,
 };
 
 const SHAPE_MIN_SIZE: vec3<f32> = vec3<f32>(-10.0, -10.0, -10.0);
@@ -59,6 +77,30 @@ fn check_on_side(position: vec3<f32>, side: i32) -> bool {
     );
 }
 
+// Returns the amount of light (between 0.0 and 1.0) reaching the given world position.
+// The shadow map is sampled 3x3 times around the position (PCF) to smooth the edges.
+fn shadow_factor(world_position: vec4<f32>) -> f32 {
+    if light.params.z == 0.0 {
+        return 1.0;
+    }
+    let light_position = light.view_proj * world_position;
+    let ndc = light_position.xyz / light_position.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+    // Outside of the shadow map, the fragment is considered lit
+    if uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0 || ndc.z > 1.0 {
+        return 1.0;
+    }
+    let depth = ndc.z - light.params.x;
+    var visibility = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * light.params.y;
+            visibility += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, depth);
+        }
+    }
+    return visibility / 9.0;
+}
+
 // Vertex shader
 // This shader is used to transform the vertices of the triangle from model space to clip space.
 @vertex
@@ -83,7 +125,8 @@ fn vs_main(
     out.color = model.color;
     out.position = model.position;
 
-    out.clip_position = camera.view_proj * model_matrix * vec4<f32>(model.position, 1.0);
+    out.world_position = model_matrix * vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_proj * out.world_position;
     return out;
 }
 
@@ -98,5 +141,6 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     ) {
         return vec4<f32>(0.0, 0.0, 0.0, 1.0);
     }
-    return vec4<f32>(in.color, 1.0);
+    let shadow = mix(SHADOW_AMBIENT, 1.0, shadow_factor(in.world_position));
+    return vec4<f32>(in.color * shadow, 1.0);
 }
@@ -0,0 +1,31 @@
+// This file contains the shader copying the shadow map into a color texture displayed in the debug window.
+
+@group(0) @binding(0)
+var shadow_map: texture_2d<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Vertex shader
+// Draws a single triangle covering the whole target.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.uv = uv;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+// Fragment shader
+// The depth is read without filtering and displayed as a gray level.
+// The map is bound as a float texture, loading from depth textures is not supported by GLSL.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(shadow_map));
+    let coords = vec2<i32>(min(in.uv * size, size - 1.0));
+    let depth = textureLoad(shadow_map, coords, 0).r;
+    return vec4<f32>(depth, depth, depth, 1.0);
+}
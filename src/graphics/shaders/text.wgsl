@@ -0,0 +1,81 @@
+// This file contains the shader drawing the glyphs of the texts.
+// Each glyph is an instance expanded into a quad in screen space, anchored either to a point of
+// the world or to a pixel of the window.
+
+// CameraUniform is a struct that contains the view projection matrix.
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// TextUniform is a struct that contains the size of the target in pixels.
+struct TextUniform {
+    viewport: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> text: TextUniform;
+@group(1) @binding(1)
+var atlas_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var atlas_sampler: sampler;
+
+struct GlyphInput {
+    // xyz: world position (w = 1.0) or pixel position (w = 0.0) of the text
+    @location(0) anchor: vec4<f32>,
+    // xy: top left corner relative to the anchor, zw: size, in pixels
+    @location(1) rect: vec4<f32>,
+    // xy: top left corner, zw: bottom right corner in the atlas
+    @location(2) uv: vec4<f32>,
+    @location(3) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+// Vertex shader
+// The six vertices of the quad are generated from the vertex index.
+@vertex
+fn vs_main(
+    @builtin(vertex_index) index: u32,
+    glyph: GlyphInput,
+) -> VertexOutput {
+    let viewport = text.viewport.xy;
+
+    var out: VertexOutput;
+    var anchor = glyph.anchor.xy;
+    if glyph.anchor.w > 0.5 {
+        let clip = camera.view_proj * vec4<f32>(glyph.anchor.xyz, 1.0);
+        // Behind the camera, the quad is clipped
+        if clip.z < 0.0 {
+            out.clip_position = vec4<f32>(0.0, 0.0, -1.0, 1.0);
+            return out;
+        }
+        let ndc = clip.xy / clip.w;
+        anchor = vec2<f32>(ndc.x + 1.0, 1.0 - ndc.y) * 0.5 * viewport;
+    }
+    // Snapped to the pixel grid to keep the glyphs sharp
+    anchor = round(anchor);
+
+    // Corners of the two triangles: (0, 1, 2) and (2, 1, 3)
+    var corners = array<u32, 6>(0u, 1u, 2u, 2u, 1u, 3u);
+    let corner_index = corners[index];
+    let corner = vec2<f32>(f32(corner_index & 1u), f32(corner_index >> 1u));
+
+    let pixel = anchor + glyph.rect.xy + corner * glyph.rect.zw;
+    let position = pixel / viewport * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(position.x, -position.y, 0.0, 1.0);
+    out.uv = mix(glyph.uv.xy, glyph.uv.zw, corner);
+    out.color = glyph.color;
+    return out;
+}
+
+// Fragment shader
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas_texture, atlas_sampler, in.uv).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -0,0 +1,575 @@
+use cgmath::InnerSpace;
+use wgpu::util::DeviceExt;
+
+use super::{
+    pipeline::DepthTexture,
+    profiler::{GpuPass, GpuProfiler},
+    types::{Buffer, InstanceRaw, Vertex},
+};
+
+pub(crate) const DEFAULT_SHADOW_RESOLUTION: u32 = 2048;
+pub(crate) const DEFAULT_SHADOW_BIAS: f32 = 0.005;
+const SHADOW_PREVIEW_SIZE: u32 = 256;
+
+/// Maps the OpenGL depth range [-1, 1] to [0, 1]
+#[rustfmt::skip]
+pub(crate) const OPENGL_TO_WGPU_DEPTH: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, 0.5, 1.0,
+);
+
+/// A light with parallel rays, such as the sun
+///
+/// The shadow map covers a box of `2 * extent` around `target`, seen from
+/// `distance` units away along the light direction.
+#[derive(Debug, Clone)]
+pub struct DirectionalLight {
+    pub direction: cgmath::Vector3<f32>,
+    pub target: cgmath::Point3<f32>,
+    pub extent: f32,
+    pub distance: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightUniform {
+    view_proj: [[f32; 4]; 4],
+    direction: [f32; 4],
+    /// x: bias, y: texel size, z: enabled (0.0 or 1.0), w: unused
+    params: [f32; 4],
+}
+
+/// Depth texture rendered from the light point of view
+pub struct ShadowMap {
+    light: DirectionalLight,
+    enabled: bool,
+    bias: f32,
+    resolution: u32,
+    depth_texture: DepthTexture,
+    sampler: wgpu::Sampler,
+    buffer: wgpu::Buffer,
+    // Bind group used by the main pipeline (light, shadow map and sampler)
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    // Bind group used by the depth pass (light only)
+    light_bind_group: wgpu::BindGroup,
+    depth_pipeline: wgpu::RenderPipeline,
+    preview: ShadowPreview,
+    /// The preview is only rendered while it is shown
+    preview_visible: bool,
+    has_been_updated: bool,
+}
+
+/// Gray-scale copy of the shadow map that can be displayed by egui
+struct ShadowPreview {
+    view: wgpu::TextureView,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            direction: cgmath::Vector3::new(-0.4, -1.0, -0.3),
+            target: cgmath::Point3::new(0.0, 0.0, 0.0),
+            extent: 30.0,
+            distance: 50.0,
+        }
+    }
+}
+
+impl DirectionalLight {
+    fn calc_matrix(&self) -> cgmath::Matrix4<f32> {
+        let direction = self.direction.normalize();
+        let position = self.target - direction * self.distance;
+        // Avoid a degenerate view matrix when the light is vertical
+        let up = if direction.y.abs() > 0.99 {
+            cgmath::Vector3::unit_z()
+        } else {
+            cgmath::Vector3::unit_y()
+        };
+        let view = cgmath::Matrix4::look_at_rh(position, self.target, up);
+        let projection = cgmath::ortho(
+            -self.extent,
+            self.extent,
+            -self.extent,
+            self.extent,
+            0.1,
+            self.distance * 2.0,
+        );
+        OPENGL_TO_WGPU_DEPTH * projection * view
+    }
+}
+
+impl ShadowMap {
+    pub fn init(
+        device: &wgpu::Device,
+        resolution: u32,
+        bias: f32,
+        enabled: bool,
+    ) -> Self {
+        let light = DirectionalLight::default();
+        let depth_texture =
+            DepthTexture::create(device, resolution, resolution, "Shadow Map");
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let uniform = Self::uniform(&light, bias, resolution, enabled);
+        let buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Light Buffer"),
+                contents: bytemuck::cast_slice(&[uniform]),
+                usage: wgpu::BufferUsages::UNIFORM
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Comparison,
+                        ),
+                        count: None,
+                    },
+                ],
+                label: Some("shadow_bind_group_layout"),
+            });
+        let bind_group = Self::create_bind_group(
+            device,
+            &bind_group_layout,
+            &buffer,
+            &depth_texture,
+            &sampler,
+        );
+
+        let light_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+                label: Some("light_bind_group_layout"),
+            });
+        let light_bind_group =
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &light_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+                label: Some("light_bind_group"),
+            });
+
+        let shader =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Shadow Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("shaders/shadow.wgsl").into(),
+                ),
+            });
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: &[&light_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let depth_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Shadow Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                },
+                fragment: None,
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DepthTexture::DEPTH_FORMAT,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: Default::default(),
+                    // Slope scaled bias, the constant part is in the shader
+                    bias: wgpu::DepthBiasState {
+                        constant: 0,
+                        slope_scale: 2.0,
+                        clamp: 0.0,
+                    },
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        let preview = ShadowPreview::init(device, &depth_texture);
+
+        Self {
+            light,
+            enabled,
+            bias,
+            resolution,
+            depth_texture,
+            sampler,
+            buffer,
+            bind_group_layout,
+            bind_group,
+            light_bind_group,
+            depth_pipeline,
+            preview,
+            preview_visible: false,
+            has_been_updated: false,
+        }
+    }
+
+    fn uniform(
+        light: &DirectionalLight,
+        bias: f32,
+        resolution: u32,
+        enabled: bool,
+    ) -> LightUniform {
+        LightUniform {
+            view_proj: light.calc_matrix().into(),
+            direction: light.direction.normalize().extend(0.0).into(),
+            params: [
+                bias,
+                1.0 / resolution as f32,
+                if enabled { 1.0 } else { 0.0 },
+                0.0,
+            ],
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        depth_texture: &DepthTexture,
+        sampler: &wgpu::Sampler,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &depth_texture.view,
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some("shadow_bind_group"),
+        })
+    }
+
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Color texture showing the content of the shadow map
+    pub fn preview_view(&self) -> &wgpu::TextureView {
+        &self.preview.view
+    }
+
+    pub fn set_preview_visible(&mut self, visible: bool) {
+        self.preview_visible = visible;
+    }
+
+    pub fn light(&self) -> &DirectionalLight {
+        &self.light
+    }
+
+    pub fn set_light(&mut self, light: DirectionalLight) {
+        self.light = light;
+        self.has_been_updated = true;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.has_been_updated = true;
+    }
+
+    pub fn bias(&self) -> f32 {
+        self.bias
+    }
+
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias;
+        self.has_been_updated = true;
+    }
+
+    pub fn resolution(&self) -> u32 {
+        self.resolution
+    }
+
+    /// Recreate the shadow map with the given size (in texels)
+    pub fn set_resolution(&mut self, device: &wgpu::Device, resolution: u32) {
+        let resolution =
+            resolution.clamp(1, device.limits().max_texture_dimension_2d);
+        if resolution == self.resolution {
+            return;
+        }
+        self.resolution = resolution;
+        self.depth_texture =
+            DepthTexture::create(device, resolution, resolution, "Shadow Map");
+        self.bind_group = Self::create_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.buffer,
+            &self.depth_texture,
+            &self.sampler,
+        );
+        self.preview.set_source(device, &self.depth_texture);
+        self.has_been_updated = true;
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        if !self.has_been_updated {
+            return;
+        }
+        let uniform = Self::uniform(
+            &self.light,
+            self.bias,
+            self.resolution,
+            self.enabled,
+        );
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.has_been_updated = false;
+    }
+
+    /// Render the depth of the buffers from the light point of view
+    ///
+    /// Every instance is drawn, the objects outside of the camera view still
+    /// cast shadows into it.
+    pub fn render<'a, I>(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        buffers: I,
+        profiler: &GpuProfiler,
+    ) where
+        I: Iterator<Item = &'a Buffer>,
+    {
+        if !self.enabled {
+            return;
+        }
+        {
+            let mut render_pass =
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Shadow Pass"),
+                    color_attachments: &[],
+                    depth_stencil_attachment: Some(
+                        wgpu::RenderPassDepthStencilAttachment {
+                            view: &self.depth_texture.view,
+                            depth_ops: Some(wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(1.0),
+                                store: wgpu::StoreOp::Store,
+                            }),
+                            stencil_ops: None,
+                        },
+                    ),
+                    occlusion_query_set: None,
+                    timestamp_writes: profiler
+                        .timestamp_writes(GpuPass::Shadow),
+                });
+
+            render_pass.set_pipeline(&self.depth_pipeline);
+            render_pass.set_bind_group(0, &self.light_bind_group, &[]);
+            for buffer in buffers {
+                buffer.render_all(&mut render_pass);
+            }
+        }
+
+        if self.preview_visible {
+            self.preview.render(encoder);
+        }
+    }
+}
+
+impl ShadowPreview {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8Unorm;
+
+    fn init(device: &wgpu::Device, source: &DepthTexture) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Shadow Preview"),
+            size: wgpu::Extent3d {
+                width: SHADOW_PREVIEW_SIZE,
+                height: SHADOW_PREVIEW_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float {
+                            filterable: false,
+                        },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                }],
+                label: Some("shadow_preview_bind_group_layout"),
+            });
+        let bind_group =
+            Self::create_bind_group(device, &bind_group_layout, source);
+
+        let shader =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Shadow Preview Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("shaders/shadow_preview.wgsl").into(),
+                ),
+            });
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Preview Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Shadow Preview Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: Self::FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Self {
+            view,
+            bind_group_layout,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        source: &DepthTexture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&source.view),
+            }],
+            label: Some("shadow_preview_bind_group"),
+        })
+    }
+
+    fn set_source(&mut self, device: &wgpu::Device, source: &DepthTexture) {
+        self.bind_group =
+            Self::create_bind_group(device, &self.bind_group_layout, source);
+    }
+
+    fn render(&self, encoder: &mut wgpu::CommandEncoder) {
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Preview Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
@@ -1,4 +1,6 @@
 use super::{
+    culling::Aabb,
+    lod::{Lod, LodMesh},
     types::{Buffer, Instance},
     Vertex,
 };
@@ -11,7 +13,21 @@ pub struct Shape {
     vertices: Vec<Vertex>,
     indices: Vec<u16>,
     instances: Vec<Instance>,
-    should_be_reloaded: bool,
+    /// Simplified meshes, by decreasing `max_size`
+    lods: Vec<Lod>,
+    /// Incremented at each change of the mesh, the renderers upload the
+    /// shape again when it differs from the revision they loaded
+    revision: u64,
+    /// Incremented when only the instances change, the renderers then
+    /// rewrite their instance buffers
+    instance_revision: u64,
+}
+
+/// Revisions of a shape loaded by a renderer, see `Shape::load`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShapeRevision {
+    mesh: u64,
+    instances: u64,
 }
 
 impl Shape {
@@ -24,8 +40,104 @@ impl Shape {
             vertices,
             indices,
             instances,
-            should_be_reloaded: true,
+            lods: Vec::new(),
+            revision: 1,
+            instance_revision: 1,
+        }
+    }
+
+    /// Draw the mesh of `lod` instead of this one for the instances whose
+    /// projected size, the diameter of their bounding sphere over the height
+    /// of the view, is under `max_size`
+    ///
+    /// The instances of `lod` are ignored, it should have the same bounds as
+    /// this shape.
+    pub fn with_lod(mut self, lod: Shape, max_size: f32) -> Self {
+        let index = self
+            .lods
+            .iter()
+            .position(|other| other.max_size < max_size)
+            .unwrap_or(self.lods.len());
+        self.lods.insert(
+            index,
+            Lod {
+                vertices: lod.vertices,
+                indices: lod.indices,
+                max_size,
+            },
+        );
+        self.revision += 1;
+        self
+    }
+
+    /// A UV sphere, `segments` is the number of vertical slices and gives
+    /// the level of detail
+    pub fn sphere(
+        center: Vector3<f32>,
+        radius: f32,
+        segments: u16,
+        color: [f32; 3],
+        instances: Vec<Instance>,
+    ) -> Self {
+        // Keeps the vertex count in the range of the u16 indices
+        let segments = segments.clamp(3, 180);
+        let rings = (segments / 2).max(2);
+        let mut vertices = Vec::new();
+        for ring in 0..=rings {
+            let theta = std::f32::consts::PI * ring as f32 / rings as f32;
+            for segment in 0..=segments {
+                let phi =
+                    std::f32::consts::TAU * segment as f32 / segments as f32;
+                let direction = Vector3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                vertices.push(Vertex::new(
+                    (center + direction * radius).into(),
+                    color,
+                ));
+            }
+        }
+        let mut indices = Vec::new();
+        let stride = segments + 1;
+        for ring in 0..rings {
+            for segment in 0..segments {
+                let top = ring * stride + segment;
+                let bottom = top + stride;
+                // Counter-clockwise seen from the outside
+                indices.extend_from_slice(&[
+                    top,
+                    bottom + 1,
+                    bottom,
+                    top,
+                    top + 1,
+                    bottom + 1,
+                ]);
+            }
         }
+        Self::new(vertices, indices, instances)
+    }
+
+    /// A rectangle facing +Z at the depth of `max`, the front face of
+    /// `Shape::rect` with the same corners
+    pub fn quad(
+        min: Vector3<f32>,
+        max: Vector3<f32>,
+        color: [f32; 3],
+        instances: Vec<Instance>,
+    ) -> Self {
+        let (vertices, indices) = shape!(
+            color;
+            A => Vector3::new(min.x, max.y, max.z).into(),
+            B => max.into(),
+            C => Vector3::new(max.x, min.y, max.z).into(),
+            D => Vector3::new(min.x, min.y, max.z).into();
+            A D C,
+            A C B,
+        );
+
+        Self::new(vertices.to_vec(), indices.to_vec(), instances)
     }
 
     pub fn rect(
@@ -78,36 +190,78 @@ impl Shape {
         &self.indices
     }
 
+    /// Bounding box of the vertices, in model space
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points(
+            self.vertices
+                .iter()
+                .map(|vertex| Vector3::from(vertex.position())),
+        )
+        .unwrap_or(Aabb::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+        ))
+    }
+
     pub fn instances(&self) -> &Vec<Instance> {
         return &self.instances;
     }
 
     pub fn set_instances(&mut self, instances: Vec<Instance>) {
         self.instances = instances;
-        self.should_be_reloaded = true;
+        self.instance_revision += 1;
     }
 
     pub fn set_color(&mut self, color: [f32; 3]) -> &mut Self {
-        self.vertices.iter_mut().for_each(|vertex| {
-            vertex.set_color(color);
-        });
-        self.should_be_reloaded = true;
+        self.vertices
+            .iter_mut()
+            .chain(self.lods.iter_mut().flat_map(|lod| lod.vertices.iter_mut()))
+            .for_each(|vertex| {
+                vertex.set_color(color);
+            });
+        self.revision += 1;
         self
     }
 
-    fn load_buffer(&self, device: &wgpu::Device) -> Buffer {
+    fn load_mesh(
+        device: &wgpu::Device,
+        vertices: &[Vertex],
+        indices: &[u16],
+    ) -> (wgpu::Buffer, wgpu::Buffer) {
         let vertex_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(&self.vertices),
-                usage: wgpu::BufferUsages::VERTEX,
+                contents: bytemuck::cast_slice(vertices),
+                usage: wgpu::BufferUsages::VERTEX
+                    | wgpu::BufferUsages::COPY_SRC,
             });
         let index_buffer =
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Index Buffer"),
-                contents: bytemuck::cast_slice(&self.indices),
-                usage: wgpu::BufferUsages::INDEX,
+                contents: bytemuck::cast_slice(indices),
+                usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_SRC,
             });
+        (vertex_buffer, index_buffer)
+    }
+
+    fn load_buffer(&self, device: &wgpu::Device) -> Buffer {
+        let (vertex_buffer, index_buffer) =
+            Self::load_mesh(device, &self.vertices, &self.indices);
+        let lods = self
+            .lods
+            .iter()
+            .map(|lod| {
+                let (vertex_buffer, index_buffer) =
+                    Self::load_mesh(device, &lod.vertices, &lod.indices);
+                LodMesh {
+                    vertex_buffer,
+                    index_buffer,
+                    num_indices: lod.indices.len() as u32,
+                    max_size: lod.max_size,
+                    num_instances: 0,
+                }
+            })
+            .collect();
         let instance_data = self
             .instances
             .iter()
@@ -117,24 +271,98 @@ impl Shape {
             device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Instance Buffer"),
                 contents: bytemuck::cast_slice(&instance_data),
-                usage: wgpu::BufferUsages::VERTEX,
+                usage: wgpu::BufferUsages::VERTEX
+                    | wgpu::BufferUsages::COPY_DST
+                    | wgpu::BufferUsages::COPY_SRC,
             });
+        let all_instance_buffer =
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("All Instance Buffer"),
+                contents: bytemuck::cast_slice(&instance_data),
+                usage: wgpu::BufferUsages::VERTEX
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+        let bounds = self.bounds();
+        let instance_bounds = self
+            .instances
+            .iter()
+            .map(|instance| bounds.transform(&instance.matrix()))
+            .collect();
         let num_indices = self.indices.len() as u32;
         let num_instances = self.instances.len() as u32;
         Buffer {
             vertex_buffer,
             instance_buffer,
+            all_instance_buffer,
             index_buffer,
             num_indices,
             num_instances,
+            instances: instance_data,
+            bounds,
+            instance_bounds,
+            needs_culling: true,
+            lods,
+            instance_lods: vec![0; self.instances.len()],
         }
     }
 
-    pub fn buffer(&self, device: &Device) -> Option<Buffer> {
-        if self.should_be_reloaded {
-            Some(self.load_buffer(&device))
-        } else {
-            None
+    /// Bring `buffer` up to date with the shape, `loaded` is the revision
+    /// of the buffer of the caller which is updated
+    ///
+    /// A new buffer is created when the mesh changed since `loaded`, only
+    /// the instances are written again when they are the sole change. Every
+    /// renderer drawing the shape keeps its own revision, the default one
+    /// when it has no buffer yet.
+    ///
+    /// Returns true when the buffer has been created or its number of
+    /// instances changed.
+    pub fn load(
+        &self,
+        device: &Device,
+        queue: &wgpu::Queue,
+        buffer: &mut Option<Buffer>,
+        loaded: &mut ShapeRevision,
+    ) -> bool {
+        match buffer {
+            Some(buffer) if loaded.mesh == self.revision => {
+                if loaded.instances == self.instance_revision {
+                    return false;
+                }
+                loaded.instances = self.instance_revision;
+                let count = buffer.total_instances();
+                buffer.write_instances(device, queue, &self.instances);
+                buffer.total_instances() != count
+            }
+            _ => {
+                *loaded = ShapeRevision {
+                    mesh: self.revision,
+                    instances: self.instance_revision,
+                };
+                *buffer = Some(self.load_buffer(device));
+                true
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instances_keep_the_mesh_revision() {
+        let mut shape = Shape::rect(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 1.0),
+            [1.0, 1.0, 1.0],
+            vec![],
+        );
+        let revision = shape.revision;
+        shape.set_instances(vec![Instance::identity()]);
+        assert_eq!(shape.revision, revision);
+        assert_eq!(shape.instance_revision, 2);
+
+        shape.set_color([0.0, 0.0, 0.0]);
+        assert_ne!(shape.revision, revision);
+    }
+}
@@ -0,0 +1,659 @@
+use std::cell::RefCell;
+
+use ab_glyph::{Font, FontRef, ScaleFont};
+use cgmath::Vector3;
+
+use super::{
+    camera::CameraBuffer,
+    profiler::{GpuPass, GpuProfiler},
+    types::Instance,
+};
+
+/// Size in pixels of the glyphs rasterized into the atlas
+const ATLAS_FONT_SIZE: f32 = 32.0;
+/// Empty pixels around each glyph of the atlas, avoids bleeding when the
+/// atlas is sampled with linear filtering
+const ATLAS_PADDING: u32 = 1;
+/// Number of glyphs per row of the atlas
+const ATLAS_COLUMNS: u32 = 16;
+/// Printable ASCII characters, the other ones are drawn as `?`
+const FIRST_CHAR: char = ' ';
+const LAST_CHAR: char = '~';
+/// Height in pixels of the text drawn by `debug_label` and `hud_text`
+const DEBUG_TEXT_SIZE: f32 = 16.0;
+/// Offset in pixels of the shadow drawn behind each glyph
+const SHADOW_OFFSET: f32 = 1.0;
+/// Number of glyphs reserved when the glyph buffer is created
+const INITIAL_CAPACITY: usize = 1024;
+
+thread_local! {
+    /// Texts queued since the last frame
+    static DEBUG_TEXTS: RefCell<Vec<Text>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// Where a text is drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAnchor {
+    /// Follows a point of the scene
+    World(Vector3<f32>),
+    /// Pixels from the top left corner of the window
+    Screen([f32; 2]),
+}
+
+/// A string drawn on top of the scene
+///
+/// The text is never hidden by the geometry, labels anchored in the world
+/// are only skipped when their anchor is behind the camera.
+#[derive(Debug, Clone)]
+pub struct Text {
+    text: String,
+    anchor: TextAnchor,
+    size: f32,
+    color: [f32; 4],
+    offset: [f32; 2],
+    centered: bool,
+}
+
+/// A glyph of the atlas, the lengths are in pixels at `ATLAS_FONT_SIZE`
+#[derive(Debug, Clone, Copy, Default)]
+struct Glyph {
+    /// Texture coordinates of the top left and bottom right corners
+    uv: [f32; 4],
+    /// Top left corner relative to the pen on the baseline
+    offset: [f32; 2],
+    size: [f32; 2],
+    advance: f32,
+}
+
+/// Printable ASCII characters rasterized into a single channel texture
+struct FontAtlas {
+    glyphs: Vec<Glyph>,
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    ascent: f32,
+    line_height: f32,
+}
+
+/// A glyph placed relative to the anchor of its text
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct GlyphQuad {
+    /// x, y: top left corner, z, w: size, in pixels
+    rect: [f32; 4],
+    uv: [f32; 4],
+}
+
+/// A glyph as uploaded to the GPU, one instance per glyph
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct GlyphInstance {
+    /// xyz: world position or pixel position, w: 1.0 in the world
+    anchor: [f32; 4],
+    /// x, y: offset from the anchor, z, w: size, in pixels
+    rect: [f32; 4],
+    uv: [f32; 4],
+    color: [f32; 4],
+}
+
+/// Draws the texts queued with `debug_text`, `debug_label` and `hud_text`
+///
+/// The texts are drawn after the post-processing so they stay sharp. Like
+/// the debug lines, the queue is emptied every time the texts are uploaded.
+pub struct TextRenderer {
+    pipeline: wgpu::RenderPipeline,
+    atlas: FontAtlas,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    count: u32,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    size: (u32, u32),
+    has_been_updated: bool,
+}
+
+impl Text {
+    fn new(anchor: TextAnchor, text: &str, centered: bool) -> Self {
+        Self {
+            text: text.to_string(),
+            anchor,
+            size: DEBUG_TEXT_SIZE,
+            color: [1.0; 4],
+            offset: [0.0; 2],
+            centered,
+        }
+    }
+
+    /// Text centered on a point of the scene
+    pub fn world<P: Into<Vector3<f32>>>(position: P, text: &str) -> Self {
+        Self::new(TextAnchor::World(position.into()), text, true)
+    }
+
+    /// Text centered on the origin of an instance
+    pub fn instance(instance: &Instance, text: &str) -> Self {
+        Self::world(instance.matrix().w.truncate(), text)
+    }
+
+    /// Text starting at a pixel of the window
+    pub fn screen(position: [f32; 2], text: &str) -> Self {
+        Self::new(TextAnchor::Screen(position), text, false)
+    }
+
+    /// Height of a line in pixels
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 3]) -> Self {
+        self.color = [color[0], color[1], color[2], 1.0];
+        self
+    }
+
+    /// Offset in pixels added to the anchor, y goes down
+    pub fn with_offset(mut self, offset: [f32; 2]) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Center the text on its anchor instead of starting there
+    pub fn with_centered(mut self, centered: bool) -> Self {
+        self.centered = centered;
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn anchor(&self) -> TextAnchor {
+        self.anchor
+    }
+}
+
+/// Queue a text to be drawn on the next frame
+pub fn debug_text(text: Text) {
+    DEBUG_TEXTS.with(|texts| texts.borrow_mut().push(text));
+}
+
+/// Queue a label centered on a point of the scene for the next frame
+pub fn debug_label<P: Into<Vector3<f32>>>(
+    position: P,
+    text: &str,
+    color: [f32; 3],
+) {
+    debug_text(Text::world(position, text).with_color(color));
+}
+
+/// Queue a text at a pixel of the window for the next frame
+pub fn hud_text(position: [f32; 2], text: &str, color: [f32; 3]) {
+    debug_text(Text::screen(position, text).with_color(color));
+}
+
+/// Remove every queued text
+pub(crate) fn take_debug_texts() -> Vec<Text> {
+    DEBUG_TEXTS.with(|texts| std::mem::take(&mut *texts.borrow_mut()))
+}
+
+impl FontAtlas {
+    /// Rasterize the monospace font shipped with egui
+    fn new() -> Result<Self, crate::Error> {
+        let definitions = egui::FontDefinitions::default();
+        let data = definitions.font_data.get("Hack").ok_or_else(|| {
+            crate::Error::Font("missing the default monospace font".into())
+        })?;
+        let font = FontRef::try_from_slice_and_index(&data.font, data.index)
+            .map_err(|error| crate::Error::Font(error.to_string()))?;
+        Self::from_font(&font)
+    }
+
+    fn from_font<F: Font>(font: &F) -> Result<Self, crate::Error> {
+        let scaled = font.as_scaled(ATLAS_FONT_SIZE);
+        let outlines = (FIRST_CHAR..=LAST_CHAR)
+            .map(|c| {
+                let glyph = scaled.scaled_glyph(c);
+                let advance = scaled.h_advance(glyph.id);
+                (font.outline_glyph(glyph), advance)
+            })
+            .collect::<Vec<_>>();
+
+        // Every glyph gets a cell as large as the largest one
+        let (cell_width, cell_height) = outlines
+            .iter()
+            .filter_map(|(outline, _)| outline.as_ref())
+            .map(|outline| outline.px_bounds())
+            .fold((0, 0), |(width, height), bounds| {
+                (
+                    width.max(bounds.width().ceil() as u32),
+                    height.max(bounds.height().ceil() as u32),
+                )
+            });
+        let cell_width = cell_width + 2 * ATLAS_PADDING;
+        let cell_height = cell_height + 2 * ATLAS_PADDING;
+        let rows = (outlines.len() as u32).div_ceil(ATLAS_COLUMNS);
+        let width = ATLAS_COLUMNS * cell_width;
+        let height = rows * cell_height;
+        let mut pixels = vec![0u8; (width * height) as usize];
+
+        let mut glyphs = Vec::with_capacity(outlines.len());
+        for (i, (outline, advance)) in outlines.into_iter().enumerate() {
+            let mut glyph = Glyph {
+                advance,
+                ..Default::default()
+            };
+            if let Some(outline) = outline {
+                let bounds = outline.px_bounds();
+                let x0 =
+                    (i as u32 % ATLAS_COLUMNS) * cell_width + ATLAS_PADDING;
+                let y0 =
+                    (i as u32 / ATLAS_COLUMNS) * cell_height + ATLAS_PADDING;
+                outline.draw(|x, y, coverage| {
+                    let index = (y0 + y) * width + x0 + x;
+                    pixels[index as usize] = (coverage * 255.0) as u8;
+                });
+                glyph.offset = [bounds.min.x, bounds.min.y];
+                glyph.size = [bounds.width(), bounds.height()];
+                glyph.uv = [
+                    x0 as f32 / width as f32,
+                    y0 as f32 / height as f32,
+                    (x0 as f32 + bounds.width()) / width as f32,
+                    (y0 as f32 + bounds.height()) / height as f32,
+                ];
+            }
+            glyphs.push(glyph);
+        }
+
+        Ok(Self {
+            glyphs,
+            pixels,
+            width,
+            height,
+            ascent: scaled.ascent(),
+            line_height: scaled.height() + scaled.line_gap(),
+        })
+    }
+
+    fn glyph(&self, c: char) -> &Glyph {
+        let index = if (FIRST_CHAR..=LAST_CHAR).contains(&c) {
+            c as usize - FIRST_CHAR as usize
+        } else {
+            '?' as usize - FIRST_CHAR as usize
+        };
+        &self.glyphs[index]
+    }
+
+    /// Place the glyphs of a text whose top left corner is the origin
+    ///
+    /// Returns the quads of the visible glyphs and the size of the text.
+    fn layout(&self, text: &str, size: f32) -> (Vec<GlyphQuad>, [f32; 2]) {
+        let scale = size / ATLAS_FONT_SIZE;
+        let mut quads = Vec::with_capacity(text.len());
+        let mut width = 0.0f32;
+        let mut lines = 0;
+        for (row, line) in text.lines().enumerate() {
+            let baseline =
+                (self.ascent + row as f32 * self.line_height) * scale;
+            let mut pen = 0.0;
+            for c in line.chars() {
+                let glyph = self.glyph(c);
+                if glyph.size[0] > 0.0 {
+                    quads.push(GlyphQuad {
+                        rect: [
+                            pen + glyph.offset[0] * scale,
+                            baseline + glyph.offset[1] * scale,
+                            glyph.size[0] * scale,
+                            glyph.size[1] * scale,
+                        ],
+                        uv: glyph.uv,
+                    });
+                }
+                pen += glyph.advance * scale;
+            }
+            width = width.max(pen);
+            lines = row + 1;
+        }
+        (quads, [width, lines as f32 * self.line_height * scale])
+    }
+}
+
+impl GlyphInstance {
+    const ATTRIBS: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Float32x4,
+        2 => Float32x4,
+        3 => Float32x4,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+impl TextRenderer {
+    pub fn init(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        config: &wgpu::SurfaceConfiguration,
+        camera: &CameraBuffer,
+    ) -> Result<Self, crate::Error> {
+        let atlas = FontAtlas::new()?;
+        let size = wgpu::Extent3d {
+            width: atlas.width,
+            height: atlas.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Font Atlas Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            &atlas.pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(atlas.width),
+                rows_per_image: Some(atlas.height),
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Font Atlas Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Uniform Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+                label: Some("text_bind_group_layout"),
+            });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("text_bind_group"),
+        });
+
+        let shader =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Text Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("shaders/text.wgsl").into(),
+                ),
+            });
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Text Pipeline Layout"),
+                bind_group_layouts: &[
+                    camera.bind_group_layout(),
+                    &bind_group_layout,
+                ],
+                push_constant_ranges: &[],
+            });
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Text Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[GlyphInstance::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    // Drawn into the surface, after the post-processing
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Ok(Self {
+            pipeline,
+            atlas,
+            buffer: Self::create_buffer(device, INITIAL_CAPACITY),
+            capacity: INITIAL_CAPACITY,
+            count: 0,
+            uniform_buffer,
+            bind_group,
+            size: (config.width, config.height),
+            has_been_updated: true,
+        })
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Glyph Buffer"),
+            size: (capacity * std::mem::size_of::<GlyphInstance>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width, height);
+        self.has_been_updated = true;
+    }
+
+    /// Number of glyphs drawn on the last frame, shadows included
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Width and height in pixels of a text once drawn
+    pub fn measure(&self, text: &Text) -> [f32; 2] {
+        self.atlas.layout(&text.text, text.size).1
+    }
+
+    /// Build the glyphs of a text, each one preceded by its shadow
+    fn glyphs(&self, text: &Text) -> impl Iterator<Item = GlyphInstance> {
+        let (quads, size) = self.atlas.layout(&text.text, text.size);
+        let mut origin = text.offset;
+        if text.centered {
+            origin[0] -= size[0] * 0.5;
+            origin[1] -= size[1] * 0.5;
+        }
+        let anchor = match text.anchor {
+            TextAnchor::World(position) => position.extend(1.0).into(),
+            TextAnchor::Screen([x, y]) => [x, y, 0.0, 0.0],
+        };
+        let color = text.color;
+        let shadow = [0.0, 0.0, 0.0, color[3] * 0.8];
+        quads.into_iter().flat_map(move |quad| {
+            let rect = |offset: f32| {
+                [
+                    origin[0] + quad.rect[0] + offset,
+                    origin[1] + quad.rect[1] + offset,
+                    quad.rect[2],
+                    quad.rect[3],
+                ]
+            };
+            [
+                GlyphInstance {
+                    anchor,
+                    rect: rect(SHADOW_OFFSET),
+                    uv: quad.uv,
+                    color: shadow,
+                },
+                GlyphInstance {
+                    anchor,
+                    rect: rect(0.0),
+                    uv: quad.uv,
+                    color,
+                },
+            ]
+        })
+    }
+
+    /// Upload the texts to draw on the next frames
+    pub fn upload(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        texts: &[Text],
+    ) {
+        if self.has_been_updated {
+            let viewport = [self.size.0 as f32, self.size.1 as f32, 0.0, 0.0];
+            queue.write_buffer(
+                &self.uniform_buffer,
+                0,
+                bytemuck::cast_slice(&[viewport]),
+            );
+            self.has_been_updated = false;
+        }
+
+        let glyphs = texts
+            .iter()
+            .flat_map(|text| self.glyphs(text))
+            .collect::<Vec<_>>();
+        if glyphs.len() > self.capacity {
+            self.capacity = glyphs.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        if !glyphs.is_empty() {
+            queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&glyphs));
+        }
+        self.count = glyphs.len() as u32;
+    }
+
+    /// Draw the texts over the content of the view
+    pub fn render(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        camera: &CameraBuffer,
+        profiler: &GpuProfiler,
+    ) {
+        if self.count == 0 {
+            return;
+        }
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Text Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: profiler.timestamp_writes(GpuPass::Text),
+            });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera.bind_group(), &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.draw(0..6, 0..self.count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let atlas = FontAtlas::new().unwrap();
+        let (quads, size) = atlas.layout("ab c", ATLAS_FONT_SIZE);
+        // The space has no quad but moves the pen
+        assert_eq!(quads.len(), 3);
+        assert!(quads[0].rect[0] < quads[1].rect[0]);
+        assert!(size[0] > quads[2].rect[0]);
+        assert_eq!(size[1], atlas.line_height);
+
+        // Half the size, two lines
+        let (quads, half) = atlas.layout("ab\nc", ATLAS_FONT_SIZE * 0.5);
+        assert_eq!(quads.len(), 3);
+        assert!(quads[2].rect[1] > quads[0].rect[1]);
+        assert_eq!(half[1], atlas.line_height);
+
+        // Unknown characters fall back to '?'
+        let (unknown, _) = atlas.layout("é", ATLAS_FONT_SIZE);
+        let (question, _) = atlas.layout("?", ATLAS_FONT_SIZE);
+        assert_eq!(unknown, question);
+    }
+
+    #[test]
+    fn test_debug_texts_are_cleared() {
+        take_debug_texts();
+        debug_label([0.0, 1.0, 0.0], "label", [1.0, 0.0, 0.0]);
+        hud_text([10.0, 10.0], "hud", [1.0, 1.0, 1.0]);
+        let texts = take_debug_texts();
+        assert_eq!(texts.len(), 2);
+        assert!(matches!(texts[0].anchor(), TextAnchor::World(_)));
+        assert_eq!(texts[1].text(), "hud");
+        assert!(take_debug_texts().is_empty());
+    }
+}
@@ -1,3 +1,13 @@
+use std::ops::Range;
+
+use cgmath::InnerSpace;
+
+use super::{
+    camera::CameraBuffer,
+    culling::{Aabb, Frustum},
+    lod::{self, LodMesh},
+};
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
@@ -9,6 +19,10 @@ pub struct Vertex {
 pub struct Instance {
     position: cgmath::Vector3<f32>,
     rotation: cgmath::Quaternion<f32>,
+    scale: cgmath::Vector3<f32>,
+    /// World matrix of the parent node, applied after the local transform.
+    /// This is the identity for instances that are not part of a scene graph.
+    parent: cgmath::Matrix4<f32>,
 }
 
 #[repr(C)]
@@ -20,9 +34,25 @@ pub struct InstanceRaw {
 pub struct Buffer {
     pub vertex_buffer: wgpu::Buffer,
     pub instance_buffer: wgpu::Buffer,
+    /// Every instance, culled or not, for the passes which do not see
+    /// through the camera
+    pub all_instance_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_indices: u32,
+    /// Number of instances to draw (the visible ones when culling)
     pub num_instances: u32,
+    /// CPU copy of every instance, used to refill the instance buffer
+    pub instances: Vec<InstanceRaw>,
+    /// Bounding box of the mesh, in model space
+    pub bounds: Aabb,
+    /// World bounding box of every instance
+    pub instance_bounds: Vec<Aabb>,
+    /// Set when the visible instances must be computed again
+    pub needs_culling: bool,
+    /// Simplified meshes, from the most detailed
+    pub lods: Vec<LodMesh>,
+    /// Level of detail of every instance, 0 being the shape itself
+    pub instance_lods: Vec<u8>,
 }
 
 /// Implement the Vertex struct
@@ -68,9 +98,9 @@ impl Vertex {
     //     self.color
     // }
 
-    // pub fn position(&self) -> [f32; 3] {
-    //     self.position
-    // }
+    pub fn position(&self) -> [f32; 3] {
+        self.position
+    }
 
     // pub fn position_mut(&mut self) -> &mut [f32; 3] {
     //     &mut self.position
@@ -98,13 +128,22 @@ impl Instance {
         Self {
             position: position.into(),
             rotation: rotation.into(),
+            ..Self::identity()
         }
     }
 
+    #[rustfmt::skip]
     pub const fn identity() -> Self {
         Self {
             position: cgmath::Vector3::new(0.0, 0.0, 0.0),
             rotation: cgmath::Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            scale: cgmath::Vector3::new(1.0, 1.0, 1.0),
+            parent: cgmath::Matrix4::new(
+                1.0, 0.0, 0.0, 0.0,
+                0.0, 1.0, 0.0, 0.0,
+                0.0, 0.0, 1.0, 0.0,
+                0.0, 0.0, 0.0, 1.0,
+            ),
         }
     }
 
@@ -124,16 +163,230 @@ impl Instance {
         self
     }
 
+    pub fn with_scale<S>(mut self, scale: S) -> Self
+    where
+        S: Into<cgmath::Vector3<f32>>,
+    {
+        self.scale = scale.into();
+        self
+    }
+
+    pub fn with_parent(mut self, parent: cgmath::Matrix4<f32>) -> Self {
+        self.parent = parent;
+        self
+    }
+
+    pub fn position(&self) -> cgmath::Vector3<f32> {
+        self.position
+    }
+
+    pub fn rotation(&self) -> cgmath::Quaternion<f32> {
+        self.rotation
+    }
+
+    pub fn scale(&self) -> cgmath::Vector3<f32> {
+        self.scale
+    }
+
+    /// Local transform of the instance (translation * rotation * scale),
+    /// without the parent matrix.
+    pub fn local_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(
+                self.scale.x,
+                self.scale.y,
+                self.scale.z,
+            )
+    }
+
+    /// World transform of the instance
+    pub fn matrix(&self) -> cgmath::Matrix4<f32> {
+        self.parent * self.local_matrix()
+    }
+
     pub fn to_raw(&self) -> InstanceRaw {
         InstanceRaw {
-            model: (cgmath::Matrix4::from_translation(self.position)
-                * cgmath::Matrix4::from(self.rotation))
-            .into(),
+            model: self.matrix().into(),
+        }
+    }
+}
+
+impl Buffer {
+    /// Total number of instances, visible or not
+    pub fn total_instances(&self) -> u32 {
+        self.instances.len() as u32
+    }
+
+    /// Replace the instances, the instance buffers are written again and
+    /// only created anew when they are too small
+    pub fn write_instances(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        instances: &[Instance],
+    ) {
+        self.instances = instances.iter().map(Instance::to_raw).collect();
+        self.instance_bounds = instances
+            .iter()
+            .map(|instance| self.bounds.transform(&instance.matrix()))
+            .collect();
+        self.instance_lods.resize(instances.len(), 0);
+        self.needs_culling = true;
+
+        let size = self.instances.len() as u64 * InstanceRaw::SIZE;
+        if size > self.all_instance_buffer.size() {
+            let size = size.next_power_of_two();
+            let create = |buffer: &wgpu::Buffer, label| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size,
+                    usage: buffer.usage(),
+                    mapped_at_creation: false,
+                })
+            };
+            self.instance_buffer =
+                create(&self.instance_buffer, "Instance Buffer");
+            self.all_instance_buffer =
+                create(&self.all_instance_buffer, "All Instance Buffer");
+        }
+        if !self.instances.is_empty() {
+            queue.write_buffer(
+                &self.all_instance_buffer,
+                0,
+                bytemuck::cast_slice(&self.instances),
+            );
+        }
+    }
+
+    /// Write only the instances intersecting the frustum into the instance
+    /// buffer. Every instance is written back if there is no frustum.
+    ///
+    /// The instances are sorted by level of detail, picked from their size
+    /// seen by the camera, the instances of each level follow those of the
+    /// previous one.
+    pub fn cull(
+        &mut self,
+        queue: &wgpu::Queue,
+        frustum: Option<&Frustum>,
+        camera: &CameraBuffer,
+    ) {
+        self.needs_culling = false;
+        let max_sizes =
+            self.lods.iter().map(|lod| lod.max_size).collect::<Vec<_>>();
+        let mut levels = vec![Vec::new(); self.lods.len() + 1];
+        for ((instance, bounds), level) in self
+            .instances
+            .iter()
+            .zip(self.instance_bounds.iter())
+            .zip(self.instance_lods.iter_mut())
+        {
+            if frustum.is_some_and(|frustum| !frustum.intersects(bounds)) {
+                continue;
+            }
+            if !max_sizes.is_empty() {
+                let size = camera.projected_size(
+                    bounds.center(),
+                    bounds.extents().magnitude(),
+                );
+                *level = lod::select(&max_sizes, size, *level as usize) as u8;
+            }
+            levels[*level as usize].push(*instance);
+        }
+        for (lod, instances) in self.lods.iter_mut().zip(&levels[1..]) {
+            lod.num_instances = instances.len() as u32;
+        }
+
+        let visible = levels.concat();
+        self.num_instances = visible.len() as u32;
+        if !visible.is_empty() {
+            queue.write_buffer(
+                &self.instance_buffer,
+                0,
+                bytemuck::cast_slice(&visible),
+            );
         }
     }
+
+    /// Meshes to draw, from the most detailed, with the range of visible
+    /// instances drawn by each of them
+    pub fn meshes(
+        &self,
+    ) -> impl Iterator<Item = (&wgpu::Buffer, &wgpu::Buffer, u32, Range<u32>)>
+    {
+        let lod_instances =
+            self.lods.iter().map(|lod| lod.num_instances).sum::<u32>();
+        let mut first = self.num_instances - lod_instances;
+        std::iter::once((
+            &self.vertex_buffer,
+            &self.index_buffer,
+            self.num_indices,
+            0..first,
+        ))
+        .chain(self.lods.iter().map(move |lod| {
+            let instances = first..first + lod.num_instances;
+            first = instances.end;
+            (
+                &lod.vertex_buffer,
+                &lod.index_buffer,
+                lod.num_indices,
+                instances,
+            )
+        }))
+    }
+
+    /// Draw the visible instances
+    ///
+    /// The pipeline and the bind groups must already be set.
+    pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        for (vertex_buffer, index_buffer, num_indices, instances) in
+            self.meshes()
+        {
+            if instances.is_empty() {
+                continue;
+            }
+            // Bound at the first instance, the draws start at 0 on every
+            // backend
+            let offset = instances.start as u64 * InstanceRaw::SIZE;
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass
+                .set_vertex_buffer(1, self.instance_buffer.slice(offset..));
+            render_pass.set_index_buffer(
+                index_buffer.slice(..),
+                wgpu::IndexFormat::Uint16,
+            );
+            render_pass.draw_indexed(
+                0..num_indices,
+                0,
+                0..instances.len() as u32,
+            );
+        }
+    }
+
+    /// Draw every instance with the most detailed mesh, whatever the culling
+    ///
+    /// The pipeline and the bind groups must already be set.
+    pub fn render_all<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        if self.instances.is_empty() {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.all_instance_buffer.slice(..));
+        render_pass.set_index_buffer(
+            self.index_buffer.slice(..),
+            wgpu::IndexFormat::Uint16,
+        );
+        render_pass.draw_indexed(
+            0..self.num_indices,
+            0,
+            0..self.total_instances(),
+        );
+    }
 }
 
 impl InstanceRaw {
+    pub const SIZE: u64 = std::mem::size_of::<InstanceRaw>() as u64;
+
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<InstanceRaw>()
@@ -0,0 +1,476 @@
+use cgmath::Deg;
+
+use super::{
+    camera::{Camera, CameraBuffer, Projection},
+    pipeline::{create_msaa_view, DepthTexture, RenderTarget},
+    post::{PostProcess, POST_COMMON},
+};
+use crate::config::ViewportLayout;
+
+/// Distance from the origin of the cameras of the orthographic views
+const ORTHOGRAPHIC_DISTANCE: f32 = 50.0;
+/// World units shown vertically by the orthographic views
+const ORTHOGRAPHIC_HEIGHT: f32 = 40.0;
+/// Size in pixels of the views shown in the debug window
+const DEBUG_VIEW_SIZE: u32 = 256;
+
+/// Where the image of a viewport is shown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ViewportPlacement {
+    /// Only rendered into its texture, to be shown as an egui image for
+    /// instance
+    Texture { width: u32, height: u32 },
+    /// Drawn over the window after the post-processing, the rectangle
+    /// `[x, y, width, height]` is given in fractions of the window
+    Screen([f32; 4]),
+}
+
+/// A view of the scene rendered from its own camera into a texture
+///
+/// The viewports draw the same layers as the window, except for the gizmo
+/// and the texts which are drawn over the window only.
+pub struct Viewport {
+    name: String,
+    pub camera: CameraBuffer,
+    placement: ViewportPlacement,
+    size: (u32, u32),
+    view: wgpu::TextureView,
+    /// Resolved into `view` when the scene is multisampled
+    msaa_view: Option<wgpu::TextureView>,
+    depth: DepthTexture,
+    /// Binds the texture for the compositing into the window
+    bind_group: wgpu::BindGroup,
+    enabled: bool,
+}
+
+/// The extra viewports and the pipeline drawing them into the window
+pub struct Viewports {
+    viewports: Vec<Viewport>,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    window: (u32, u32),
+    /// Samples per pixel of the scene
+    samples: u32,
+}
+
+impl ViewportPlacement {
+    /// Rectangle `[x, y, width, height]` in pixels covered in the window,
+    /// snapped to the pixels so the viewports placed side by side do not
+    /// leave gaps
+    fn pixels(rect: [f32; 4], window: (u32, u32)) -> [u32; 4] {
+        let [x, y, width, height] = rect;
+        let snap = |value: f32, size: u32| {
+            (value.clamp(0.0, 1.0) * size as f32).round() as u32
+        };
+        let left = snap(x, window.0).min(window.0.saturating_sub(1));
+        let top = snap(y, window.1).min(window.1.saturating_sub(1));
+        let right = snap(x + width, window.0).max(left + 1);
+        let bottom = snap(y + height, window.1).max(top + 1);
+        [left, top, right - left, bottom - top]
+    }
+
+    /// Size in pixels of the texture for a window size
+    fn size(&self, window: (u32, u32)) -> (u32, u32) {
+        match *self {
+            ViewportPlacement::Texture { width, height } => (width, height),
+            ViewportPlacement::Screen(rect) => {
+                let [_, _, width, height] = Self::pixels(rect, window);
+                (width, height)
+            }
+        }
+    }
+}
+
+impl Viewport {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn placement(&self) -> ViewportPlacement {
+        self.placement
+    }
+
+    /// Color texture the viewport is rendered into, in
+    /// `PostProcess::HDR_FORMAT`
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub(crate) fn target(&self, clear: wgpu::Color) -> RenderTarget<'_> {
+        RenderTarget {
+            color: self.msaa_view.as_ref().unwrap_or(&self.view),
+            resolve: self.msaa_view.as_ref().map(|_| &self.view),
+            depth: &self.depth.view,
+            clear,
+        }
+    }
+}
+
+impl Viewports {
+    pub fn init(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        samples: u32,
+    ) -> Self {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Viewport Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float {
+                                filterable: true,
+                            },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(
+                            wgpu::SamplerBindingType::Filtering,
+                        ),
+                        count: None,
+                    },
+                ],
+                label: Some("viewport_bind_group_layout"),
+            });
+
+        // The blit effect only reads the texture, its uniform is unused
+        let shader =
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Viewport Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    format!(
+                        "{}\n{}",
+                        POST_COMMON,
+                        include_str!("shaders/post/blit.wgsl")
+                    )
+                    .into(),
+                ),
+            });
+        let layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Viewport Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Viewport Pipeline"),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            });
+
+        Self {
+            viewports: Vec::new(),
+            pipeline,
+            bind_group_layout,
+            sampler,
+            window: (config.width, config.height),
+            samples,
+        }
+    }
+
+    /// Create the texture, the multisampled texture, the depth buffer and
+    /// the bind group of a viewport
+    fn create_targets(
+        &self,
+        device: &wgpu::Device,
+        size: (u32, u32),
+    ) -> (
+        wgpu::TextureView,
+        Option<wgpu::TextureView>,
+        DepthTexture,
+        wgpu::BindGroup,
+    ) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Viewport Texture"),
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: PostProcess::HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let msaa_view = create_msaa_view(
+            device,
+            size.0,
+            size.1,
+            self.samples,
+            "Viewport Multisampled Texture",
+        );
+        let depth = DepthTexture::create_multisampled(
+            device,
+            size.0,
+            size.1,
+            self.samples,
+            "Viewport Depth",
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+            label: Some("viewport_bind_group"),
+        });
+        (view, msaa_view, depth, bind_group)
+    }
+
+    /// Add a viewport, `main_camera` provides the layout of the camera bind
+    /// group. Returns the index of the viewport.
+    pub fn add(
+        &mut self,
+        device: &wgpu::Device,
+        main_camera: &CameraBuffer,
+        name: &str,
+        camera: Camera,
+        projection: Projection,
+        placement: ViewportPlacement,
+    ) -> usize {
+        let size = placement.size(self.window);
+        let mut camera = main_camera.share(device, camera, projection);
+        camera.resize(size.0, size.1);
+        let (view, msaa_view, depth, bind_group) =
+            self.create_targets(device, size);
+        self.viewports.push(Viewport {
+            name: name.to_string(),
+            camera,
+            placement,
+            size,
+            view,
+            msaa_view,
+            depth,
+            bind_group,
+            enabled: true,
+        });
+        self.viewports.len() - 1
+    }
+
+    /// Add the top, front and side orthographic views of a layout
+    pub fn add_layout(
+        &mut self,
+        device: &wgpu::Device,
+        main_camera: &CameraBuffer,
+        layout: ViewportLayout,
+    ) {
+        let views = [
+            (
+                "Top View",
+                // Looking down, with -z at the top of the image
+                Camera::new(
+                    [0.0, ORTHOGRAPHIC_DISTANCE, 0.0],
+                    Deg(-90.0),
+                    Deg(-89.9),
+                ),
+            ),
+            (
+                "Front View",
+                Camera::new(
+                    [0.0, 0.0, ORTHOGRAPHIC_DISTANCE],
+                    Deg(-90.0),
+                    Deg(0.0),
+                ),
+            ),
+            (
+                "Side View",
+                Camera::new(
+                    [ORTHOGRAPHIC_DISTANCE, 0.0, 0.0],
+                    Deg(180.0),
+                    Deg(0.0),
+                ),
+            ),
+        ];
+        let placements = match layout {
+            ViewportLayout::None => Vec::new(),
+            ViewportLayout::PictureInPicture => {
+                vec![ViewportPlacement::Screen([0.7, 0.7, 0.28, 0.28])]
+            }
+            ViewportLayout::Split => (0..3)
+                .map(|i| {
+                    let height = 1.0 / 3.0;
+                    ViewportPlacement::Screen([
+                        2.0 / 3.0,
+                        i as f32 * height,
+                        1.0 / 3.0,
+                        height,
+                    ])
+                })
+                .collect(),
+            ViewportLayout::Debug => vec![
+                ViewportPlacement::Texture {
+                    width: DEBUG_VIEW_SIZE,
+                    height: DEBUG_VIEW_SIZE,
+                };
+                3
+            ],
+        };
+        for ((name, camera), placement) in views.into_iter().zip(placements) {
+            let size = placement.size(self.window);
+            let projection = Projection::orthographic(
+                size.0,
+                size.1,
+                ORTHOGRAPHIC_HEIGHT,
+                0.1,
+                2.0 * ORTHOGRAPHIC_DISTANCE,
+            );
+            self.add(device, main_camera, name, camera, projection, placement);
+        }
+    }
+
+    /// Add the viewports of a pipeline whose device was lost in place of the
+    /// current ones, with the same cameras, placements and states
+    ///
+    /// The viewports keep their indices.
+    pub fn restore(
+        &mut self,
+        device: &wgpu::Device,
+        main_camera: &CameraBuffer,
+        lost: &Viewports,
+    ) {
+        self.viewports.clear();
+        for viewport in lost.iter() {
+            let index = self.add(
+                device,
+                main_camera,
+                &viewport.name,
+                viewport.camera.camera.clone(),
+                viewport.camera.projection.clone(),
+                viewport.placement,
+            );
+            self.viewports[index].enabled = viewport.enabled;
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Viewport> {
+        self.viewports.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Viewport> {
+        self.viewports.get_mut(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Viewport> {
+        self.viewports.iter()
+    }
+
+    /// The viewports placed in the window follow its size
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.window = (width, height);
+        for i in 0..self.viewports.len() {
+            let size = self.viewports[i].placement.size(self.window);
+            if size == self.viewports[i].size {
+                continue;
+            }
+            let (view, msaa_view, depth, bind_group) =
+                self.create_targets(device, size);
+            let viewport = &mut self.viewports[i];
+            viewport.size = size;
+            viewport.view = view;
+            viewport.msaa_view = msaa_view;
+            viewport.depth = depth;
+            viewport.bind_group = bind_group;
+            viewport.camera.resize(size.0, size.1);
+        }
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue) {
+        for viewport in self.viewports.iter_mut() {
+            viewport.camera.update(queue);
+        }
+    }
+
+    /// Draw the viewports placed in the window over the view
+    pub fn composite(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+    ) {
+        let mut viewports = self
+            .viewports
+            .iter()
+            .filter(|viewport| viewport.enabled)
+            .filter_map(|viewport| match viewport.placement {
+                ViewportPlacement::Screen(rect) => Some((viewport, rect)),
+                ViewportPlacement::Texture { .. } => None,
+            })
+            .peekable();
+        if viewports.peek().is_none() {
+            return;
+        }
+        let mut render_pass =
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Viewport Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+        render_pass.set_pipeline(&self.pipeline);
+        for (viewport, rect) in viewports {
+            // The fullscreen triangle of the blit fills the rectangle
+            let [x, y, width, height] =
+                ViewportPlacement::pixels(rect, self.window)
+                    .map(|value| value as f32);
+            render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+            render_pass.set_bind_group(0, &viewport.bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+    }
+}
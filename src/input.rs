@@ -0,0 +1,374 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use winit::{
+    event::{
+        ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent,
+    },
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+/// Key or mouse button bound to an action
+///
+/// Named after `winit::keyboard::KeyCode` for the keys (`KeyW`, `ArrowUp`,
+/// `Space`, `F11`...) and `Mouse` followed by the button for the mouse
+/// (`MouseLeft`, `MouseRight`, `MouseMiddle`, `MouseBack`, `MouseForward`,
+/// `Mouse8`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Input {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl Input {
+    /// The input pressed or released by a window event, the repeated key
+    /// presses are ignored
+    pub fn from_event(event: &WindowEvent) -> Option<(Self, ElementState)> {
+        match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => Some((Self::Key(*key), *state)),
+            WindowEvent::MouseInput { button, state, .. } => {
+                Some((Self::Mouse(*button), *state))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Input of a window, in a form that can be recorded and replayed
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InputEvent {
+    /// A key or a mouse button is pressed or released
+    Input {
+        input: Input,
+        state: ElementState,
+    },
+    /// Raw motion of the mouse, not limited to the window
+    MouseMotion {
+        delta: (f64, f64),
+    },
+    Scroll {
+        delta: MouseScrollDelta,
+    },
+    Resize {
+        width: u32,
+        height: u32,
+    },
+}
+
+impl InputEvent {
+    /// The input of a window event, the repeated key presses are ignored
+    pub fn from_event(event: &WindowEvent) -> Option<Self> {
+        match event {
+            WindowEvent::MouseWheel { delta, .. } => {
+                Some(Self::Scroll { delta: *delta })
+            }
+            WindowEvent::Resized(size) => Some(Self::Resize {
+                width: size.width,
+                height: size.height,
+            }),
+            event => Input::from_event(event)
+                .map(|(input, state)| Self::Input { input, state }),
+        }
+    }
+}
+
+impl std::str::FromStr for Input {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("unknown key or mouse button {:?}", value);
+        if let Some(button) = value.strip_prefix("Mouse") {
+            return Ok(Self::Mouse(match button {
+                "Left" => MouseButton::Left,
+                "Right" => MouseButton::Right,
+                "Middle" => MouseButton::Middle,
+                "Back" => MouseButton::Back,
+                "Forward" => MouseButton::Forward,
+                other => {
+                    MouseButton::Other(other.parse().map_err(|_| invalid())?)
+                }
+            }));
+        }
+        let deserializer = serde::de::value::StrDeserializer::<
+            serde::de::value::Error,
+        >::new(value);
+        KeyCode::deserialize(deserializer)
+            .map(Self::Key)
+            .map_err(|_| invalid())
+    }
+}
+
+impl TryFrom<String> for Input {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl std::fmt::Display for Input {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Key(key) => write!(f, "{:?}", key),
+            Self::Mouse(MouseButton::Other(button)) => {
+                write!(f, "Mouse{}", button)
+            }
+            Self::Mouse(button) => write!(f, "Mouse{:?}", button),
+        }
+    }
+}
+
+impl From<Input> for String {
+    fn from(value: Input) -> Self {
+        value.to_string()
+    }
+}
+
+/// What the inputs do
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    /// The mouse rotates the camera while held
+    RotateCamera,
+    ResetCamera,
+    ToggleFullscreen,
+    /// Switch to the next present mode supported by the surface
+    NextPresentMode,
+    Quit,
+    /// Registered by a scene, see `Scene::actions`
+    Scene(String),
+}
+
+impl Action {
+    pub const BUILTIN: [Action; 11] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::RotateCamera,
+        Action::ResetCamera,
+        Action::ToggleFullscreen,
+        Action::NextPresentMode,
+        Action::Quit,
+    ];
+
+    /// Name in the configuration file and the debug window
+    pub fn name(&self) -> &str {
+        match self {
+            Action::MoveForward => "move-forward",
+            Action::MoveBackward => "move-backward",
+            Action::MoveLeft => "move-left",
+            Action::MoveRight => "move-right",
+            Action::MoveUp => "move-up",
+            Action::MoveDown => "move-down",
+            Action::RotateCamera => "rotate-camera",
+            Action::ResetCamera => "reset-camera",
+            Action::ToggleFullscreen => "toggle-fullscreen",
+            Action::NextPresentMode => "next-present-mode",
+            Action::Quit => "quit",
+            Action::Scene(name) => name,
+        }
+    }
+
+    /// The built-in action with this name, else the action of a scene
+    ///
+    /// The names of the former `[keys]` section (`forward`, `fullscreen`,
+    /// `present_mode`...) are still accepted.
+    pub fn from_name(name: &str) -> Self {
+        let name = match name {
+            "forward" | "backward" | "left" | "right" | "up" | "down" => {
+                format!("move-{}", name)
+            }
+            "fullscreen" => "toggle-fullscreen".to_string(),
+            "present_mode" => "next-present-mode".to_string(),
+            name => name.to_string(),
+        };
+        Self::BUILTIN
+            .into_iter()
+            .find(|action| action.name() == name)
+            .unwrap_or(Action::Scene(name))
+    }
+}
+
+/// Inputs bound to each action
+///
+/// In the configuration file, a table from the names of the actions to the
+/// names of their inputs. The actions missing from the file keep their
+/// default inputs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    actions: BTreeMap<Action, Vec<Input>>,
+}
+
+impl Bindings {
+    pub fn inputs(&self, action: &Action) -> &[Input] {
+        self.actions.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn set(&mut self, action: Action, inputs: Vec<Input>) {
+        self.actions.insert(action, inputs);
+    }
+
+    /// Add an action with its default inputs, the inputs already bound to it
+    /// are kept
+    pub fn register(&mut self, action: Action, inputs: Vec<Input>) {
+        self.actions.entry(action).or_insert(inputs);
+    }
+
+    /// Actions triggered by an input
+    pub fn actions(&self, input: Input) -> impl Iterator<Item = &Action> {
+        self.actions
+            .iter()
+            .filter(move |(_, inputs)| inputs.contains(&input))
+            .map(|(action, _)| action)
+    }
+
+    pub fn is_bound(&self, action: &Action, input: Input) -> bool {
+        self.inputs(action).contains(&input)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Action, &[Input])> {
+        self.actions
+            .iter()
+            .map(|(action, inputs)| (action, inputs.as_slice()))
+    }
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Input::{Key, Mouse};
+        let actions = [
+            (
+                Action::MoveForward,
+                vec![Key(KeyCode::KeyW), Key(KeyCode::ArrowUp)],
+            ),
+            (
+                Action::MoveBackward,
+                vec![Key(KeyCode::KeyS), Key(KeyCode::ArrowDown)],
+            ),
+            (
+                Action::MoveLeft,
+                vec![Key(KeyCode::KeyA), Key(KeyCode::ArrowLeft)],
+            ),
+            (
+                Action::MoveRight,
+                vec![Key(KeyCode::KeyD), Key(KeyCode::ArrowRight)],
+            ),
+            (Action::MoveUp, vec![Key(KeyCode::Space)]),
+            (Action::MoveDown, vec![Key(KeyCode::ShiftLeft)]),
+            (Action::RotateCamera, vec![Mouse(MouseButton::Left)]),
+            (Action::ResetCamera, vec![Key(KeyCode::KeyR)]),
+            (Action::ToggleFullscreen, vec![Key(KeyCode::F11)]),
+            (Action::NextPresentMode, vec![Key(KeyCode::KeyV)]),
+            (Action::Quit, vec![Key(KeyCode::Escape)]),
+        ];
+        Self {
+            actions: actions.into_iter().collect(),
+        }
+    }
+}
+
+impl Serialize for Bindings {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.actions
+                .iter()
+                .map(|(action, inputs)| (action.name(), inputs)),
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for Bindings {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let mut bindings = Self::default();
+        for (name, inputs) in
+            BTreeMap::<String, Vec<Input>>::deserialize(deserializer)?
+        {
+            bindings.set(Action::from_name(&name), inputs);
+        }
+        Ok(bindings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_names() {
+        for name in ["KeyW", "F11", "Space", "MouseLeft", "MouseBack", "Mouse8"]
+        {
+            let input = name.parse::<Input>().unwrap();
+            assert_eq!(input.to_string(), name);
+        }
+        assert_eq!("MouseRight".parse(), Ok(Input::Mouse(MouseButton::Right)));
+        assert!("KeyWW".parse::<Input>().is_err());
+        assert!("MouseSide".parse::<Input>().is_err());
+    }
+
+    #[test]
+    fn test_bindings() {
+        let bindings: Bindings = toml::from_str(
+            r#"
+            quit = ["KeyQ", "MouseMiddle"]
+            jump = ["Space"]
+            fullscreen = ["F10"]
+            "#,
+        )
+        .unwrap();
+        let quit = Input::Key(KeyCode::KeyQ);
+        assert!(bindings.is_bound(&Action::Quit, quit));
+        assert!(!bindings.is_bound(&Action::Quit, Input::Key(KeyCode::Escape)));
+        assert_eq!(
+            bindings.inputs(&Action::ToggleFullscreen),
+            [Input::Key(KeyCode::F10)]
+        );
+        // The other actions keep their default inputs
+        assert_eq!(
+            bindings.inputs(&Action::ResetCamera),
+            [Input::Key(KeyCode::KeyR)]
+        );
+        let space = Input::Key(KeyCode::Space);
+        assert_eq!(
+            bindings.actions(space).collect::<Vec<_>>(),
+            [&Action::MoveUp, &Action::Scene("jump".to_string())]
+        );
+
+        // A scene does not override the inputs of the configuration
+        let mut registered = bindings.clone();
+        registered.register(Action::Scene("jump".to_string()), vec![quit]);
+        registered.register(Action::Scene("fire".to_string()), vec![quit]);
+        assert_eq!(
+            registered.inputs(&Action::Scene("jump".to_string())),
+            [space]
+        );
+        assert_eq!(registered.actions(quit).count(), 2);
+
+        let dumped: Bindings =
+            toml::from_str(&toml::to_string(&bindings).unwrap()).unwrap();
+        assert_eq!(dumped, bindings);
+    }
+}
@@ -1,16 +1,29 @@
 mod app;
+mod benchmark;
 mod config;
 mod debug;
+mod error;
 mod graphics;
+mod input;
+mod recording;
 mod render;
 mod scene;
 mod utils;
 
 pub use app::App;
-pub use config::Config;
+pub use config::{Config, WindowConfig, WindowContent};
 pub use debug::widget::Logger;
+pub use error::Error;
+pub use graphics::culling::Aabb;
+pub use graphics::graph::{Node, SceneGraph};
+pub use graphics::lines::{
+    debug_aabb, debug_line, debug_polyline, debug_sphere, LineStyle, Polyline,
+};
+pub use graphics::points::{Point, PointCloud, PointShape, PointSizing};
 pub use graphics::shapes::Shape;
+pub use graphics::text::{debug_label, debug_text, hud_text, Text, TextAnchor};
 pub use graphics::types::Instance;
+pub use input::{Action, Bindings, Input, InputEvent};
 pub use render::Renderer;
 pub use scene::Scene;
 
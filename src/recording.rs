@@ -0,0 +1,183 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::input::InputEvent;
+
+/// Inputs received before a frame and the time step of its update
+///
+/// A recording holds one frame per line, in JSON, so the frames written
+/// before a crash can be replayed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedFrame {
+    /// Time since the start of the recording
+    pub time: Duration,
+    /// Time step given to `Renderer::step`
+    pub dt: Duration,
+    pub events: Vec<InputEvent>,
+    /// Input of the debug interface of the main window, with the events it
+    /// used and kept from `events`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interface: Option<egui::RawInput>,
+}
+
+/// Writes the inputs of the main window and the time steps of its frames
+pub struct Recorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    started: Instant,
+    events: Vec<InputEvent>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Self, crate::Error> {
+        let file =
+            File::create(path).map_err(|source| crate::Error::Record {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(file),
+            started: Instant::now(),
+            events: Vec::new(),
+        })
+    }
+
+    /// Keep an input for the next frame
+    pub fn record(&mut self, event: InputEvent) {
+        self.events.push(event);
+    }
+
+    /// Write the frame updated with `dt`, the inputs received before it and
+    /// the input of its debug interface
+    pub fn end_frame(
+        &mut self,
+        dt: Duration,
+        interface: Option<egui::RawInput>,
+    ) -> Result<(), crate::Error> {
+        let frame = RecordedFrame {
+            time: self.started.elapsed(),
+            dt,
+            events: std::mem::take(&mut self.events),
+            interface,
+        };
+        let line = serde_json::to_string(&frame).unwrap_or_default();
+        writeln!(self.writer, "{}", line)
+            .and_then(|_| self.writer.flush())
+            .map_err(|source| crate::Error::Record {
+                path: self.path.clone(),
+                source,
+            })
+    }
+}
+
+/// Frames of a recording, given back one by one
+pub struct Replay {
+    frames: std::vec::IntoIter<RecordedFrame>,
+}
+
+impl Replay {
+    pub fn open(path: &Path) -> Result<Self, crate::Error> {
+        let file = File::open(path)
+            .map_err(|error| crate::Error::asset(path, error))?;
+        let frames = parse(BufReader::new(file))
+            .map_err(|error| crate::Error::asset(path, error))?;
+        log::info!("Replaying {} frames of {}", frames.len(), path.display());
+        Ok(Self {
+            frames: frames.into_iter(),
+        })
+    }
+
+    pub fn next_frame(&mut self) -> Option<RecordedFrame> {
+        self.frames.next()
+    }
+}
+
+/// The frames of a recording, the empty lines are skipped
+fn parse(reader: impl BufRead) -> Result<Vec<RecordedFrame>, String> {
+    let mut frames = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|error| error.to_string())?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let frame = serde_json::from_str(&line)
+            .map_err(|error| format!("line {}: {}", index + 1, error))?;
+        frames.push(frame);
+    }
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::{
+        event::{ElementState, MouseButton, MouseScrollDelta},
+        keyboard::KeyCode,
+    };
+
+    use super::*;
+    use crate::input::Input;
+
+    #[test]
+    fn test_round_trip() {
+        let frames = vec![
+            RecordedFrame {
+                time: Duration::from_millis(16),
+                dt: Duration::from_nanos(16_666_667),
+                events: vec![
+                    InputEvent::Input {
+                        input: Input::Key(KeyCode::KeyW),
+                        state: ElementState::Pressed,
+                    },
+                    InputEvent::Scroll {
+                        delta: MouseScrollDelta::LineDelta(0.0, -1.0),
+                    },
+                ],
+                interface: None,
+            },
+            RecordedFrame {
+                time: Duration::from_millis(33),
+                dt: Duration::from_nanos(16_912_003),
+                events: vec![
+                    InputEvent::Input {
+                        input: Input::Mouse(MouseButton::Left),
+                        state: ElementState::Released,
+                    },
+                    InputEvent::MouseMotion {
+                        delta: (1.5, -0.25),
+                    },
+                    InputEvent::Resize {
+                        width: 1280,
+                        height: 720,
+                    },
+                ],
+                // A click in the debug window
+                interface: Some(egui::RawInput {
+                    events: vec![egui::Event::PointerButton {
+                        pos: egui::pos2(12.0, 40.0),
+                        button: egui::PointerButton::Primary,
+                        pressed: true,
+                        modifiers: egui::Modifiers::NONE,
+                    }],
+                    time: Some(1.25),
+                    ..Default::default()
+                }),
+            },
+        ];
+        let text = frames
+            .iter()
+            .map(|frame| serde_json::to_string(frame).unwrap() + "\n\n")
+            .collect::<String>();
+        // The time steps are replayed exactly
+        assert_eq!(parse(text.as_bytes()).unwrap(), frames);
+
+        let error = parse("{}\n".as_bytes()).unwrap_err();
+        assert!(error.starts_with("line 1:"), "{}", error);
+    }
+}
@@ -1,21 +1,33 @@
 use std::borrow::Borrow;
 use std::cell::RefCell;
-use std::error::Error;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
 use wgpu::core::instance;
 
-use crate::config::{Config, WindowSizeHint};
-use crate::graphics::shapes::Shape;
+use crate::config::{
+    Config, DisplayConfig, FullscreenMode, VideoModeSelection, WindowSizeHint,
+};
+use crate::graphics::debug_draw::DebugDraws;
+use crate::graphics::points::{PointBuffer, PointCloud};
+use crate::graphics::profiler::GpuPass;
+use crate::graphics::shapes::{Shape, ShapeRevision};
 use crate::graphics::types::{Buffer, Instance};
+use crate::graphics::viewport::ViewportPlacement;
+use crate::input::InputEvent;
 use crate::{debug, elapsed_handler, graphics, scene, Scene};
 
-pub struct GraphicalProcessUnit<'a> {
-    pub surface: wgpu::Surface<'a>,
-    pub device: wgpu::Device,
-    pub queue: wgpu::Queue,
+pub struct GraphicalProcessUnit {
+    pub surface: wgpu::Surface<'static>,
+    /// Shared by the renderers of every window
+    pub device: Arc<wgpu::Device>,
+    pub queue: Arc<wgpu::Queue>,
     pub config: wgpu::SurfaceConfiguration,
+    pub capabilities: graphics::Capabilities,
+    /// What the surface supports with the adapter, kept to change the
+    /// present mode at runtime
+    pub surface_caps: wgpu::SurfaceCapabilities,
 }
 
 pub struct DrawPipeline<'a> {
@@ -23,27 +35,89 @@ pub struct DrawPipeline<'a> {
     pub window: &'a winit::window::Window,
     pub view: &'a wgpu::TextureView,
     pub screen: &'a egui_wgpu::ScreenDescriptor,
+    pub timestamp_writes: Option<wgpu::RenderPassTimestampWrites<'a>>,
 }
 
-pub struct Renderer<'a> {
+/// Where a texture registered in the debug window comes from, to register
+/// it again when the device is recreated
+enum DebugTexture {
+    External,
+    ShadowPreview,
+    Viewport(usize),
+}
+
+impl DebugTexture {
+    /// The texture in `pipeline`, `None` for the external textures
+    fn view<'p>(
+        &self,
+        pipeline: &'p graphics::Pipeline,
+    ) -> Option<&'p wgpu::TextureView> {
+        match self {
+            DebugTexture::External => None,
+            DebugTexture::ShadowPreview => Some(pipeline.shadow.preview_view()),
+            DebugTexture::Viewport(index) => pipeline
+                .viewports
+                .get(*index)
+                .map(|viewport| viewport.view()),
+        }
+    }
+}
+
+/// Device requested from an adapter, shared by the renderers of the windows
+#[derive(Clone)]
+struct SharedDevice {
+    instance: Arc<wgpu::Instance>,
+    adapter: Arc<wgpu::Adapter>,
+    device: Arc<wgpu::Device>,
+    queue: Arc<wgpu::Queue>,
+    capabilities: graphics::Capabilities,
+    device_lost: Arc<Mutex<Option<String>>>,
+}
+
+/// Draws the scenes and the debug interface into a window
+///
+/// The first renderer creates the device, the renderers of the other windows
+/// are opened from it with `open` and share its device.
+pub struct Renderer {
     // Graphics Devices
-    gpu: GraphicalProcessUnit<'a>,
+    instance: Arc<wgpu::Instance>,
+    adapter: Arc<wgpu::Adapter>,
+    gpu: GraphicalProcessUnit,
+    /// Set when the device is lost, with the reason, shared by the
+    /// renderers of every window
+    device_lost: Arc<Mutex<Option<String>>>,
 
     // Graphic Pipeline
+    shader: &'static str,
     pub pipeline: graphics::Pipeline,
+    /// Only the debug interface is drawn when unset
+    draw_scene: bool,
+    /// Custom post effects, added again when the device is recreated
+    post_effects: Vec<(String, String, [f32; 4])>,
     // pub shapes: Option<&'a Vec<graphics::shapes::Shape>>,
     pub shapes: Vec<Rc<RefCell<Shape>>>,
     pub buffers: Vec<Option<Buffer>>,
+    /// Revision of each shape in `buffers`, see `Shape::buffer`
+    shape_revisions: Vec<ShapeRevision>,
+    pub point_clouds: Vec<Rc<RefCell<PointCloud>>>,
+    pub point_buffers: Vec<Option<PointBuffer>>,
+    point_revisions: Vec<u64>,
+    /// Lines and texts queued by the scenes updated with this renderer
+    debug_draws: Rc<RefCell<DebugDraws>>,
+    /// Lines and texts drawn, those of the main window for
+    /// `WindowContent::MainScenes`
+    shown_debug_draws: Rc<RefCell<DebugDraws>>,
 
     // Debug window renderer
     pub debug_renderer: Option<debug::DebugRenderer>,
     debug_window: debug::Debug,
+    debug_textures: Vec<DebugTexture>,
 
     // Fullscreen
     is_fullscreen: bool,
 
     // Winit stuff
-    pub window: &'a winit::window::Window,
+    pub window: Arc<winit::window::Window>,
     size: winit::dpi::PhysicalSize<u32>,
 
     // Config
@@ -56,50 +130,229 @@ pub struct Renderer<'a> {
     last_update_instant: std::time::Instant,
 }
 
-impl<'a> Renderer<'a> {
+impl Renderer {
     pub async fn new(
-        window: &'a winit::window::Window,
+        window: Arc<winit::window::Window>,
         app_config: Config,
         shader: &'static str,
-    ) -> Result<Self, Box<dyn Error>> {
+    ) -> Result<Self, crate::Error> {
+        let instance =
+            Arc::new(wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends: app_config.backends,
+                ..Default::default()
+            }));
+
+        let surface = instance.create_surface(window.clone())?;
+
+        let device_lost = Arc::new(Mutex::new(None));
+        let shared =
+            Self::request_device(instance, &surface, &app_config, device_lost)
+                .await?;
+
+        Self::init(window, surface, app_config, shader, shared, true)
+    }
+
+    /// Create the renderer of another window, drawing with the device of
+    /// this renderer
+    ///
+    /// The window has its own surface, pipeline (and so camera) and debug
+    /// interface, configured by `app_config`. When `draw_scene` is unset,
+    /// the window only shows its debug interface.
+    pub fn open(
+        &self,
+        window: Arc<winit::window::Window>,
+        app_config: Config,
+        draw_scene: bool,
+    ) -> Result<Self, crate::Error> {
+        let surface = self.instance.create_surface(window.clone())?;
+        if !self.adapter.is_surface_supported(&surface) {
+            return Err(crate::Error::AdapterIncompatible(
+                self.adapter.get_info().name,
+            ));
+        }
+        Self::init(
+            window,
+            surface,
+            app_config,
+            self.shader,
+            self.shared(),
+            draw_scene,
+        )
+    }
+
+    fn init(
+        window: Arc<winit::window::Window>,
+        surface: wgpu::Surface<'static>,
+        app_config: Config,
+        shader: &'static str,
+        shared: SharedDevice,
+        draw_scene: bool,
+    ) -> Result<Self, crate::Error> {
         let size = window.inner_size();
+        let (config, surface_caps) =
+            Self::configure_surface(&shared, &surface, &app_config, size)?;
 
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: app_config.backends,
-            ..Default::default()
+        // Setup the debug renderer
+        let debug_renderer = (!app_config.disable_egui).then(|| {
+            debug::DebugRenderer::new(
+                &shared.device,
+                config.format,
+                None,
+                1,
+                &window,
+            )
         });
 
-        let surface = instance.create_surface(window)?;
+        // Setup the debug window
+        let debug_window = debug::Debug::init(&app_config.debug);
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .ok_or("No suitable adapter found!")?;
+        let gpu = GraphicalProcessUnit {
+            surface,
+            device: shared.device,
+            queue: shared.queue,
+            config,
+            capabilities: shared.capabilities,
+            surface_caps,
+        };
+
+        // Setup the graphics pipeline
+        let pipeline = graphics::Pipeline::init(&gpu, shader, &app_config)?;
+        let debug_draws = Rc::new(RefCell::new(DebugDraws::default()));
+
+        Ok(Self {
+            instance: shared.instance,
+            adapter: shared.adapter,
+            gpu,
+            device_lost: shared.device_lost,
+            shader,
+            pipeline,
+            draw_scene,
+            post_effects: Vec::new(),
+            shapes: Vec::new(),
+            buffers: Vec::new(),
+            shape_revisions: Vec::new(),
+            point_clouds: Vec::new(),
+            point_buffers: Vec::new(),
+            point_revisions: Vec::new(),
+            debug_draws: debug_draws.clone(),
+            shown_debug_draws: debug_draws,
+            debug_renderer,
+            debug_window,
+            debug_textures: Vec::new(),
+            window,
+            size,
+            is_fullscreen: false,
+            config: app_config,
+            mouse_pressed: false,
+            last_update_instant: std::time::Instant::now(),
+        })
+    }
 
+    /// The device of the renderer, to share it with another window
+    fn shared(&self) -> SharedDevice {
+        SharedDevice {
+            instance: self.instance.clone(),
+            adapter: self.adapter.clone(),
+            device: self.gpu.device.clone(),
+            queue: self.gpu.queue.clone(),
+            capabilities: self.gpu.capabilities.clone(),
+            device_lost: self.device_lost.clone(),
+        }
+    }
+
+    /// Create a device able to draw on the surface
+    ///
+    /// The device has the optional features and the limits negotiated with
+    /// the adapter, see `Capabilities::negotiate`. The loss of the device is reported into `device_lost`.
+    async fn request_device(
+        instance: Arc<wgpu::Instance>,
+        surface: &wgpu::Surface<'_>,
+        app_config: &Config,
+        device_lost: Arc<Mutex<Option<String>>>,
+    ) -> Result<SharedDevice, crate::Error> {
+        let adapter =
+            Self::select_adapter(&instance, surface, app_config).await?;
+        let info = adapter.get_info();
+        log::info!(
+            "Using adapter {} ({:?}, {:?})",
+            info.name,
+            info.backend,
+            info.device_type
+        );
+
+        let capabilities = graphics::Capabilities::negotiate(&adapter);
+        capabilities.log();
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
+                    required_features: capabilities.features,
+                    required_limits: capabilities.limits.clone(),
                 },
                 None,
             )
             .await?;
 
-        let surface_caps = surface.get_capabilities(&adapter);
+        let lost = device_lost.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            // Also called when the device is dropped or its callback replaced
+            if matches!(
+                reason,
+                wgpu::DeviceLostReason::Unknown
+                    | wgpu::DeviceLostReason::Destroyed
+            ) {
+                log::error!("Device lost ({:?}): {}", reason, message);
+                if let Ok(mut lost) = lost.lock() {
+                    lost.get_or_insert(message);
+                }
+            }
+        });
+        let lost = device_lost.clone();
+        device.on_uncaptured_error(Box::new(move |error| {
+            let Ok(mut lost) = lost.lock() else {
+                return;
+            };
+            match error {
+                // Every call fails once the device is lost
+                _ if lost.is_some() => log::debug!("{}", error),
+                wgpu::Error::OutOfMemory { .. } => {
+                    log::error!("{}", error);
+                    *lost = Some(error.to_string());
+                }
+                _ => log::error!("{}", error),
+            }
+        }));
+
+        Ok(SharedDevice {
+            instance,
+            adapter: Arc::new(adapter),
+            device: Arc::new(device),
+            queue: Arc::new(queue),
+            capabilities,
+            device_lost,
+        })
+    }
+
+    /// Configure a surface to be drawn with the device
+    fn configure_surface(
+        shared: &SharedDevice,
+        surface: &wgpu::Surface<'_>,
+        app_config: &Config,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> Result<
+        (wgpu::SurfaceConfiguration, wgpu::SurfaceCapabilities),
+        crate::Error,
+    > {
+        let surface_caps = surface.get_capabilities(&shared.adapter);
         let surface_format = surface_caps
             .formats
             .iter()
             .copied()
             .find(|f| f.is_srgb())
-            .ok_or("No sRGB format found on surface!")?;
-        let selected_present_mode =
-            app_config.to_wgpu_present_mode(&surface_caps)?;
+            .ok_or(crate::Error::NoSurfaceFormat)?;
+        let selected_present_mode = app_config
+            .to_wgpu_present_mode(&surface_caps)
+            .map_err(crate::Error::PresentMode)?;
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
@@ -109,43 +362,172 @@ impl<'a> Renderer<'a> {
             present_mode: selected_present_mode,
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
-            desired_maximum_frame_latency: 2, // TODO - what is this? (among every other thing I do not understand in this codebase)
+            desired_maximum_frame_latency: app_config.frame_latency,
         };
 
-        surface.configure(&device, &config);
+        surface.configure(&shared.device, &config);
 
-        // Setup the debug renderer
-        let debug_renderer = (!app_config.disable_egui).then(|| {
-            debug::DebugRenderer::new(&device, config.format, None, 1, window)
-        });
-
-        // Setup the debug window
-        let debug_window = debug::Debug::init();
+        Ok((config, surface_caps))
+    }
 
-        let gpu = GraphicalProcessUnit {
-            surface,
-            device,
-            queue,
-            config,
+    /// The adapter selected in the configuration, else the one matching its
+    /// preferences
+    async fn select_adapter(
+        instance: &wgpu::Instance,
+        surface: &wgpu::Surface<'_>,
+        app_config: &Config,
+    ) -> Result<wgpu::Adapter, crate::Error> {
+        let config = &app_config.adapter;
+        let Some(select) = &config.select else {
+            return instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: config.power_preference.to_wgpu(),
+                    compatible_surface: Some(surface),
+                    force_fallback_adapter: config.force_fallback,
+                })
+                .await
+                .ok_or(crate::Error::NoAdapter);
         };
+        let adapters = instance.enumerate_adapters(app_config.backends);
+        let adapter = match select.parse::<usize>() {
+            Ok(index) => adapters.into_iter().nth(index),
+            Err(_) => {
+                let name = select.to_lowercase();
+                adapters.into_iter().find(|adapter| {
+                    adapter.get_info().name.to_lowercase().contains(&name)
+                })
+            }
+        }
+        .ok_or_else(|| crate::Error::AdapterNotFound(select.clone()))?;
+        if !adapter.is_surface_supported(surface) {
+            return Err(crate::Error::AdapterIncompatible(
+                adapter.get_info().name,
+            ));
+        }
+        Ok(adapter)
+    }
 
-        // Setup the graphics pipeline
-        let pipeline = graphics::Pipeline::init(&gpu, shader)?;
+    /// Message of the device loss, if the device has been lost
+    pub fn device_lost(&self) -> Option<String> {
+        self.device_lost.lock().ok().and_then(|lost| lost.clone())
+    }
 
-        Ok(Self {
-            gpu,
-            pipeline,
-            shapes: Vec::new(),
-            buffers: Vec::new(),
-            debug_renderer,
-            debug_window,
-            window,
-            size,
-            is_fullscreen: false,
-            config: app_config,
-            mouse_pressed: false,
-            last_update_instant: std::time::Instant::now(),
-        })
+    /// Recreate the device and everything living on it after a device loss
+    ///
+    /// The pipeline is created again from the configuration, keeping the
+    /// camera and the settings changed at runtime, and the shape and point
+    /// buffers are reloaded from their CPU data. The textures given to
+    /// `register_texture` cannot be restored, the debug window shows an
+    /// empty texture instead.
+    ///
+    /// The renderers of the other windows still draw with the lost device,
+    /// they must then be given the new one with `recover_shared`.
+    pub fn recover(&mut self) -> Result<(), crate::Error> {
+        log::warn!("Recreating the device");
+        let shared = pollster::block_on(Self::request_device(
+            self.instance.clone(),
+            &self.gpu.surface,
+            &self.config,
+            self.device_lost.clone(),
+        ))?;
+        if let Ok(mut lost) = self.device_lost.lock() {
+            *lost = None;
+        }
+        self.rebuild(shared, None)
+    }
+
+    /// Draw with the device recreated by `main` after a device loss, see
+    /// `recover`
+    ///
+    /// The textures of the pipeline registered in the debug window are taken
+    /// from `main`, for a debug interface detached from the main window.
+    pub fn recover_shared(
+        &mut self,
+        main: &Renderer,
+    ) -> Result<(), crate::Error> {
+        self.rebuild(main.shared(), Some(&main.pipeline))
+    }
+
+    /// Recreate everything living on the device with a new device
+    ///
+    /// The textures of the pipeline registered in the debug window are taken
+    /// from `texture_source`, else from the new pipeline.
+    fn rebuild(
+        &mut self,
+        shared: SharedDevice,
+        texture_source: Option<&graphics::Pipeline>,
+    ) -> Result<(), crate::Error> {
+        let (config, surface_caps) = Self::configure_surface(
+            &shared,
+            &self.gpu.surface,
+            &self.config,
+            self.size,
+        )?;
+        // Keep the present mode and the latency changed at runtime
+        let present_mode = self.gpu.config.present_mode;
+        let frame_latency = self.gpu.config.desired_maximum_frame_latency;
+        self.adapter = shared.adapter;
+        self.gpu.device = shared.device;
+        self.gpu.queue = shared.queue;
+        self.gpu.config = config;
+        self.gpu.capabilities = shared.capabilities;
+        self.gpu.surface_caps = surface_caps;
+        self.set_present_mode(present_mode);
+        self.set_frame_latency(frame_latency);
+
+        let mut pipeline =
+            graphics::Pipeline::init(&self.gpu, self.shader, &self.config)?;
+        // The effects compiled once already
+        for (name, source, params) in self.post_effects.iter() {
+            pipeline.post.add_effect(
+                &self.gpu.device,
+                name,
+                source,
+                *params,
+            )?;
+        }
+        pipeline.restore(&self.gpu.device, &self.pipeline);
+        self.pipeline = pipeline;
+
+        if let Some(lost) = &self.debug_renderer {
+            let mut debug_renderer = debug::DebugRenderer::new(
+                &self.gpu.device,
+                self.gpu.config.format,
+                None,
+                1,
+                &self.window,
+            );
+            debug_renderer.set_recording(lost.is_recording());
+            // Registered in the same order, the textures keep their ids
+            let empty =
+                self.gpu.device.create_texture(&wgpu::TextureDescriptor {
+                    label: Some("Empty Debug Texture"),
+                    size: wgpu::Extent3d::default(),
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                });
+            let empty = empty.create_view(&Default::default());
+            let texture_source = texture_source.unwrap_or(&self.pipeline);
+            for texture in self.debug_textures.iter() {
+                let view = texture.view(texture_source).unwrap_or(&empty);
+                debug_renderer.register_texture(&self.gpu.device, view);
+            }
+            self.debug_renderer = Some(debug_renderer);
+        }
+
+        self.buffers.iter_mut().for_each(|buffer| *buffer = None);
+        self.shape_revisions.fill(ShapeRevision::default());
+        self.point_buffers
+            .iter_mut()
+            .for_each(|buffer| *buffer = None);
+        self.point_revisions.fill(0);
+        self.load_shapes();
+        self.load_point_clouds();
+        Ok(())
     }
 
     pub fn size(&self) -> winit::dpi::PhysicalSize<u32> {
@@ -159,37 +541,188 @@ impl<'a> Renderer<'a> {
     }
 
     pub fn window(&self) -> &winit::window::Window {
-        self.window
+        &self.window
     }
 
     pub fn debug(&mut self) -> &mut debug::Debug {
         &mut self.debug_window
     }
 
+    /// Make a texture available to the debug window
+    ///
+    /// Returns `None` if the debug renderer is disabled.
+    pub fn register_texture(
+        &mut self,
+        view: &wgpu::TextureView,
+    ) -> Option<egui::TextureId> {
+        let device = &self.gpu.device;
+        let texture = self.debug_renderer.as_mut().map(|debug_renderer| {
+            debug_renderer.register_texture(device, view)
+        });
+        self.debug_textures.push(DebugTexture::External);
+        texture
+    }
+
+    /// Make the shadow map preview available to the debug window
+    ///
+    /// The preview is the one of `source` when the debug interface is
+    /// detached from the window drawing the scene, else of this renderer.
+    pub fn register_shadow_preview(
+        &mut self,
+        source: Option<&Renderer>,
+    ) -> Option<egui::TextureId> {
+        let device = &self.gpu.device;
+        let pipeline = source.map_or(&self.pipeline, |source| &source.pipeline);
+        let texture = DebugTexture::ShadowPreview;
+        let view = texture.view(pipeline)?;
+        let id = self.debug_renderer.as_mut().map(|debug_renderer| {
+            debug_renderer.register_texture(device, view)
+        });
+        self.debug_textures.push(texture);
+        id
+    }
+
+    /// Make the viewports rendered into textures available to the debug
+    /// window, returns their names and textures
+    ///
+    /// The viewports are the ones of `source`, see `register_shadow_preview`.
+    pub fn register_viewports(
+        &mut self,
+        source: Option<&Renderer>,
+    ) -> Vec<(String, Option<egui::TextureId>)> {
+        let device = &self.gpu.device;
+        let viewports = &source
+            .map_or(&self.pipeline, |source| &source.pipeline)
+            .viewports;
+        let debug_renderer = &mut self.debug_renderer;
+        let debug_textures = &mut self.debug_textures;
+        viewports
+            .iter()
+            .enumerate()
+            .filter(|(_, viewport)| {
+                matches!(
+                    viewport.placement(),
+                    ViewportPlacement::Texture { .. }
+                )
+            })
+            .map(|(index, viewport)| {
+                let texture = debug_renderer.as_mut().map(|debug_renderer| {
+                    debug_renderer.register_texture(device, viewport.view())
+                });
+                debug_textures.push(DebugTexture::Viewport(index));
+                (viewport.name().to_string(), texture)
+            })
+            .collect()
+    }
+
+    /// Append a custom effect at the end of the post-processing chain, an
+    /// effect which does not compile is an `Error::Shader`
+    pub fn add_post_effect(
+        &mut self,
+        name: &str,
+        source: &str,
+        params: [f32; 4],
+    ) -> Result<(), crate::Error> {
+        self.pipeline.post.add_effect(
+            &self.gpu.device,
+            name,
+            source,
+            params,
+        )?;
+        self.post_effects
+            .push((name.to_string(), source.to_string(), params));
+        Ok(())
+    }
+
+    pub fn set_shadow_resolution(&mut self, resolution: u32) {
+        self.pipeline
+            .shadow
+            .set_resolution(&self.gpu.device, resolution);
+    }
+
+    /// Replace the skybox, see `Background::load_skybox`
+    pub fn load_skybox(
+        &mut self,
+        paths: &[std::path::PathBuf],
+    ) -> Result<(), crate::Error> {
+        self.pipeline.background.load_skybox(
+            &self.gpu.device,
+            &self.gpu.queue,
+            paths,
+        )?;
+        Ok(())
+    }
+
     pub fn set_shapes(&mut self, shapes: Vec<Rc<RefCell<Shape>>>) {
         self.shapes = shapes;
         self.buffers = std::iter::repeat_with(|| None)
             .take(self.shapes.len())
             .collect::<Vec<_>>();
+        self.shape_revisions =
+            vec![ShapeRevision::default(); self.shapes.len()];
+        self.pipeline.batch.invalidate();
     }
 
-    pub fn load_shape(&mut self, index: usize) -> Result<(), Box<dyn Error>> {
+    pub fn load_shape(&mut self, index: usize) -> Result<(), crate::Error> {
         if let Some(shape) = self.shapes.get(index) {
             let shape = shape.as_ref().borrow();
-            if let Some(buffer) = shape.buffer(&self.gpu.device) {
-                self.buffers[index] = Some(buffer);
+            if shape.load(
+                &self.gpu.device,
+                &self.gpu.queue,
+                &mut self.buffers[index],
+                &mut self.shape_revisions[index],
+            ) {
+                self.pipeline.batch.invalidate();
             }
             Ok(())
         } else {
-            Err("Shape not found!".into())
+            Err(crate::Error::ShapeNotFound(index))
         }
     }
 
     pub fn load_shapes(&mut self) {
         for (i, shape) in self.shapes.iter().enumerate() {
             let shape = shape.as_ref().borrow();
-            if let Some(buffer) = shape.buffer(&self.gpu.device) {
-                self.buffers[i] = Some(buffer);
+            if shape.load(
+                &self.gpu.device,
+                &self.gpu.queue,
+                &mut self.buffers[i],
+                &mut self.shape_revisions[i],
+            ) {
+                self.pipeline.batch.invalidate();
+            }
+        }
+    }
+
+    pub fn set_point_clouds(&mut self, clouds: Vec<Rc<RefCell<PointCloud>>>) {
+        self.point_clouds = clouds;
+        self.point_buffers = std::iter::repeat_with(|| None)
+            .take(self.point_clouds.len())
+            .collect::<Vec<_>>();
+        self.point_revisions = vec![0; self.point_clouds.len()];
+    }
+
+    /// Draw the lines and texts queued by the scenes of another renderer
+    /// instead of those of its own scenes
+    pub(crate) fn share_debug_draws(&mut self, main: &Renderer) {
+        self.shown_debug_draws = main.debug_draws.clone();
+    }
+
+    /// Keep what the scenes queued with the `debug_*` functions for this
+    /// window, called after each call into the scenes out of `step`
+    pub(crate) fn collect_debug_draws(&self) {
+        self.debug_draws.borrow_mut().collect();
+    }
+
+    pub fn load_point_clouds(&mut self) {
+        for (i, cloud) in self.point_clouds.iter().enumerate() {
+            let cloud = cloud.as_ref().borrow();
+            if let Some(buffer) = cloud.buffer(
+                &self.gpu.device,
+                &self.pipeline.points,
+                &mut self.point_revisions[i],
+            ) {
+                self.point_buffers[i] = Some(buffer);
             }
         }
     }
@@ -206,17 +739,90 @@ impl<'a> Renderer<'a> {
         }
     }
 
+    /// Present modes supported by the surface
+    pub fn present_modes(&self) -> &[wgpu::PresentMode] {
+        &self.gpu.surface_caps.present_modes
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.gpu.config.present_mode
+    }
+
+    /// Reconfigure the surface with another present mode, ignored if the
+    /// surface does not support it
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) {
+        if present_mode == self.gpu.config.present_mode {
+            return;
+        }
+        if !self.present_modes().contains(&present_mode) {
+            log::warn!("Present mode {:?} not supported", present_mode);
+            return;
+        }
+        log::info!("Using present mode {:?}", present_mode);
+        self.gpu.config.present_mode = present_mode;
+        self.gpu
+            .surface
+            .configure(&self.gpu.device, &self.gpu.config);
+    }
+
+    /// Switch to the present mode following the current one in
+    /// `present_modes`
+    pub fn next_present_mode(&mut self) -> wgpu::PresentMode {
+        let present_modes = self.present_modes();
+        let next = present_modes
+            .iter()
+            .position(|mode| *mode == self.gpu.config.present_mode)
+            .map_or(0, |index| (index + 1) % present_modes.len());
+        if let Some(present_mode) = present_modes.get(next).copied() {
+            self.set_present_mode(present_mode);
+        }
+        self.present_mode()
+    }
+
+    pub fn frame_latency(&self) -> u32 {
+        self.gpu.config.desired_maximum_frame_latency
+    }
+
+    /// Reconfigure the surface with another maximum frame latency, see
+    /// `config::FRAME_LATENCIES`
+    pub fn set_frame_latency(&mut self, frame_latency: u32) {
+        if frame_latency == self.gpu.config.desired_maximum_frame_latency {
+            return;
+        }
+        log::info!("Using a frame latency of {}", frame_latency);
+        self.gpu.config.desired_maximum_frame_latency = frame_latency;
+        self.gpu
+            .surface
+            .configure(&self.gpu.device, &self.gpu.config);
+    }
+
     pub fn input(&mut self, event: &winit::event::WindowEvent) -> bool {
         // self.window().request_redraw();
+        InputEvent::from_event(event)
+            .is_some_and(|event| self.input_event(&event))
+    }
+
+    /// Handle an input, live or replayed, returns whether it has been used
+    pub fn input_event(&mut self, event: &InputEvent) -> bool {
         self.pipeline.process_input(event, &mut self.mouse_pressed)
     }
 
-    pub fn update(&mut self, scenes: &mut Vec<Box<dyn Scene>>) {
+    /// Update the pipeline and the scenes with the time since the last
+    /// update, which is returned
+    pub fn update(&mut self, scenes: &mut Vec<Box<dyn Scene>>) -> Duration {
         let dt = self.last_update_instant.elapsed();
+        self.step(scenes, dt);
+        dt
+    }
+
+    /// Update the pipeline and the scenes with a given time step, to replay
+    /// a recording
+    pub fn step(&mut self, scenes: &mut Vec<Box<dyn Scene>>, dt: Duration) {
         self.pipeline.update(&self.gpu.queue, dt);
         for scene in scenes.iter_mut() {
             scene.update(dt);
         }
+        self.debug_draws.borrow_mut().end_frame();
         self.last_update_instant = std::time::Instant::now();
     }
 
@@ -224,7 +830,10 @@ impl<'a> Renderer<'a> {
         &mut self,
         wgpu_time: &mut Duration,
         debug_time: &mut Duration,
-    ) -> Result<(), wgpu::SurfaceError> {
+    ) -> Result<(), crate::Error> {
+        if let Some(message) = self.device_lost() {
+            return Err(crate::Error::DeviceLost(message));
+        }
         let output = self.gpu.surface.get_current_texture()?;
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
             label: Some("Render View"),
@@ -237,13 +846,35 @@ impl<'a> Renderer<'a> {
             array_layer_count: None,
         });
 
+        self.pipeline.profiler.begin_frame();
         let mut encoder = self.gpu.device.create_command_encoder(
             &wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             },
         );
 
-        elapsed_handler!(*wgpu_time => self.pipeline.render(&view, &mut encoder, self.buffers.iter().filter_map(|b| b.as_ref())));
+        elapsed_handler!(*wgpu_time => {
+            if self.draw_scene {
+                self.pipeline.cull(&self.gpu.queue, self.buffers.iter_mut().filter_map(|b| b.as_mut()));
+                self.pipeline.batch.prepare(
+                    &self.gpu.device,
+                    &self.gpu.queue,
+                    &mut encoder,
+                    self.buffers.iter().filter_map(|b| b.as_ref()),
+                );
+                let debug_draws = self.shown_debug_draws.as_ref().borrow();
+                self.pipeline.lines.upload(&self.gpu.device, &self.gpu.queue, debug_draws.lines());
+                self.pipeline.text.upload(&self.gpu.device, &self.gpu.queue, debug_draws.texts());
+                self.pipeline.render(
+                    &view,
+                    &mut encoder,
+                    self.buffers.iter().filter_map(|b| b.as_ref()),
+                    self.point_buffers.iter().filter_map(|b| b.as_ref()),
+                )
+            } else {
+                Self::clear(&view, &mut encoder);
+            }
+        });
 
         if self.debug_renderer.is_some() {
             let screen_descriptor = egui_wgpu::ScreenDescriptor {
@@ -252,9 +883,13 @@ impl<'a> Renderer<'a> {
             };
             let draw_pipeline = DrawPipeline {
                 encoder: &mut encoder,
-                window: self.window,
+                window: &self.window,
                 view: &view,
                 screen: &screen_descriptor,
+                timestamp_writes: self
+                    .pipeline
+                    .profiler
+                    .timestamp_writes(GpuPass::Egui),
             };
             elapsed_handler!(
                 *debug_time =>
@@ -264,33 +899,52 @@ impl<'a> Renderer<'a> {
             );
         }
 
+        self.pipeline.profiler.resolve(&mut encoder);
         self.gpu.queue.submit(std::iter::once(encoder.finish()));
+        self.pipeline.profiler.map();
         output.present();
+        // Read the timings of the previous frames which are done
+        self.pipeline.profiler.poll(&self.gpu.device);
 
         Ok(())
     }
 
-    pub fn update_size(&mut self) {
+    /// Clear the surface of a window drawing only its debug interface
+    fn clear(view: &wgpu::TextureView, encoder: &mut wgpu::CommandEncoder) {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Clear Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+    }
+
+    pub fn update_size(&mut self) -> Result<(), crate::Error> {
         match self.config.window_size.hint {
             WindowSizeHint::Fullscreen => {
                 let monitor = self
-                    .window
-                    .current_monitor()
-                    .ok_or("No monitor found!")
-                    .unwrap();
+                    .fullscreen_monitor()?
+                    .ok_or(crate::Error::NoMonitor)?;
+                let video_mode = self.fullscreen_video_mode(&monitor)?;
+                log::info!(
+                    "Exclusive fullscreen in {}",
+                    describe_video_mode(&video_mode)
+                );
                 self.window.set_fullscreen(Some(
-                    winit::window::Fullscreen::Exclusive(
-                        monitor
-                            .video_modes()
-                            .next()
-                            .ok_or("No video mode found!")
-                            .unwrap(),
-                    ),
+                    winit::window::Fullscreen::Exclusive(video_mode),
                 ));
                 self.is_fullscreen = true;
             }
             WindowSizeHint::FullscreenBorderless => {
-                let monitor = self.window.current_monitor();
+                let monitor = self.fullscreen_monitor()?;
                 self.window.set_fullscreen(Some(
                     winit::window::Fullscreen::Borderless(monitor),
                 ));
@@ -306,19 +960,253 @@ impl<'a> Renderer<'a> {
                 self.is_fullscreen = false;
             }
         }
+        Ok(())
+    }
+
+    /// Monitors connected, in the order of `--list-monitors`
+    pub fn available_monitors(&self) -> Vec<winit::monitor::MonitorHandle> {
+        self.window.available_monitors().collect()
+    }
+
+    /// The monitor selected in the configuration, else the one of the
+    /// window
+    pub fn fullscreen_monitor(
+        &self,
+    ) -> Result<Option<winit::monitor::MonitorHandle>, crate::Error> {
+        match &self.config.display.monitor {
+            Some(select) => {
+                let monitors = self.available_monitors();
+                find_monitor(&monitors, select)
+                    .map(|index| Some(monitors[index].clone()))
+                    .ok_or_else(|| {
+                        crate::Error::MonitorNotFound(select.clone())
+                    })
+            }
+            None => Ok(self.window.current_monitor()),
+        }
+    }
+
+    /// The video mode selected in the configuration, else the largest one
+    fn fullscreen_video_mode(
+        &self,
+        monitor: &winit::monitor::MonitorHandle,
+    ) -> Result<winit::monitor::VideoMode, crate::Error> {
+        let video_modes = monitor.video_modes().collect::<Vec<_>>();
+        let selection = self.config.display.video_mode;
+        find_video_mode(&video_modes, selection)
+            .map(|index| video_modes[index].clone())
+            .ok_or_else(|| match selection {
+                Some(selection) => {
+                    crate::Error::VideoModeNotFound(selection.to_string())
+                }
+                None => crate::Error::NoVideoMode,
+            })
+    }
+
+    pub fn display(&self) -> &DisplayConfig {
+        &self.config.display
+    }
+
+    /// Change the monitor, the video mode or the kind of fullscreen, applied
+    /// at once in fullscreen and kept unchanged on failure
+    pub fn set_display(
+        &mut self,
+        display: DisplayConfig,
+    ) -> Result<(), crate::Error> {
+        let previous = std::mem::replace(&mut self.config.display, display);
+        if !self.is_fullscreen {
+            return Ok(());
+        }
+        let hint = self.config.window_size.hint.clone();
+        self.config.window_size.hint = self.fullscreen_hint();
+        self.update_size().inspect_err(|_| {
+            self.config.display = previous;
+            self.config.window_size.hint = hint;
+        })
+    }
+
+    fn fullscreen_hint(&self) -> WindowSizeHint {
+        match self.config.display.fullscreen {
+            FullscreenMode::Borderless => WindowSizeHint::FullscreenBorderless,
+            FullscreenMode::Exclusive => WindowSizeHint::Fullscreen,
+        }
+    }
+
+    /// Delay after which the debug window must be drawn again, see
+    /// `DebugRenderer::repaint_delay`
+    pub fn debug_repaint_delay(&self) -> Option<std::time::Duration> {
+        self.debug_renderer
+            .as_ref()
+            .and_then(|debug_renderer| debug_renderer.repaint_delay())
     }
 
     pub fn fullscreen(&self) -> bool {
         self.is_fullscreen
     }
 
-    pub fn set_fullscreen(&mut self, value: bool) {
-        self.is_fullscreen = value;
+    /// Switch between windowed and the fullscreen of the configuration,
+    /// the window is left unchanged on failure
+    pub fn set_fullscreen(&mut self, value: bool) -> Result<(), crate::Error> {
+        let hint = self.config.window_size.hint.clone();
         if value {
-            self.config.window_size.hint = WindowSizeHint::Fullscreen;
+            self.config.window_size.hint = self.fullscreen_hint();
         } else {
             self.config.window_size.hint = WindowSizeHint::Windowed;
         }
-        self.update_size();
+        self.update_size().inspect_err(|_| {
+            self.config.window_size.hint = hint;
+        })
+    }
+}
+
+/// Index of the monitor selected by index or by part of its name
+pub fn find_monitor(
+    monitors: &[winit::monitor::MonitorHandle],
+    select: &str,
+) -> Option<usize> {
+    match select.parse::<usize>() {
+        Ok(index) => (index < monitors.len()).then_some(index),
+        Err(_) => {
+            let name = select.to_lowercase();
+            monitors.iter().position(|monitor| {
+                monitor.name().is_some_and(|monitor| {
+                    monitor.to_lowercase().contains(&name)
+                })
+            })
+        }
+    }
+}
+
+/// Index of the best video mode matching the selection: the largest one,
+/// then the one with the highest refresh rate and bit depth
+pub fn find_video_mode(
+    video_modes: &[winit::monitor::VideoMode],
+    selection: Option<VideoModeSelection>,
+) -> Option<usize> {
+    video_modes
+        .iter()
+        .enumerate()
+        .filter(|(index, video_mode)| {
+            let size = video_mode.size();
+            selection.is_none_or(|selection| {
+                selection.matches(
+                    *index,
+                    (size.width, size.height),
+                    video_mode.refresh_rate_millihertz(),
+                )
+            })
+        })
+        .max_by_key(|(_, video_mode)| {
+            let size = video_mode.size();
+            (
+                size.width as u64 * size.height as u64,
+                video_mode.refresh_rate_millihertz(),
+                video_mode.bit_depth(),
+            )
+        })
+        .map(|(index, _)| index)
+}
+
+pub fn describe_monitor(monitor: &winit::monitor::MonitorHandle) -> String {
+    let size = monitor.size();
+    format!(
+        "{} ({}x{})",
+        monitor.name().unwrap_or_else(|| "unknown".to_string()),
+        size.width,
+        size.height
+    )
+}
+
+pub fn describe_video_mode(video_mode: &winit::monitor::VideoMode) -> String {
+    let size = video_mode.size();
+    format!(
+        "{}x{} @ {:.3} Hz, {} bits",
+        size.width,
+        size.height,
+        video_mode.refresh_rate_millihertz() as f32 / 1000.0,
+        video_mode.bit_depth()
+    )
+}
+
+/// Describe the monitors and their video modes, in the order used to
+/// select them by index
+pub fn describe_monitors() -> Result<String, crate::Error> {
+    let event_loop = winit::event_loop::EventLoop::new()?;
+    let primary = event_loop.primary_monitor();
+    let monitors = event_loop.available_monitors().collect::<Vec<_>>();
+    if monitors.is_empty() {
+        return Ok("No monitor found\n".to_string());
+    }
+    Ok(monitors
+        .iter()
+        .enumerate()
+        .map(|(index, monitor)| {
+            let position = monitor.position();
+            let mut description = format!(
+                "[{}] {}{}\n    position: ({}, {}), scale factor: {}\n",
+                index,
+                describe_monitor(monitor),
+                if primary.as_ref() == Some(monitor) {
+                    " primary"
+                } else {
+                    ""
+                },
+                position.x,
+                position.y,
+                monitor.scale_factor()
+            );
+            for (index, video_mode) in monitor.video_modes().enumerate() {
+                description += &format!(
+                    "    [{}] {}\n",
+                    index,
+                    describe_video_mode(&video_mode)
+                );
+            }
+            description
+        })
+        .collect())
+}
+
+/// Describe the adapters of the backends, in the order used to select them
+/// by index
+pub fn describe_adapters(backends: wgpu::Backends) -> String {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends,
+        ..Default::default()
+    });
+    let adapters = instance.enumerate_adapters(backends);
+    if adapters.is_empty() {
+        return "No adapter found\n".to_string();
     }
+    adapters
+        .iter()
+        .enumerate()
+        .map(|(index, adapter)| {
+            let info = adapter.get_info();
+            let limits = adapter.limits();
+            let driver = [info.driver.as_str(), info.driver_info.as_str()]
+                .into_iter()
+                .filter(|part| !part.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            format!(
+                "[{}] {}\n    \
+                 backend: {:?}, type: {:?}, vendor: {:#06x}, device: {:#06x}\n    \
+                 driver: {}\n    \
+                 max texture size: {}, max buffer size: {}, \
+                 max bind groups: {}, max samplers: {}\n",
+                index,
+                info.name,
+                info.backend,
+                info.device_type,
+                info.vendor,
+                info.device,
+                if driver.is_empty() { "unknown" } else { &driver },
+                limits.max_texture_dimension_2d,
+                limits.max_buffer_size,
+                limits.max_bind_groups,
+                limits.max_samplers_per_shader_stage,
+            )
+        })
+        .collect()
 }
@@ -1,6 +1,10 @@
 use std::{cell::RefCell, rc::Rc};
 
-use crate::{debug::widget::debug::DebugItem, graphics::shapes::Shape};
+use crate::{
+    debug::widget::debug::DebugItem,
+    graphics::{points::PointCloud, shapes::Shape, Capabilities},
+    input::Input,
+};
 
 pub trait Scene {
     // ...
@@ -11,6 +15,25 @@ pub trait Scene {
 
     fn update(&mut self, dt: std::time::Duration);
 
+    /// Called with what the device can do once it has been created, and
+    /// again when it is recreated after a device loss
+    fn set_capabilities(&mut self, _capabilities: &Capabilities) {}
+
+    /// Whether the scene changes by itself and must be drawn again, only
+    /// asked in the reactive redraw mode
+    fn needs_redraw(&self) -> bool {
+        true
+    }
+
+    /// Actions of the scene with their default inputs, they can be bound
+    /// again by name in the `[keys]` section of the configuration
+    fn actions(&self) -> Vec<(String, Vec<Input>)> {
+        Vec::new()
+    }
+
+    /// Called when an input bound to one of `actions` is pressed or released
+    fn on_action(&mut self, _action: &str, _pressed: bool) {}
+
     /// Returns the shapes that are part of the scene
     /// This is used to load the shapes into the GPU
     /// and render them
@@ -21,4 +44,10 @@ pub trait Scene {
     fn shapes(&self) -> Vec<Rc<RefCell<Shape>>> {
         Vec::new()
     }
+
+    /// Returns the point clouds that are part of the scene
+    /// They are uploaded to the GPU whenever they change
+    fn point_clouds(&self) -> Vec<Rc<RefCell<PointCloud>>> {
+        Vec::new()
+    }
 }
@@ -0,0 +1,170 @@
+use std::time::{Duration, Instant};
+
+use crate::config::RedrawMode;
+
+/// Time slept in a busy loop before a frame, the timers of the event loop
+/// may wake up late by about a millisecond
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+/// What the event loop should do before the next frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pacing {
+    /// Draw a frame now
+    Redraw,
+    /// Draw a frame at this instant, too close to wait with the event loop
+    Spin(Instant),
+    /// Sleep until this instant, then ask again
+    WaitUntil(Instant),
+    /// Sleep until the next event
+    Wait,
+}
+
+/// Decides when the frames are drawn
+///
+/// Caps the frame rate to `max_fps` and, in the reactive mode, only draws
+/// the frames that have been requested.
+pub struct FramePacer {
+    mode: RedrawMode,
+    /// Shortest time between two frames, `None` when not limited
+    interval: Option<Duration>,
+    /// Earliest start of the next frame
+    next_frame: Instant,
+    /// Earliest frame requested since the last frame
+    requested: Option<Instant>,
+}
+
+impl FramePacer {
+    pub fn new(mode: RedrawMode, max_fps: u32) -> Self {
+        let mut pacer = Self {
+            mode,
+            interval: None,
+            next_frame: Instant::now(),
+            requested: Some(Instant::now()),
+        };
+        pacer.set_max_fps(max_fps);
+        pacer
+    }
+
+    pub fn set_mode(&mut self, mode: RedrawMode) {
+        self.mode = mode;
+    }
+
+    /// Highest number of frames per second, 0 does not limit them
+    pub fn set_max_fps(&mut self, max_fps: u32) {
+        self.interval = (max_fps > 0)
+            .then(|| Duration::from_secs_f64(1.0 / max_fps as f64));
+    }
+
+    /// Ask for a frame as soon as possible
+    pub fn request_redraw(&mut self) {
+        self.request_redraw_at(Instant::now());
+    }
+
+    /// Ask for a frame at `instant`, the earliest request wins
+    pub fn request_redraw_at(&mut self, instant: Instant) {
+        self.requested = Some(match self.requested {
+            Some(requested) => requested.min(instant),
+            None => instant,
+        });
+    }
+
+    /// Called when a frame starts to be drawn
+    pub fn frame_started(&mut self, now: Instant) {
+        self.requested = None;
+        if let Some(interval) = self.interval {
+            // Keep the cadence, unless a whole frame has been missed
+            self.next_frame = if now > self.next_frame + interval {
+                now + interval
+            } else {
+                self.next_frame + interval
+            };
+        }
+    }
+
+    /// What to do when the event loop is about to wait
+    ///
+    /// `animating` is set when something changes by itself (camera moving,
+    /// animated scene), a frame is then drawn even in the reactive mode.
+    pub fn poll(&self, now: Instant, animating: bool) -> Pacing {
+        let wanted = match self.mode {
+            RedrawMode::Continuous => Some(now),
+            RedrawMode::Reactive if animating => Some(now),
+            RedrawMode::Reactive => self.requested,
+        };
+        let Some(wanted) = wanted else {
+            return Pacing::Wait;
+        };
+        let at = match self.interval {
+            Some(_) => wanted.max(self.next_frame),
+            None => wanted,
+        };
+        if at <= now {
+            Pacing::Redraw
+        } else if at - now <= SPIN_MARGIN {
+            Pacing::Spin(at)
+        } else {
+            Pacing::WaitUntil(at - SPIN_MARGIN)
+        }
+    }
+}
+
+/// Busy wait until `instant`, for the last moments before a frame
+pub fn spin_until(instant: Instant) {
+    while Instant::now() < instant {
+        std::thread::yield_now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cap() {
+        let now = Instant::now();
+        let mut pacer = FramePacer::new(RedrawMode::Continuous, 50);
+        pacer.next_frame = now;
+        assert_eq!(pacer.poll(now, false), Pacing::Redraw);
+
+        pacer.frame_started(now);
+        let next = now + Duration::from_millis(20);
+        assert_eq!(
+            pacer.poll(now, false),
+            Pacing::WaitUntil(next - SPIN_MARGIN)
+        );
+        assert_eq!(pacer.poll(next - SPIN_MARGIN, false), Pacing::Spin(next));
+        assert_eq!(pacer.poll(next, false), Pacing::Redraw);
+
+        // A late frame does not make the next ones hurry
+        let late = now + Duration::from_millis(100);
+        pacer.frame_started(late);
+        assert_eq!(
+            pacer.poll(late, false),
+            Pacing::WaitUntil(late + Duration::from_millis(20) - SPIN_MARGIN)
+        );
+
+        pacer.set_max_fps(0);
+        assert_eq!(pacer.poll(late, false), Pacing::Redraw);
+    }
+
+    #[test]
+    fn test_reactive() {
+        let mut pacer = FramePacer::new(RedrawMode::Reactive, 0);
+        let now = Instant::now();
+        // The first frame is always drawn
+        assert_eq!(pacer.poll(now, false), Pacing::Redraw);
+
+        pacer.frame_started(now);
+        assert_eq!(pacer.poll(now, false), Pacing::Wait);
+        assert_eq!(pacer.poll(now, true), Pacing::Redraw);
+
+        let later = now + Duration::from_millis(500);
+        pacer.request_redraw_at(later);
+        pacer.request_redraw_at(later + Duration::from_secs(1));
+        assert_eq!(
+            pacer.poll(now, false),
+            Pacing::WaitUntil(later - SPIN_MARGIN)
+        );
+        assert_eq!(pacer.poll(later, false), Pacing::Redraw);
+    }
+}
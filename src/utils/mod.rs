@@ -1,5 +1,8 @@
 mod circular_buffer;
+mod frame_pacer;
 
 pub mod lisp;
+pub mod point_cloud;
 pub mod shape;
 pub use circular_buffer::CircularBuffer;
+pub use frame_pacer::{spin_until, FramePacer, Pacing};
@@ -0,0 +1,428 @@
+//! Readers for the `.ply` and `.xyz` point cloud formats
+//!
+//! Only the position and the color of the vertices are read, faces and
+//! other elements are ignored.
+
+use std::error::Error;
+
+/// A point read from a file, the color defaults to white
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RawPoint {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PlyType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+#[derive(Debug)]
+struct PlyProperty {
+    name: String,
+    ty: PlyType,
+    /// Type of the length of a list property
+    list: Option<PlyType>,
+}
+
+#[derive(Debug)]
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(match name {
+            "char" | "int8" => Self::Int8,
+            "uchar" | "uint8" => Self::UInt8,
+            "short" | "int16" => Self::Int16,
+            "ushort" | "uint16" => Self::UInt16,
+            "int" | "int32" => Self::Int32,
+            "uint" | "uint32" => Self::UInt32,
+            "float" | "float32" => Self::Float32,
+            "double" | "float64" => Self::Float64,
+            _ => return Err(format!("Unknown PLY type {}", name).into()),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::Int8 | Self::UInt8 => 1,
+            Self::Int16 | Self::UInt16 => 2,
+            Self::Int32 | Self::UInt32 | Self::Float32 => 4,
+            Self::Float64 => 8,
+        }
+    }
+
+    /// Scale applied to a color channel to bring it into `[0, 1]`
+    fn color_scale(&self) -> f64 {
+        match self {
+            Self::Int8 | Self::UInt8 => 1.0 / 255.0,
+            Self::Int16 | Self::UInt16 => 1.0 / 65535.0,
+            Self::Int32 | Self::UInt32 => 1.0 / u32::MAX as f64,
+            Self::Float32 | Self::Float64 => 1.0,
+        }
+    }
+
+    fn read(
+        &self,
+        bytes: &[u8],
+        format: PlyFormat,
+    ) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        let bytes = bytes
+            .get(..self.size())
+            .ok_or("Unexpected end of PLY data")?;
+        macro_rules! read {
+            ($ty: ty) => {{
+                let bytes = bytes.try_into()?;
+                (if format == PlyFormat::BinaryBigEndian {
+                    <$ty>::from_be_bytes(bytes)
+                } else {
+                    <$ty>::from_le_bytes(bytes)
+                }) as f64
+            }};
+        }
+        Ok(match self {
+            Self::Int8 => read!(i8),
+            Self::UInt8 => read!(u8),
+            Self::Int16 => read!(i16),
+            Self::UInt16 => read!(u16),
+            Self::Int32 => read!(i32),
+            Self::UInt32 => read!(u32),
+            Self::Float32 => read!(f32),
+            Self::Float64 => read!(f64),
+        })
+    }
+}
+
+/// Read the vertices of a `.ply` file, in ASCII or binary format
+pub fn parse_ply(
+    data: &[u8],
+) -> Result<Vec<RawPoint>, Box<dyn Error + Send + Sync>> {
+    let end_header = data
+        .windows(10)
+        .position(|window| window == b"end_header")
+        .ok_or("Missing PLY end_header")?;
+    // The body starts after the end of the line, which may end with "\r\n"
+    let header_end = data[end_header..]
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .map_or(data.len(), |position| end_header + position + 1);
+    let header = std::str::from_utf8(&data[..header_end])?;
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err("Not a PLY file".into());
+    }
+
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    for line in lines {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["format", "ascii", _] => format = Some(PlyFormat::Ascii),
+            ["format", "binary_little_endian", _] => {
+                format = Some(PlyFormat::BinaryLittleEndian)
+            }
+            ["format", "binary_big_endian", _] => {
+                format = Some(PlyFormat::BinaryBigEndian)
+            }
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count.parse()?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", length, ty, name] => elements
+                .last_mut()
+                .ok_or("PLY property outside of an element")?
+                .properties
+                .push(PlyProperty {
+                    name: name.to_string(),
+                    ty: PlyType::parse(ty)?,
+                    list: Some(PlyType::parse(length)?),
+                }),
+            ["property", ty, name] => elements
+                .last_mut()
+                .ok_or("PLY property outside of an element")?
+                .properties
+                .push(PlyProperty {
+                    name: name.to_string(),
+                    ty: PlyType::parse(ty)?,
+                    list: None,
+                }),
+            _ => {}
+        }
+    }
+    let format = format.ok_or("Missing PLY format")?;
+    let body = &data[header_end..];
+
+    let mut points = Vec::new();
+    match format {
+        PlyFormat::Ascii => {
+            let mut lines = std::str::from_utf8(body)?
+                .lines()
+                .filter(|line| !line.trim().is_empty());
+            for element in &elements {
+                for _ in 0..element.count {
+                    let line = lines.next().ok_or("Unexpected end of PLY")?;
+                    if element.name != "vertex" {
+                        continue;
+                    }
+                    let mut words = line.split_whitespace();
+                    let mut next =
+                        || -> Result<f64, Box<dyn Error + Send + Sync>> {
+                            Ok(words
+                                .next()
+                                .ok_or("Missing PLY property value")?
+                                .parse::<f64>()?)
+                        };
+                    let mut values =
+                        Vec::with_capacity(element.properties.len());
+                    for property in &element.properties {
+                        match property.list {
+                            // The length is followed by the items, which
+                            // are skipped
+                            Some(_) => {
+                                let count = next()? as usize;
+                                for _ in 0..count {
+                                    next()?;
+                                }
+                                values.push(0.0);
+                            }
+                            None => values.push(next()?),
+                        }
+                    }
+                    points.push(ply_point(&element.properties, &values)?);
+                }
+            }
+        }
+        PlyFormat::BinaryLittleEndian | PlyFormat::BinaryBigEndian => {
+            let mut offset = 0;
+            for element in &elements {
+                for _ in 0..element.count {
+                    let mut values =
+                        Vec::with_capacity(element.properties.len());
+                    for property in &element.properties {
+                        match property.list {
+                            Some(length) => {
+                                let count = length.read(
+                                    &body[offset.min(body.len())..],
+                                    format,
+                                )?
+                                    as usize;
+                                offset = count
+                                    .checked_mul(property.ty.size())
+                                    .and_then(|size| {
+                                        size.checked_add(length.size())
+                                    })
+                                    .and_then(|size| offset.checked_add(size))
+                                    .ok_or("PLY list too long")?;
+                                values.push(0.0);
+                            }
+                            None => {
+                                values.push(property.ty.read(
+                                    &body[offset.min(body.len())..],
+                                    format,
+                                )?);
+                                offset += property.ty.size();
+                            }
+                        }
+                    }
+                    if element.name == "vertex" {
+                        points.push(ply_point(&element.properties, &values)?);
+                    }
+                }
+            }
+        }
+    }
+    Ok(points)
+}
+
+/// Build a point from the values of the properties of a vertex
+fn ply_point(
+    properties: &[PlyProperty],
+    values: &[f64],
+) -> Result<RawPoint, Box<dyn Error + Send + Sync>> {
+    let mut point = RawPoint {
+        position: [0.0; 3],
+        color: [1.0; 3],
+    };
+    let mut has_position = [false; 3];
+    for (property, value) in properties.iter().zip(values) {
+        match property.name.as_str() {
+            "x" | "y" | "z" => {
+                let axis = (property.name.as_bytes()[0] - b'x') as usize;
+                point.position[axis] = *value as f32;
+                has_position[axis] = true;
+            }
+            "red" | "green" | "blue" => {
+                let channel = match property.name.as_str() {
+                    "red" => 0,
+                    "green" => 1,
+                    _ => 2,
+                };
+                point.color[channel] =
+                    (value * property.ty.color_scale()) as f32;
+            }
+            _ => {}
+        }
+    }
+    if has_position.contains(&false) {
+        return Err("PLY vertex without x, y and z".into());
+    }
+    Ok(point)
+}
+
+/// Read a `.xyz` file: one `x y z [r g b]` point per line
+///
+/// Colors are expected in `[0, 1]`, unless one of them is greater than 1 in
+/// which case every color of the file is read in `[0, 255]`.
+pub fn parse_xyz(
+    text: &str,
+) -> Result<Vec<RawPoint>, Box<dyn Error + Send + Sync>> {
+    let mut points = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let values = line
+            .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+            .filter(|value| !value.is_empty())
+            .map(str::parse::<f32>)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Line {}: {}", i + 1, e))?;
+        let position = match values.as_slice() {
+            [x, y, z, ..] => [*x, *y, *z],
+            _ => return Err(format!("Line {}: expected x y z", i + 1).into()),
+        };
+        let color = match values.as_slice() {
+            [_, _, _, r, g, b, ..] => Some([*r, *g, *b]),
+            _ => None,
+        };
+        points.push((position, color));
+    }
+    let scale = if points
+        .iter()
+        .filter_map(|(_, color)| *color)
+        .any(|color| color.iter().any(|channel| *channel > 1.0))
+    {
+        1.0 / 255.0
+    } else {
+        1.0
+    };
+    let points = points
+        .into_iter()
+        .map(|(position, color)| RawPoint {
+            position,
+            color: color.map_or([1.0; 3], |color| color.map(|c| c * scale)),
+        })
+        .collect();
+    Ok(points)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ply_ascii() {
+        let data = b"ply
+format ascii 1.0
+comment test
+element vertex 2
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 1 2 255 0 0
+3.5 4 5 0 255 0
+3 0 1 0
+";
+        let points = parse_ply(data).unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[1].position, [3.5, 4.0, 5.0]);
+        assert_eq!(points[0].color, [1.0, 0.0, 0.0]);
+
+        // A list in the vertex element does not shift the next properties
+        let data = b"ply
+format ascii 1.0
+element vertex 1
+property float x
+property list uchar int indices
+property float y
+property float z
+property uchar red
+end_header
+1 3 7 8 9 2 3 255
+";
+        let points = parse_ply(data).unwrap();
+        assert_eq!(points[0].position, [1.0, 2.0, 3.0]);
+        assert_eq!(points[0].color, [1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_parse_ply_binary() {
+        let mut data = b"ply
+format binary_little_endian 1.0
+element vertex 2
+property float x
+property float y
+property float z
+end_header
+"
+        .to_vec();
+        for value in [1.0f32, 2.0, 3.0, -1.0, -2.0, -3.0] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        let points = parse_ply(&data).unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[1].position, [-1.0, -2.0, -3.0]);
+        assert_eq!(points[1].color, [1.0; 3]);
+
+        // Truncated data
+        assert!(parse_ply(&data[..data.len() - 2]).is_err());
+
+        // A list longer than the data
+        let mut data = b"ply
+format binary_little_endian 1.0
+element vertex 1
+property list uint double indices
+property float x
+end_header
+"
+        .to_vec();
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_ply(&data).is_err());
+    }
+
+    #[test]
+    fn test_parse_xyz() {
+        let points = parse_xyz("# comment\n1 2 3\n4,5,6,255,0,0\n").unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].color, [1.0; 3]);
+        assert_eq!(points[1].position, [4.0, 5.0, 6.0]);
+        assert_eq!(points[1].color, [1.0, 0.0, 0.0]);
+        assert!(parse_xyz("1 2\n").is_err());
+    }
+}